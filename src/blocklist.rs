@@ -0,0 +1,56 @@
+use regex::bytes::{Regex, RegexSet};
+use std::{fs, path::Path};
+
+// --- Blocklist ---
+// Content moderation with RBIN_BLOCKLIST_FILE, one pattern per line: a
+// substring, matched ignoring case, or a regex between slashes like
+// `/free \w+ crypto/`. Empty lines and lines starting with `#` are skipped.
+// All patterns are compiled into one set, so a paste is scanned once however
+// long the list is, and only its first `SCAN_LEN` bytes.
+
+/// Bytes of a paste that are checked, the rest is let through.
+pub const SCAN_LEN: usize = 1024 * 1024;
+
+#[derive(Debug)]
+pub struct Blocklist {
+    set: RegexSet,
+    // As written in the file, for the logs
+    patterns: Vec<String>,
+}
+
+impl Blocklist {
+    /// Reads and compiles the patterns of `path`.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let list = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let patterns: Vec<String> = list
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+        let regexes = patterns.iter().enumerate().map(|(index, pattern)| {
+            let regex = match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+                Some(regex) if !regex.is_empty() => regex.to_string(),
+                _ => format!("(?i){}", regex::escape(pattern)),
+            };
+            // Checked one by one first, the set's error doesn't tell which line
+            Regex::new(&regex)
+                .map(|_| regex)
+                .map_err(|e| format!("pattern {} '{}': {}", index + 1, pattern, e))
+        });
+        let regexes = regexes.collect::<Result<Vec<_>, _>>()?;
+        let set = RegexSet::new(regexes).map_err(|e| e.to_string())?;
+        Ok(Self { set, patterns })
+    }
+
+    /// The first pattern found in `content`, if any.
+    pub fn find(&self, content: &[u8]) -> Option<&str> {
+        let content = &content[..content.len().min(SCAN_LEN)];
+        let index = self.set.matches(content).into_iter().next()?;
+        Some(&self.patterns[index])
+    }
+
+    pub fn pattern_count(&self) -> usize {
+        self.patterns.len()
+    }
+}
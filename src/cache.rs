@@ -0,0 +1,47 @@
+use crate::meta::PasteMeta;
+use axum::body::Bytes;
+use lru::LruCache;
+use std::{num::NonZeroUsize, sync::Mutex, time::SystemTime};
+
+// --- Paste Cache ---
+// Keeps the content and metadata of recently read pastes in memory so popular
+// pastes don't hit the disk on every request. Anything that changes or removes
+// a paste on disk must `invalidate` it here.
+#[derive(Debug)]
+pub struct PasteCache {
+    entries: Mutex<LruCache<String, CachedPaste>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedPaste {
+    pub content: Bytes,
+    pub meta: Option<PasteMeta>,
+    /// See `PasteFile::version`.
+    pub version: String,
+    pub modified: Option<SystemTime>,
+}
+
+impl PasteCache {
+    /// Creates a cache holding up to `capacity` pastes, or `None` for a capacity of zero.
+    pub fn new(capacity: usize) -> Option<Self> {
+        NonZeroUsize::new(capacity).map(|capacity| Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Option<CachedPaste> {
+        self.lock().get(id).cloned()
+    }
+
+    pub fn insert(&self, id: &str, paste: CachedPaste) {
+        self.lock().put(id.to_string(), paste);
+    }
+
+    pub fn invalidate(&self, id: &str) {
+        self.lock().pop(id);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LruCache<String, CachedPaste>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
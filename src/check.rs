@@ -0,0 +1,134 @@
+use crate::{
+    cli::Args,
+    config::{Config, Storage},
+};
+use axum_server::tls_rustls::RustlsConfig;
+use std::fmt::Display;
+
+// --- Config Check ---
+// `rbin --check` validates the settings and what they point at without
+// starting the server, for CI and deploy pipelines: one line per check, then
+// whether all of them passed. Nothing is bound, but the paste directory or
+// database is created if missing, as on startup, and RBIN_PASTE_DIR gets a
+// probe file for a moment.
+
+const PROBE_FILE: &str = ".rbin-check";
+
+/// Runs all checks, printing the report. Returns whether they all passed.
+pub async fn run(args: &Args) -> bool {
+    let mut report = Report::default();
+    let config = match Config::from_args(args) {
+        Ok(config) => {
+            report.pass("Configuration", "all settings are valid");
+            config
+        }
+        Err(e) => {
+            // Nothing else can be checked without it
+            report.fail("Configuration", e);
+            return report.finish();
+        }
+    };
+
+    match &config.unix_socket {
+        Some(path) => {
+            let dir = path
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(".".as_ref());
+            if dir.is_dir() {
+                report.pass("Listen", format!("Unix socket {}", path.display()));
+            } else {
+                report.fail(
+                    "Listen",
+                    format!("directory {} of the socket doesn't exist", dir.display()),
+                );
+            }
+        }
+        None => {
+            let addrs: Vec<String> = config
+                .hosts
+                .iter()
+                .map(|host| std::net::SocketAddr::from((*host, config.port)).to_string())
+                .collect();
+            report.pass("Listen", addrs.join(", "));
+        }
+    }
+
+    if let Some((cert, key)) = &config.tls {
+        // Only one crypto provider is compiled in, so this can't conflict
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        match RustlsConfig::from_pem_file(cert, key).await {
+            Ok(_) => report.pass("TLS", format!("certificate {} and key {} load", cert, key)),
+            Err(e) => report.fail(
+                "TLS",
+                format!("could not load certificate {} or key {}: {}", cert, key, e),
+            ),
+        }
+    }
+
+    if matches!(config.storage, Storage::Filesystem) {
+        let probe = config.paste_dir.join(PROBE_FILE);
+        let written = match tokio::fs::create_dir_all(&config.paste_dir).await {
+            Ok(()) => tokio::fs::write(&probe, b"rbin").await,
+            Err(e) => Err(e),
+        };
+        match written {
+            Ok(()) => {
+                let _ = tokio::fs::remove_file(&probe).await;
+                report.pass(
+                    "Paste directory",
+                    format!("{} is writable", config.paste_dir.display()),
+                );
+            }
+            Err(e) => report.fail(
+                "Paste directory",
+                format!("{} is not writable: {}", config.paste_dir.display(), e),
+            ),
+        }
+    }
+
+    let name = match &config.storage {
+        Storage::Filesystem => format!("filesystem store {}", config.paste_dir.display()),
+        Storage::Memory => "in-memory store".to_string(),
+        Storage::S3(s3) => format!("S3 bucket {}", s3.bucket),
+        Storage::Sqlite(path) => format!("SQLite database {}", path.display()),
+    };
+    let encrypted = config.cipher.is_some();
+    match crate::open_store(config.storage, config.paste_dir, encrypted).await {
+        Ok(store) => match store.list().await {
+            Ok(ids) => report.pass(
+                "Storage",
+                format!("{} is reachable, {} pastes stored", name, ids.len()),
+            ),
+            Err(e) => report.fail("Storage", format!("could not list {}: {}", name, e)),
+        },
+        Err(e) => report.fail("Storage", e),
+    }
+
+    report.finish()
+}
+
+#[derive(Default)]
+struct Report {
+    failed: usize,
+}
+
+impl Report {
+    fn pass(&mut self, check: &str, detail: impl Display) {
+        println!("ok    {}: {}", check, detail);
+    }
+
+    fn fail(&mut self, check: &str, detail: impl Display) {
+        self.failed += 1;
+        println!("FAIL  {}: {}", check, detail);
+    }
+
+    fn finish(self) -> bool {
+        match self.failed {
+            0 => println!("\nAll checks passed"),
+            1 => println!("\n1 check failed"),
+            failed => println!("\n{} checks failed", failed),
+        }
+        self.failed == 0
+    }
+}
@@ -0,0 +1,110 @@
+use clap::{CommandFactory, Parser};
+use std::collections::HashMap;
+
+// --- Command Line ---
+// The most common settings as options, for quick local runs, and any other
+// with `--set`. They take precedence over the environment and the config
+// file, see `Source`.
+
+/// rbin, a minimal pastebin. Every option can also be set as an RBIN_*
+/// environment variable or in the RBIN_CONFIG file, see GET / for all of them.
+#[derive(Debug, Default, Parser)]
+#[command(version)]
+pub struct Args {
+    /// Validate the configuration, TLS files and storage, then exit
+    #[arg(long)]
+    pub check: bool,
+    /// Config file with more settings (RBIN_CONFIG)
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<String>,
+    /// IP address to listen on, or a comma-separated list (RBIN_HOST)
+    #[arg(long)]
+    pub host: Option<String>,
+    /// Port to listen on, 0 for a free one (RBIN_PORT)
+    #[arg(short, long)]
+    pub port: Option<String>,
+    /// Listen on a Unix socket instead of TCP (RBIN_UNIX_SOCKET)
+    #[arg(long, value_name = "PATH")]
+    pub unix_socket: Option<String>,
+    /// Where pastes are kept: fs, memory, s3 or sqlite (RBIN_STORAGE)
+    #[arg(long)]
+    pub storage: Option<String>,
+    /// Directory for storing pastes (RBIN_PASTE_DIR)
+    #[arg(long, value_name = "DIR")]
+    pub paste_dir: Option<String>,
+    /// Database file with --storage sqlite (RBIN_DB_PATH)
+    #[arg(long, value_name = "FILE")]
+    pub db_path: Option<String>,
+    /// Public URL prefix for paste links (RBIN_BASE_URL)
+    #[arg(long, value_name = "URL")]
+    pub base_url: Option<String>,
+    /// Maximum paste size, e.g. 512k or 5M (RBIN_MAX_SIZE)
+    #[arg(long, value_name = "SIZE")]
+    pub max_size: Option<String>,
+    /// Lifetime of pastes without an 'expires' field, e.g. 7d (RBIN_DEFAULT_TTL)
+    #[arg(long, value_name = "DURATION")]
+    pub default_ttl: Option<String>,
+    /// PEM certificate chain, serves HTTPS with --tls-key (RBIN_TLS_CERT)
+    #[arg(long, value_name = "FILE")]
+    pub tls_cert: Option<String>,
+    /// PEM private key for --tls-cert (RBIN_TLS_KEY)
+    #[arg(long, value_name = "FILE")]
+    pub tls_key: Option<String>,
+    /// Any other setting, e.g. `--set max_lines=1000` for RBIN_MAX_LINES
+    #[arg(short, long = "set", value_name = "KEY=VALUE", value_parser = parse_setting)]
+    pub settings: Vec<(String, String)>,
+}
+
+impl Args {
+    /// The given options by setting key, e.g. `paste_dir`.
+    pub fn settings(&self) -> HashMap<String, String> {
+        let options = [
+            ("config", &self.config),
+            ("host", &self.host),
+            ("port", &self.port),
+            ("unix_socket", &self.unix_socket),
+            ("storage", &self.storage),
+            ("paste_dir", &self.paste_dir),
+            ("db_path", &self.db_path),
+            ("base_url", &self.base_url),
+            ("max_size", &self.max_size),
+            ("default_ttl", &self.default_ttl),
+            ("tls_cert", &self.tls_cert),
+            ("tls_key", &self.tls_key),
+        ];
+        // The named options win over the same setting given with `--set`
+        let mut settings: HashMap<String, String> = self.settings.iter().cloned().collect();
+        for (key, value) in options {
+            if let Some(value) = value {
+                settings.insert(key.to_string(), value.clone());
+            }
+        }
+        settings
+    }
+}
+
+/// How setting `key` is given on the command line, for errors: `--port`, or
+/// `--set max_lines` for those without an option of their own.
+pub fn option_name(key: &str) -> String {
+    let long = key.replace('_', "-");
+    let named = Args::command()
+        .get_arguments()
+        .any(|arg| arg.get_long() == Some(long.as_str()));
+    match named {
+        true => format!("--{}", long),
+        false => format!("--set {}", key),
+    }
+}
+
+/// A `--set` value such as `max_lines=1000` or `RBIN_MAX_LINES=1000`.
+fn parse_setting(value: &str) -> Result<(String, String), String> {
+    let (key, value) = value
+        .split_once('=')
+        .ok_or("expected KEY=VALUE, e.g. max_lines=1000")?;
+    let key = key.trim().to_ascii_lowercase();
+    let key = key.strip_prefix("rbin_").unwrap_or(&key).replace('-', "_");
+    if key.is_empty() {
+        return Err("the key is missing, expected KEY=VALUE".to_string());
+    }
+    Ok((key, value.to_string()))
+}
@@ -0,0 +1,739 @@
+use crate::{
+    blocklist::Blocklist,
+    cli::{self, Args},
+    encryption::Cipher,
+    highlight,
+    id_alphabet::IdAlphabet,
+    id_scheme::IdScheme,
+    ip_filter::IpFilter,
+    meta,
+    rate_limit::RateLimiter,
+    storage::S3Config,
+    DEFAULT_CACHE_ENTRIES, DEFAULT_CLEANUP_INTERVAL, DEFAULT_DB_PATH, DEFAULT_FIELD_NAME,
+    DEFAULT_HOST, DEFAULT_ID_LENGTH, DEFAULT_MAX_BATCH, DEFAULT_MAX_SIZE, DEFAULT_PASTE_DIR,
+    DEFAULT_PORT, DEFAULT_REQUEST_LOG_LEVEL, DEFAULT_REQUEST_TIMEOUT, DEFAULT_S3_REGION,
+    DEFAULT_UNIX_SOCKET_MODE, FORM_FIELDS, MAX_ID_LENGTH, MIN_ID_LENGTH,
+};
+use axum::http::{HeaderName, HeaderValue};
+use std::{collections::HashMap, env, fmt::Display, net::IpAddr, path::PathBuf, time::Duration};
+
+// --- Configuration ---
+// Every setting is an `RBIN_*` environment variable, and may also be given in
+// a TOML or JSON file named by RBIN_CONFIG. The file uses the variable names
+// without the prefix in lowercase, i.e. `port = 8080` for RBIN_PORT.
+// Environment variables take precedence over the file, and command line
+// options (see `Args`) over both.
+
+/// Where settings are read from, see `Source::get`.
+pub struct Source {
+    args: HashMap<String, String>,
+    file: HashMap<String, String>,
+    // Unset for `from_map`, which only has the settings it was given
+    env: bool,
+}
+
+impl Source {
+    /// Reads the config file named by RBIN_CONFIG, if any.
+    pub fn load() -> Result<Self, String> {
+        Self::with_args(HashMap::new())
+    }
+
+    /// Like `load`, with settings from the command line by key (`port`) that
+    /// override all others. They may name the config file as `config`.
+    pub fn with_args(args: HashMap<String, String>) -> Result<Self, String> {
+        let Some(path) = args
+            .get("config")
+            .cloned()
+            .or_else(|| env::var("RBIN_CONFIG").ok())
+            .filter(|p| !p.trim().is_empty())
+        else {
+            return Ok(Self {
+                args,
+                file: HashMap::new(),
+                env: true,
+            });
+        };
+        let data = std::fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read config file {}: {}", path, e))?;
+        let file = if path.ends_with(".json") {
+            let values: HashMap<String, serde_json::Value> = serde_json::from_str(&data)
+                .map_err(|e| format!("invalid config file {}: {}", path, e))?;
+            values
+                .into_iter()
+                .map(|(key, value)| Ok((key.clone(), json_setting(&key, value)?)))
+                .collect::<Result<_, String>>()?
+        } else {
+            let values: toml::Table = toml::from_str(&data)
+                .map_err(|e| format!("invalid config file {}: {}", path, e))?;
+            values
+                .into_iter()
+                .map(|(key, value)| Ok((key.clone(), toml_setting(&key, value)?)))
+                .collect::<Result<_, String>>()?
+        };
+        Ok(Self {
+            args,
+            file,
+            env: true,
+        })
+    }
+
+    /// Just the given settings by key (`port`), as if from a config file,
+    /// without the environment. For embedding rbin and for tests.
+    pub fn from_map(settings: HashMap<String, String>) -> Self {
+        Self {
+            args: HashMap::new(),
+            file: settings,
+            env: false,
+        }
+    }
+
+    /// The value of setting `key` (e.g. `port`): `--port` if given, else
+    /// RBIN_PORT if set, else the config file's `port`.
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.args
+            .get(key)
+            .cloned()
+            .or_else(|| {
+                let name = format!("RBIN_{}", key.to_ascii_uppercase());
+                env::var(name).ok().filter(|_| self.env)
+            })
+            .or_else(|| self.file.get(key).cloned())
+    }
+
+    /// How setting `key` was given, for errors: `--port` or RBIN_PORT.
+    fn name(&self, key: &str) -> String {
+        match self.args.contains_key(key) {
+            true => cli::option_name(key),
+            false => format!("RBIN_{}", key.to_ascii_uppercase()),
+        }
+    }
+}
+
+// Config file values are handled like the strings of environment variables.
+// Lists are joined with commas, e.g. for `cors_origins`.
+fn toml_setting(key: &str, value: toml::Value) -> Result<String, String> {
+    match value {
+        toml::Value::String(s) => Ok(s),
+        toml::Value::Integer(n) => Ok(n.to_string()),
+        toml::Value::Float(n) => Ok(n.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        toml::Value::Array(values) => values
+            .into_iter()
+            .map(|value| toml_setting(key, value))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|values| values.join(",")),
+        _ => Err(format!("unsupported value for '{}' in config file", key)),
+    }
+}
+
+fn json_setting(key: &str, value: serde_json::Value) -> Result<String, String> {
+    match value {
+        serde_json::Value::String(s) => Ok(s),
+        serde_json::Value::Number(n) => Ok(n.to_string()),
+        serde_json::Value::Bool(b) => Ok(b.to_string()),
+        serde_json::Value::Array(values) => values
+            .into_iter()
+            .map(|value| json_setting(key, value))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|values| values.join(",")),
+        _ => Err(format!("unsupported value for '{}' in config file", key)),
+    }
+}
+
+/// Where pastes are kept, see RBIN_STORAGE.
+#[derive(Debug, Clone)]
+pub enum Storage {
+    /// Files in RBIN_PASTE_DIR
+    Filesystem,
+    /// Gone with a restart
+    Memory,
+    /// Objects in a bucket, see RBIN_S3_*
+    S3(S3Config),
+    /// A database file, RBIN_DB_PATH
+    Sqlite(PathBuf),
+}
+
+/// All settings of the server, see the help text of `GET /` for their meaning.
+#[derive(Debug)]
+pub struct Config {
+    pub hosts: Vec<IpAddr>,
+    pub port: u16,
+    /// Gets the bound addresses, for RBIN_PORT=0
+    pub port_file: Option<PathBuf>,
+    /// Listen here instead of on TCP
+    pub unix_socket: Option<PathBuf>,
+    /// Permissions of the socket file
+    pub unix_socket_mode: u32,
+    pub storage: Storage,
+    pub paste_dir: PathBuf,
+    pub id_length: usize,
+    pub id_alphabet: IdAlphabet,
+    pub id_scheme: IdScheme,
+    /// Put in front of generated IDs, required of custom ones
+    pub id_prefix: String,
+    pub max_size: usize,
+    pub max_lines: Option<u64>,
+    /// In characters
+    pub max_line_length: Option<u64>,
+    /// In characters, without leading and trailing whitespace
+    pub min_length: Option<u64>,
+    /// Store empty pastes instead of rejecting them
+    pub allow_empty: bool,
+    /// Form field of the paste content
+    pub field_name: String,
+    pub default_ttl: Option<Duration>,
+    /// Pastes per request of `POST /batch`
+    pub max_batch: u64,
+    pub rate_limit: Option<RateLimiter>,
+    /// Who may create pastes
+    pub ip_filter: Option<IpFilter>,
+    /// Patterns new pastes must not contain
+    pub blocklist: Option<Blocklist>,
+    pub compress: bool,
+    /// Encrypts new pastes at rest
+    pub cipher: Option<Cipher>,
+    pub allow_html: bool,
+    /// Syntect theme of the HTML view
+    pub highlight_theme: String,
+    /// For browsers preferring a light color scheme
+    pub light_theme: Option<String>,
+    /// Hide the help text and upload form of `GET /`
+    pub disable_index: bool,
+    /// Let search engines index pastes, see RBIN_ROBOTS
+    pub allow_robots: bool,
+    /// Reject creating, editing and deleting pastes
+    pub read_only: bool,
+    pub cache_entries: usize,
+    pub base_url: Option<String>,
+    pub cleanup_interval: Option<Duration>,
+    /// Time to answer a request, and between two chunks of an upload
+    pub request_timeout: Option<Duration>,
+    /// Certificate chain and private key
+    pub tls: Option<(String, String)>,
+    pub trust_proxy: bool,
+    pub cors_origins: Option<String>,
+    /// Set on every response, an empty value drops one of the defaults
+    pub extra_headers: Vec<(HeaderName, HeaderValue)>,
+    pub max_total_bytes: Option<u64>,
+    /// Evict the oldest pastes beyond this many
+    pub max_pastes: Option<u64>,
+    pub admin_token: Option<String>,
+    pub webhook_url: Option<String>,
+    /// Used for `tower_http` unless RUST_LOG is set
+    pub request_log_level: String,
+    /// Log as JSON lines rather than human-readable text
+    pub json_logs: bool,
+}
+
+impl Config {
+    /// Reads and validates all settings from the environment and the RBIN_CONFIG
+    /// file. Errors name the offending setting.
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_source(&Source::load()?)
+    }
+
+    /// Like `from_env`, with the command line options taking precedence.
+    pub fn from_args(args: &Args) -> Result<Self, String> {
+        Self::from_source(&Source::with_args(args.settings())?)
+    }
+
+    pub fn from_source(source: &Source) -> Result<Self, String> {
+        let hosts = setting(source, "host", parse_hosts)?
+            .unwrap_or_else(|| vec![DEFAULT_HOST.parse().unwrap()]);
+        let port = setting(source, "port", |v| v.parse::<u16>())?.unwrap_or(DEFAULT_PORT);
+        let port_file = setting(source, "port_file", |v| Ok::<_, String>(PathBuf::from(v)))?;
+        let storage = match setting(source, "storage", |v| {
+            match v.to_ascii_lowercase().as_str() {
+                "fs" | "filesystem" => Ok("fs"),
+                "memory" => Ok("memory"),
+                "s3" => Ok("s3"),
+                "sqlite" => Ok("sqlite"),
+                _ => Err("expected fs, memory, s3 or sqlite"),
+            }
+        })? {
+            Some("memory") => Storage::Memory,
+            Some("s3") => Storage::S3(s3_config(source)?),
+            Some("sqlite") => Storage::Sqlite(
+                setting(source, "db_path", |v| Ok::<_, String>(PathBuf::from(v)))?
+                    .unwrap_or_else(|| PathBuf::from(DEFAULT_DB_PATH)),
+            ),
+            _ => Storage::Filesystem,
+        };
+        let paste_dir = setting(source, "paste_dir", |v| Ok::<_, String>(PathBuf::from(v)))?
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_PASTE_DIR));
+        let id_length = setting(source, "id_length", |v| {
+            let len = v.parse::<usize>().map_err(|e| e.to_string())?;
+            if !(MIN_ID_LENGTH..=MAX_ID_LENGTH).contains(&len) {
+                return Err(format!(
+                    "must be between {} and {}",
+                    MIN_ID_LENGTH, MAX_ID_LENGTH
+                ));
+            }
+            Ok(len)
+        })?
+        .unwrap_or(DEFAULT_ID_LENGTH);
+        let id_alphabet = setting(source, "id_alphabet", IdAlphabet::parse)?.unwrap_or_default();
+        let id_scheme = setting(source, "id_scheme", IdScheme::parse)?.unwrap_or_default();
+        let generated_length = id_scheme.id_length(id_length);
+        // Part of the ID like any other character, so limited to the same ones
+        let id_prefix = setting(source, "id_prefix", |v| {
+            if !v
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err("may only contain a-z, A-Z, 0-9, '-' and '_'".to_string());
+            }
+            if v.len() + generated_length > MAX_ID_LENGTH {
+                return Err(format!(
+                    "IDs of length {} would exceed {} characters with it",
+                    generated_length, MAX_ID_LENGTH
+                ));
+            }
+            Ok(v.to_string())
+        })?
+        .unwrap_or_default();
+        let max_size = setting(source, "max_size", parse_size)?.unwrap_or(DEFAULT_MAX_SIZE);
+        // Unset means any number of lines, of any length
+        let max_lines = setting(source, "max_lines", parse_limit)?;
+        let max_line_length = setting(source, "max_line_length", parse_limit)?;
+        // Unset means anything but empty pastes, whitespace included
+        let min_length = setting(source, "min_length", parse_limit)?;
+        // Unset means pastes never expire by default
+        let default_ttl = setting(source, "default_ttl", meta::parse_ttl)?;
+        // A token of a form, and not one of the option fields
+        let field_name = setting(source, "field_name", |v| {
+            if !v
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err("may only contain a-z, A-Z, 0-9, '-' and '_'".to_string());
+            }
+            if FORM_FIELDS.contains(&v) {
+                return Err(format!("'{}' is already the name of an option field", v));
+            }
+            Ok(v.to_string())
+        })?
+        .unwrap_or_else(|| DEFAULT_FIELD_NAME.to_string());
+        let max_batch = setting(source, "max_batch", parse_limit)?.unwrap_or(DEFAULT_MAX_BATCH);
+        // Rate limiting of new pastes is off unless RBIN_RATE_LIMIT is set
+        let rate_limit = setting(source, "rate_limit", RateLimiter::parse)?;
+        // Anyone may create pastes unless RBIN_ALLOW_IPS or RBIN_DENY_IPS is set
+        let ip_filter = IpFilter::new(
+            setting(source, "allow_ips", IpFilter::parse_list)?.unwrap_or_default(),
+            setting(source, "deny_ips", IpFilter::parse_list)?.unwrap_or_default(),
+        );
+        // No moderation unless RBIN_BLOCKLIST_FILE is set
+        let blocklist = setting(source, "blocklist_file", |v| Blocklist::load(v.as_ref()))?;
+        // A secret, so unlike other settings its value is left out of errors
+        let cipher = text(source, "encryption_key")?
+            .map(|key| {
+                Cipher::from_base64(&key).map_err(|e| format!("Invalid RBIN_ENCRYPTION_KEY: {}", e))
+            })
+            .transpose()?;
+        let cache_entries = setting(source, "cache_entries", |v| v.parse::<usize>())?
+            .unwrap_or(DEFAULT_CACHE_ENTRIES);
+        // Public link prefix, e.g. behind a reverse proxy on a subpath
+        let base_url = setting(source, "base_url", |v| {
+            let url = v.trim_end_matches('/');
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err("must start with http:// or https://");
+            }
+            Ok(url.to_string())
+        })?;
+        // Notified of every new paste, see `webhook`
+        let webhook_url = setting(source, "webhook_url", |v| {
+            if !(v.starts_with("http://") || v.starts_with("https://")) {
+                return Err("must start with http:// or https://");
+            }
+            Ok(v.to_string())
+        })?;
+        // "0" or "off" disables the periodic removal of expired pastes
+        let cleanup_interval = setting(source, "cleanup_interval", |v| match v {
+            "0" | "off" => Ok(None),
+            _ => meta::parse_ttl(v).map(Some),
+        })?
+        .unwrap_or(Some(DEFAULT_CLEANUP_INTERVAL));
+        let request_timeout = setting(source, "request_timeout", |v| match v {
+            "0" | "off" => Ok(None),
+            _ => meta::parse_ttl(v).map(Some),
+        })?
+        .unwrap_or(Some(DEFAULT_REQUEST_TIMEOUT));
+        // HTTPS needs both the certificate chain and the private key (PEM files)
+        let tls = match (text(source, "tls_cert")?, text(source, "tls_key")?) {
+            (Some(cert), Some(key)) => Some((cert, key)),
+            (None, None) => None,
+            _ => {
+                return Err(
+                    "Set both RBIN_TLS_CERT and RBIN_TLS_KEY to enable HTTPS, or neither."
+                        .to_string(),
+                )
+            }
+        };
+        let unix_socket = setting(source, "unix_socket", |v| {
+            if cfg!(unix) {
+                Ok(PathBuf::from(v))
+            } else {
+                Err("Unix sockets aren't supported on this platform")
+            }
+        })?;
+        if unix_socket.is_some() && tls.is_some() {
+            return Err(
+                "RBIN_UNIX_SOCKET can't be combined with HTTPS, let the proxy in front terminate TLS."
+                    .to_string(),
+            );
+        }
+        // Read and write for the owner and group, e.g. with nginx in the group
+        let unix_socket_mode =
+            setting(source, "unix_socket_mode", |v| {
+                match u32::from_str_radix(v, 8) {
+                    Ok(mode) if mode <= 0o777 => Ok(mode),
+                    _ => Err("expected octal permissions like 660"),
+                }
+            })?
+            .unwrap_or(DEFAULT_UNIX_SOCKET_MODE);
+        // No disk quota unless RBIN_MAX_TOTAL_BYTES is set
+        let max_total_bytes = setting(source, "max_total_bytes", |v| {
+            parse_size(v).map(|size| size as u64)
+        })?;
+        // Pastes are only evicted when RBIN_MAX_PASTES is set
+        let max_pastes = setting(source, "max_pastes", parse_limit)?;
+        let request_log_level = setting(source, "request_log_level", |v| {
+            match v.to_ascii_lowercase().as_str() {
+                level @ ("off" | "error" | "warn" | "info" | "debug" | "trace") => {
+                    Ok(level.to_string())
+                }
+                _ => Err("expected off, error, warn, info, debug or trace"),
+            }
+        })?
+        .unwrap_or_else(|| DEFAULT_REQUEST_LOG_LEVEL.to_string());
+        let highlight_theme = setting(source, "highlight_theme", parse_theme)?
+            .unwrap_or_else(|| highlight::DEFAULT_THEME.to_string());
+        let light_theme = setting(source, "light_theme", parse_theme)?;
+        // Pastes are kept out of search engines unless the operator wants them there
+        let allow_robots = setting(source, "robots", |v| {
+            match v.to_ascii_lowercase().as_str() {
+                "allow" => Ok(true),
+                "disallow" => Ok(false),
+                _ => Err("expected allow or disallow"),
+            }
+        })?
+        .unwrap_or(false);
+        let json_logs = setting(source, "log_format", |v| {
+            match v.to_ascii_lowercase().as_str() {
+                "json" => Ok(true),
+                "text" => Ok(false),
+                _ => Err("expected json or text"),
+            }
+        })?
+        .unwrap_or(false);
+
+        Ok(Self {
+            hosts,
+            port,
+            port_file,
+            unix_socket,
+            unix_socket_mode,
+            storage,
+            paste_dir,
+            id_length,
+            id_alphabet,
+            id_scheme,
+            id_prefix,
+            max_size,
+            max_lines,
+            max_line_length,
+            min_length,
+            allow_empty: flag(source, "allow_empty")?,
+            field_name,
+            default_ttl,
+            max_batch,
+            rate_limit,
+            ip_filter,
+            blocklist,
+            compress: flag(source, "compress")?,
+            cipher,
+            allow_html: flag(source, "allow_html")?,
+            highlight_theme,
+            light_theme,
+            disable_index: flag(source, "disable_index")?,
+            allow_robots,
+            read_only: flag(source, "read_only")?,
+            cache_entries,
+            base_url,
+            cleanup_interval,
+            request_timeout,
+            tls,
+            // Only honor X-Forwarded-* headers when told a proxy sets them
+            trust_proxy: flag(source, "trust_proxy")?,
+            // No CORS headers at all unless RBIN_CORS_ORIGINS is set
+            cors_origins: text(source, "cors_origins")?,
+            extra_headers: setting(source, "extra_headers", parse_headers)?.unwrap_or_default(),
+            max_total_bytes,
+            max_pastes,
+            // The admin endpoints don't exist unless RBIN_ADMIN_TOKEN is set
+            admin_token: text(source, "admin_token")?,
+            webhook_url,
+            request_log_level,
+            json_logs,
+        })
+    }
+}
+
+/// One of syntect's bundled themes, e.g. `InspiredGitHub`.
+fn parse_theme(value: &str) -> Result<String, String> {
+    if !highlight::is_theme(value) {
+        return Err(format!(
+            "expected one of {}",
+            highlight::theme_names().join(", ")
+        ));
+    }
+    Ok(value.to_string())
+}
+
+/// Parses setting `key` with `parse`, or `None` if it is unset or empty.
+/// Values are trimmed first.
+fn setting<T, E: Display>(
+    source: &Source,
+    key: &str,
+    parse: impl FnOnce(&str) -> Result<T, E>,
+) -> Result<Option<T>, String> {
+    match source.get(key) {
+        Some(value) if !value.trim().is_empty() => parse(value.trim())
+            .map(Some)
+            .map_err(|e| format!("Invalid {} '{}': {}", source.name(key), value.trim(), e)),
+        _ => Ok(None),
+    }
+}
+
+/// A free-form setting such as a path or token.
+fn text(source: &Source, key: &str) -> Result<Option<String>, String> {
+    setting(source, key, |v| Ok::<_, String>(v.to_string()))
+}
+
+/// Reads the RBIN_S3_* settings, only needed with `RBIN_STORAGE=s3`.
+fn s3_config(source: &Source) -> Result<S3Config, String> {
+    let bucket =
+        text(source, "s3_bucket")?.ok_or("RBIN_S3_BUCKET must be set when RBIN_STORAGE is s3")?;
+    let region = text(source, "s3_region")?.unwrap_or_else(|| DEFAULT_S3_REGION.to_string());
+    let endpoint = setting(source, "s3_endpoint", |v| {
+        url::Url::parse(v).map(|_| v.trim_end_matches('/').to_string())
+    })?;
+    // The secret never ends up in an error message
+    let credentials = match (
+        text(source, "s3_access_key")?,
+        text(source, "s3_secret_key")?,
+    ) {
+        (Some(access_key), Some(secret_key)) => Some((access_key, secret_key)),
+        (None, None) => None,
+        _ => return Err("RBIN_S3_ACCESS_KEY and RBIN_S3_SECRET_KEY must be set together".into()),
+    };
+    Ok(S3Config {
+        bucket,
+        region,
+        endpoint,
+        credentials,
+    })
+}
+
+/// Parses a comma-separated list of addresses to listen on, e.g. `0.0.0.0,::`.
+fn parse_hosts(value: &str) -> Result<Vec<IpAddr>, String> {
+    let mut hosts = Vec::new();
+    for host in value
+        .split(',')
+        .map(str::trim)
+        .filter(|host| !host.is_empty())
+    {
+        let ip = host
+            .parse::<IpAddr>()
+            .map_err(|e| format!("invalid address '{}': {}", host, e))?;
+        if !hosts.contains(&ip) {
+            hosts.push(ip);
+        }
+    }
+    if hosts.is_empty() {
+        return Err("no address given".to_string());
+    }
+    Ok(hosts)
+}
+
+/// Parses a count that has to be at least 1, e.g. for RBIN_MAX_LINES.
+fn parse_limit(value: &str) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(limit) => Ok(limit),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// `Name: value` pairs separated by `|`, as commas and semicolons are common
+/// in the values, e.g. `X-Frame-Options: DENY|Content-Security-Policy: ...`.
+fn parse_headers(value: &str) -> Result<Vec<(HeaderName, HeaderValue)>, String> {
+    value
+        .split('|')
+        .map(str::trim)
+        .filter(|header| !header.is_empty())
+        .map(|header| {
+            let (name, value) = header
+                .split_once(':')
+                .ok_or_else(|| format!("expected 'Name: value' in '{}'", header))?;
+            let name = HeaderName::from_bytes(name.trim().as_bytes())
+                .map_err(|_| format!("'{}' is not a valid header name", name.trim()))?;
+            let value = HeaderValue::from_str(value.trim())
+                .map_err(|_| format!("the value of {} is not a valid header value", name))?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Reads a boolean setting such as `RBIN_COMPRESS=1` or `compress = true`.
+fn flag(source: &Source, key: &str) -> Result<bool, String> {
+    setting(source, key, |v| match v.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err("expected 1/0, true/false, yes/no or on/off"),
+    })
+    .map(|value| value.unwrap_or(false))
+}
+
+/// Parses a size such as `512k`, `5M`, `1GiB` or a plain number of bytes.
+/// Units are binary, `1k` is 1024 bytes.
+fn parse_size(input: &str) -> Result<usize, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: usize = number
+        .parse()
+        .map_err(|e| format!("invalid number '{}': {}", number, e))?;
+    let multiplier: usize = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        other => return Err(format!("unknown unit '{}'", other)),
+    };
+    let size = number
+        .checked_mul(multiplier)
+        .ok_or_else(|| "size is too large".to_string())?;
+    if size == 0 {
+        return Err("size must be greater than zero".to_string());
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(settings: &[(&str, &str)]) -> Source {
+        let settings = settings.iter().map(|(k, v)| (k.to_string(), v.to_string()));
+        Source::from_map(settings.collect())
+    }
+
+    fn config(settings: &[(&str, &str)]) -> Result<Config, String> {
+        Config::from_source(&source(settings))
+    }
+
+    #[test]
+    fn sizes_are_binary() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("512k"), Ok(512 * 1024));
+        assert_eq!(parse_size("5 MiB"), Ok(5 * 1024 * 1024));
+        assert_eq!(parse_size("1G"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("5x").is_err());
+        assert!(parse_size("k").is_err());
+        assert!(parse_size(&format!("{}G", usize::MAX)).is_err());
+    }
+
+    #[test]
+    fn hosts_are_deduplicated() {
+        let hosts = parse_hosts("127.0.0.1, ::1,127.0.0.1").unwrap();
+        assert_eq!(
+            hosts,
+            [
+                "127.0.0.1".parse::<IpAddr>().unwrap(),
+                "::1".parse().unwrap()
+            ]
+        );
+        assert!(parse_hosts(" , ").is_err());
+        assert!(parse_hosts("localhost").is_err());
+    }
+
+    #[test]
+    fn headers_are_split_on_pipes() {
+        let headers = parse_headers("X-Frame-Options: DENY | Feature-Policy: a; b, c|").unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(
+            headers[0],
+            (
+                HeaderName::from_static("x-frame-options"),
+                HeaderValue::from_static("DENY")
+            )
+        );
+        assert_eq!(headers[1].1, "a; b, c");
+        // An empty value drops a default header
+        assert_eq!(parse_headers("Referrer-Policy:").unwrap()[0].1, "");
+        assert!(parse_headers("nosniff").is_err());
+        assert!(parse_headers("Bad Name: x").is_err());
+    }
+
+    #[test]
+    fn flags_take_the_usual_spellings() {
+        for value in ["1", "true", "Yes", "ON"] {
+            assert_eq!(flag(&source(&[("compress", value)]), "compress"), Ok(true));
+        }
+        for value in ["0", "false", "no", "off", ""] {
+            assert_eq!(flag(&source(&[("compress", value)]), "compress"), Ok(false));
+        }
+        assert_eq!(flag(&source(&[]), "compress"), Ok(false));
+        let error = flag(&source(&[("compress", "maybe")]), "compress").unwrap_err();
+        assert!(
+            error.starts_with("Invalid RBIN_COMPRESS 'maybe'"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn id_prefix_must_fit_an_id() {
+        assert_eq!(config(&[("id_prefix", "tmp-")]).unwrap().id_prefix, "tmp-");
+        assert!(config(&[("id_prefix", "a/b")]).is_err());
+        let long = "x".repeat(MAX_ID_LENGTH);
+        assert!(config(&[("id_prefix", &long)]).is_err());
+    }
+
+    #[test]
+    fn field_name_is_a_plain_token() {
+        assert_eq!(config(&[]).unwrap().field_name, DEFAULT_FIELD_NAME);
+        assert_eq!(
+            config(&[("field_name", "file")]).unwrap().field_name,
+            "file"
+        );
+        assert!(config(&[("field_name", "my field")]).is_err());
+        assert!(config(&[("field_name", "expires")]).is_err());
+    }
+
+    #[test]
+    fn command_line_wins_over_the_file() {
+        let source = Source {
+            args: HashMap::from([("port".to_string(), "8080".to_string())]),
+            file: HashMap::from([
+                ("port".to_string(), "9000".to_string()),
+                ("max_lines".to_string(), "10".to_string()),
+            ]),
+            env: false,
+        };
+        let config = Config::from_source(&source).unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.max_lines, Some(10));
+        // Errors name the setting the way it was given
+        let source = Source {
+            args: HashMap::from([("port".to_string(), "http".to_string())]),
+            ..source
+        };
+        let error = Config::from_source(&source).map(|_| ()).unwrap_err();
+        assert!(error.starts_with("Invalid --port 'http'"), "{}", error);
+    }
+}
@@ -0,0 +1,144 @@
+// --- Content Types ---
+// Pastes may carry a content type to be served with instead of `text/plain`.
+// Only the types below are accepted; those a browser would run scripts from
+// additionally need RBIN_ALLOW_HTML.
+
+const ALLOWED: &[&str] = &[
+    "text/plain",
+    "text/csv",
+    "text/markdown",
+    "text/css",
+    "text/xml",
+    "application/json",
+    "application/xml",
+    "application/yaml",
+    "application/toml",
+    "application/javascript",
+    "application/octet-stream",
+    "application/pdf",
+    "application/gzip",
+    "application/zip",
+    "application/x-tar",
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+];
+
+// Allowed types other than `text/*` that are text too
+const TEXT_APPLICATION: &[&str] = &[
+    "application/json",
+    "application/xml",
+    "application/yaml",
+    "application/toml",
+    "application/javascript",
+    "application/xhtml+xml",
+];
+
+// Rendered as active content when opened in a browser
+const SCRIPTABLE: &[&str] = &["text/html", "image/svg+xml", "application/xhtml+xml"];
+
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("txt", "text/plain"),
+    ("csv", "text/csv"),
+    ("md", "text/markdown"),
+    ("markdown", "text/markdown"),
+    ("css", "text/css"),
+    ("json", "application/json"),
+    ("xml", "application/xml"),
+    ("yaml", "application/yaml"),
+    ("yml", "application/yaml"),
+    ("toml", "application/toml"),
+    ("js", "application/javascript"),
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("svg", "image/svg+xml"),
+    ("xhtml", "application/xhtml+xml"),
+    ("bin", "application/octet-stream"),
+    ("pdf", "application/pdf"),
+    ("gz", "application/gzip"),
+    ("tgz", "application/gzip"),
+    ("zip", "application/zip"),
+    ("tar", "application/x-tar"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+];
+
+// Magic numbers of the binary formats above
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"PK\x03\x04", "application/zip"),
+];
+
+/// Fallback for binary pastes nothing else is known about.
+pub const BINARY: &str = "application/octet-stream";
+
+/// Normalizes a content type such as `Application/JSON; charset=utf-8` to its
+/// bare lowercase form, rejecting anything not on the allowlist.
+pub fn sanitize(value: &str, allow_html: bool) -> Result<String, String> {
+    let essence = value
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    if ALLOWED.contains(&essence.as_str()) {
+        return Ok(essence);
+    }
+    if SCRIPTABLE.contains(&essence.as_str()) {
+        if allow_html {
+            return Ok(essence);
+        }
+        return Err(format!(
+            "content type '{}' is disabled on this server",
+            essence
+        ));
+    }
+    Err(format!("unsupported content type '{}'", essence))
+}
+
+/// Whether a sanitized content type is a text format, whose content must be
+/// valid UTF-8.
+pub fn is_text(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || content_type == "image/svg+xml"
+        || TEXT_APPLICATION.contains(&content_type)
+}
+
+/// Guesses the content type from an uploaded file name, e.g. `data.json`.
+pub fn from_file_name(file_name: &str) -> Option<&'static str> {
+    let (_, ext) = file_name.rsplit_once('.')?;
+    EXTENSIONS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(ext))
+        .map(|(_, content_type)| *content_type)
+}
+
+/// Guesses the type of binary content from its first bytes.
+pub fn sniff(head: &[u8]) -> Option<&'static str> {
+    if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP".as_slice()) {
+        return Some("image/webp");
+    }
+    SIGNATURES
+        .iter()
+        .find(|(magic, _)| head.starts_with(magic))
+        .map(|(_, content_type)| *content_type)
+}
+
+/// The `Content-Type` header value for a stored content type. Text pastes are
+/// always UTF-8, so say so.
+pub fn header_value(content_type: &str, text: bool) -> String {
+    if text {
+        format!("{}; charset=utf-8", content_type)
+    } else {
+        content_type.to_string()
+    }
+}
@@ -0,0 +1,179 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{
+        generic_array::GenericArray,
+        stream::{DecryptorBE32, EncryptorBE32},
+        KeyInit,
+    },
+    ChaCha20Poly1305,
+};
+use rand::RngCore;
+use std::{
+    fmt, io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::io::AsyncWrite;
+
+// --- Encryption at Rest ---
+// With RBIN_ENCRYPTION_KEY, paste content is encrypted with ChaCha20-Poly1305
+// before it touches the disk. Files are written as they stream in, using the
+// STREAM construction: a random 7 byte nonce prefix, followed by the content
+// in chunks of `CHUNK_LEN` bytes, each sealed with its own 16 byte tag. The
+// last chunk is marked as such, so truncated files fail to decrypt.
+
+const KEY_LEN: usize = 32;
+const NONCE_PREFIX_LEN: usize = 7;
+const TAG_LEN: usize = 16;
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// The server's key, see RBIN_ENCRYPTION_KEY.
+#[derive(Clone)]
+pub struct Cipher {
+    aead: ChaCha20Poly1305,
+}
+
+impl Cipher {
+    /// Decodes a base64 encoded 32 byte key. The key is never part of the error.
+    pub fn from_base64(key: &str) -> Result<Self, String> {
+        let key = STANDARD
+            .decode(key.trim())
+            .map_err(|_| "the key is not valid base64".to_string())?;
+        if key.len() != KEY_LEN {
+            return Err(format!(
+                "the key must be {} bytes, not {}, e.g. from `openssl rand -base64 32`",
+                KEY_LEN,
+                key.len()
+            ));
+        }
+        Ok(Self {
+            aead: ChaCha20Poly1305::new(GenericArray::from_slice(&key)),
+        })
+    }
+
+    /// Decrypts a whole file written by `EncryptWriter`. CPU-bound, call this
+    /// from a blocking task.
+    pub fn decrypt(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "failed to decrypt paste, is RBIN_ENCRYPTION_KEY the one it was written with?",
+            )
+        };
+        if data.len() < NONCE_PREFIX_LEN + TAG_LEN {
+            return Err(invalid());
+        }
+        let (prefix, mut rest) = data.split_at(NONCE_PREFIX_LEN);
+        let mut decryptor =
+            DecryptorBE32::from_aead(self.aead.clone(), GenericArray::from_slice(prefix));
+        let mut content = Vec::with_capacity(rest.len());
+        // A last chunk of exactly `CHUNK_LEN` bytes is still the last one
+        while rest.len() > CHUNK_LEN + TAG_LEN {
+            let (chunk, tail) = rest.split_at(CHUNK_LEN + TAG_LEN);
+            content.extend(decryptor.decrypt_next(chunk).map_err(|_| invalid())?);
+            rest = tail;
+        }
+        content.extend(decryptor.decrypt_last(rest).map_err(|_| invalid())?);
+        Ok(content)
+    }
+}
+
+// Leave the key out of debug output
+impl fmt::Debug for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Cipher")
+    }
+}
+
+/// Encrypts everything written to it into `inner`. `shutdown` seals the last
+/// chunk, a writer dropped before that leaves a file that won't decrypt.
+pub struct EncryptWriter<W> {
+    inner: W,
+    // Taken to seal the last chunk
+    encryptor: Option<EncryptorBE32<ChaCha20Poly1305>>,
+    // Content of the chunk being filled
+    buffer: Vec<u8>,
+    // Sealed output not written to `inner` yet, from `written` on
+    pending: Vec<u8>,
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin> EncryptWriter<W> {
+    pub fn new(inner: W, cipher: &Cipher) -> Self {
+        let mut prefix = [0; NONCE_PREFIX_LEN];
+        rand::thread_rng().fill_bytes(&mut prefix);
+        let encryptor =
+            EncryptorBE32::from_aead(cipher.aead.clone(), GenericArray::from_slice(&prefix));
+        Self {
+            inner,
+            encryptor: Some(encryptor),
+            buffer: Vec::with_capacity(CHUNK_LEN),
+            pending: prefix.to_vec(),
+            written: 0,
+        }
+    }
+
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.pending.len() {
+            let n =
+                ready!(Pin::new(&mut self.inner).poll_write(cx, &self.pending[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += n;
+        }
+        self.pending.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    fn seal(&mut self, last: bool) -> io::Result<()> {
+        let failed = |_| io::Error::other("failed to encrypt paste");
+        self.pending = match self.encryptor.take() {
+            Some(encryptor) if last => encryptor.encrypt_last(self.buffer.as_slice()),
+            Some(mut encryptor) => {
+                let sealed = encryptor.encrypt_next(self.buffer.as_slice());
+                self.encryptor = Some(encryptor);
+                sealed
+            }
+            None => return Err(io::Error::other("write after shutdown")),
+        }
+        .map_err(failed)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for EncryptWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        // Only sealed once more content follows, the last chunk is special
+        if this.buffer.len() == CHUNK_LEN {
+            this.seal(false)?;
+        }
+        let n = buf.len().min(CHUNK_LEN - this.buffer.len());
+        this.buffer.extend_from_slice(&buf[..n]);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        if this.encryptor.is_some() {
+            this.seal(true)?;
+            ready!(this.poll_pending(cx))?;
+        }
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
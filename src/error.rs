@@ -0,0 +1,137 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+// --- API Errors ---
+// What handlers fail with: a message for people and a stable code for
+// programs. Sent as plain text like every other error, `error_pages` turns it
+// into `{"error": "...", "code": "..."}` for clients that prefer JSON.
+
+#[derive(Debug)]
+pub enum ApiError {
+    /// The paste content is empty
+    EmptyBody,
+    /// A paste ID that is malformed or may not be used
+    InvalidId(String),
+    NotFound(String),
+    /// Over RBIN_MAX_SIZE or another limit of the content
+    TooLarge(String),
+    /// Whitespace only or below RBIN_MIN_LENGTH
+    TooShort(String),
+    /// A custom ID that is already taken
+    IdTaken(String),
+    /// Content matching RBIN_BLOCKLIST_FILE
+    Blocked(String),
+    /// Anything else wrong with the request
+    BadRequest(String),
+    /// A failure on the server's side, e.g. of the store
+    Internal(String),
+    /// Any other status, with the code derived from it
+    Other(StatusCode, String),
+}
+
+/// The code of an error response, set by `ApiError`.
+#[derive(Clone, Copy, Debug)]
+pub struct ErrorCode(pub &'static str);
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::EmptyBody
+            | ApiError::InvalidId(_)
+            | ApiError::TooShort(_)
+            | ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::IdTaken(_) => StatusCode::CONFLICT,
+            ApiError::Blocked(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Other(status, _) => *status,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::EmptyBody => "EMPTY_BODY",
+            ApiError::InvalidId(_) => "INVALID_ID",
+            ApiError::NotFound(_) => "NOT_FOUND",
+            ApiError::TooLarge(_) => "TOO_LARGE",
+            ApiError::TooShort(_) => "TOO_SHORT",
+            ApiError::IdTaken(_) => "ID_TAKEN",
+            ApiError::Blocked(_) => "BLOCKED",
+            ApiError::BadRequest(_) => "BAD_REQUEST",
+            ApiError::Internal(_) => "INTERNAL_ERROR",
+            ApiError::Other(status, _) => status_code(*status),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            ApiError::EmptyBody => "Paste content cannot be empty",
+            ApiError::InvalidId(message)
+            | ApiError::NotFound(message)
+            | ApiError::TooLarge(message)
+            | ApiError::TooShort(message)
+            | ApiError::IdTaken(message)
+            | ApiError::Blocked(message)
+            | ApiError::BadRequest(message)
+            | ApiError::Internal(message)
+            | ApiError::Other(_, message) => message,
+        }
+    }
+}
+
+/// The code of errors that don't come with one, from their status.
+pub fn status_code(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "BAD_REQUEST",
+        StatusCode::UNAUTHORIZED => "UNAUTHORIZED",
+        StatusCode::FORBIDDEN => "FORBIDDEN",
+        StatusCode::NOT_FOUND => "NOT_FOUND",
+        StatusCode::METHOD_NOT_ALLOWED => "METHOD_NOT_ALLOWED",
+        StatusCode::REQUEST_TIMEOUT => "TIMEOUT",
+        StatusCode::CONFLICT => "CONFLICT",
+        StatusCode::GONE => "GONE",
+        StatusCode::PAYLOAD_TOO_LARGE => "TOO_LARGE",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "UNSUPPORTED_MEDIA_TYPE",
+        StatusCode::RANGE_NOT_SATISFIABLE => "RANGE_NOT_SATISFIABLE",
+        StatusCode::TOO_MANY_REQUESTS => "RATE_LIMITED",
+        StatusCode::INSUFFICIENT_STORAGE => "STORAGE_FULL",
+        StatusCode::SERVICE_UNAVAILABLE => "UNAVAILABLE",
+        status if status.is_server_error() => "INTERNAL_ERROR",
+        _ => "ERROR",
+    }
+}
+
+/// For rejections of axum's extractors, which come as status and message.
+impl From<(StatusCode, String)> for ApiError {
+    fn from((status, message): (StatusCode, String)) -> Self {
+        match status {
+            StatusCode::BAD_REQUEST => ApiError::BadRequest(message),
+            StatusCode::NOT_FOUND => ApiError::NotFound(message),
+            StatusCode::PAYLOAD_TOO_LARGE => ApiError::TooLarge(message),
+            _ => ApiError::Other(status, message),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let code = ErrorCode(self.code());
+        let mut response = (self.status(), self.message().to_string()).into_response();
+        response.extensions_mut().insert(code);
+        response
+    }
+}
+
+/// JSON body of error responses, see `error_pages`.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorJson<'a> {
+    pub error: &'a str,
+    pub code: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<&'a str>,
+}
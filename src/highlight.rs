@@ -0,0 +1,147 @@
+use crate::html;
+use std::{collections::HashMap, sync::OnceLock, time::SystemTime};
+use syntect::{
+    highlighting::{Color, Theme, ThemeSet},
+    html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator},
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+/// Theme of the HTML view unless RBIN_HIGHLIGHT_THEME names another one.
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+/// What `?theme=light` picks without RBIN_HIGHLIGHT_THEME_LIGHT.
+pub const DEFAULT_LIGHT_THEME: &str = "base16-ocean.light";
+
+// Loading the bundled syntax definitions takes a moment, so do it once on first use
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+// The CSS of every bundled theme, generated once on first use
+fn themes() -> &'static HashMap<String, String> {
+    static THEMES: OnceLock<HashMap<String, String>> = OnceLock::new();
+    THEMES.get_or_init(|| {
+        ThemeSet::load_defaults()
+            .themes
+            .into_iter()
+            .map(|(name, theme)| {
+                let css = theme_css(&theme);
+                (name, css)
+            })
+            .collect()
+    })
+}
+
+/// The names of syntect's bundled themes, sorted.
+pub fn theme_names() -> Vec<&'static str> {
+    let mut names: Vec<&str> = themes().keys().map(String::as_str).collect();
+    names.sort_unstable();
+    names
+}
+
+pub fn is_theme(name: &str) -> bool {
+    themes().contains_key(name)
+}
+
+/// The highlighting classes of `theme`, followed by the page chrome in its
+/// colors so light themes don't end up on the dark default background.
+fn theme_css(theme: &Theme) -> String {
+    let mut css = css_for_theme_with_class_style(theme, ClassStyle::Spaced).unwrap_or_default();
+    let settings = &theme.settings;
+    if let (Some(background), Some(foreground)) = (settings.background, settings.foreground) {
+        css.push_str(&format!(
+            "body {{ background: {}; color: {}; }}\n",
+            css_color(background),
+            css_color(foreground)
+        ));
+    }
+    if let Some(highlight) = settings.line_highlight {
+        css.push_str(&format!(
+            ".line:target, .line.hl {{ background: {}; }}\n",
+            css_color(highlight)
+        ));
+    }
+    if let Some(gutter) = settings.gutter_foreground {
+        css.push_str(&format!(
+            ".ln, footer {{ color: {}; }}\n",
+            css_color(gutter)
+        ));
+    }
+    css
+}
+
+fn css_color(color: Color) -> String {
+    match color.a {
+        0xff => format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b),
+        a => format!("#{:02x}{:02x}{:02x}{:02x}", color.r, color.g, color.b, a),
+    }
+}
+
+/// The styles of a paste page in `theme`, and in `light_theme` for browsers
+/// preferring a light color scheme. Both must be names of `theme_names`.
+pub fn page_css(theme: &str, light_theme: Option<&str>) -> String {
+    let themes = themes();
+    let mut css = themes.get(theme).cloned().unwrap_or_default();
+    if let Some(light) = light_theme.and_then(|light| themes.get(light)) {
+        css.push_str(&format!(
+            "@media (prefers-color-scheme: light) {{\n{}}}\n",
+            light
+        ));
+    }
+    css
+}
+
+/// Looks up a syntax by file extension (`rs`) or name token (`rust`),
+/// falling back to plain text for anything unknown.
+fn find_syntax(ext: &str) -> &'static SyntaxReference {
+    let syntaxes = syntax_set();
+    syntaxes
+        .find_syntax_by_extension(ext)
+        .or_else(|| syntaxes.find_syntax_by_token(ext))
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text())
+}
+
+// Detection only looks at the first line, enough for shebangs, `<?php`,
+// `<?xml`, modelines and the like
+const DETECT_PREFIX: usize = 1024;
+
+/// Guesses the language of `content` from its first line, returning an
+/// extension for `render_html`. `None` if nothing matched.
+pub fn detect_language(content: &[u8]) -> Option<String> {
+    let prefix = &content[..content.len().min(DETECT_PREFIX)];
+    let first_line = String::from_utf8_lossy(prefix);
+    let first_line = first_line.lines().next()?;
+    let syntax = syntax_set().find_syntax_by_first_line(first_line)?;
+    Some(
+        syntax
+            .file_extensions
+            .first()
+            .cloned()
+            .unwrap_or_else(|| syntax.name.clone()),
+    )
+}
+
+/// Renders `content` as a standalone HTML page highlighted for `ext`, styled
+/// with `css` from `page_css`. This is CPU-bound, run it off the async
+/// executor for large pastes.
+pub fn render_html(
+    id: &str,
+    ext: &str,
+    content: &str,
+    created: Option<SystemTime>,
+    css: &str,
+) -> String {
+    let syntaxes = syntax_set();
+    let syntax = find_syntax(ext);
+    let mut generator =
+        ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, ClassStyle::Spaced);
+    for line in LinesWithEndings::from(content) {
+        if let Err(e) = generator.parse_html_for_line_which_includes_newline(line) {
+            // Shouldn't happen with the bundled syntaxes, degrade to unhighlighted output
+            tracing::warn!("Failed to highlight paste {} as {}: {}", id, ext, e);
+            return html::paste_page(id, &html::escape(content), css, created);
+        }
+    }
+    html::paste_page(id, &generator.finalize(), css, created)
+}
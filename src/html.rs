@@ -0,0 +1,345 @@
+use std::{
+    fmt::Write,
+    time::{Duration, SystemTime},
+};
+
+// --- HTML Rendering ---
+// Shared page chrome for the browser views of a paste.
+
+/// Wraps already-escaped paste markup in a standalone page with a copy button
+/// and numbered lines, which can be linked to as `#L42` or `#L10-L20`.
+/// `extra_css` is inlined after the base styles (e.g. a highlighting theme).
+/// The creation time, if known, is shown in a footer.
+pub fn paste_page(id: &str, body: &str, extra_css: &str, created: Option<SystemTime>) -> String {
+    let footer = created
+        .map(|created| {
+            format!(
+                "<footer>Created {}</footer>",
+                humantime::format_rfc3339_seconds(created)
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{id}</title>
+<style>
+body {{ margin: 0; background: #2b303b; color: #c0c5ce; font-family: sans-serif; }}
+pre {{ margin: 0; padding: 1em; font-size: 14px; white-space: pre-wrap; word-wrap: break-word; }}
+#copy {{ position: fixed; top: 0.5em; right: 0.5em; }}
+.line {{ display: block; padding-left: 5em; text-indent: -5em; }}
+.line:target, .line.hl {{ background: #343d46; }}
+.ln {{ display: inline-block; width: 4em; margin-right: 1em; text-align: right; text-indent: 0; color: #65737e; text-decoration: none; user-select: none; }}
+.ln::before {{ content: attr(data-line); }}
+footer {{ padding: 0 1em 1em; font-size: 12px; color: #65737e; }}
+{extra_css}
+</style>
+</head>
+<body>
+<button id="copy" type="button">Copy</button>
+<pre id="paste" class="code">{body}</pre>
+{footer}
+<script>
+var paste = document.getElementById("paste");
+document.getElementById("copy").addEventListener("click", function () {{
+  var button = this;
+  // textContent leaves out the line numbers, which are only generated by CSS
+  navigator.clipboard.writeText(paste.textContent).then(function () {{
+    button.textContent = "Copied!";
+    setTimeout(function () {{ button.textContent = "Copy"; }}, 1500);
+  }});
+}});
+// Ranges (#L10-L20) aren't element IDs, so mark their lines here
+function highlightLines() {{
+  document.querySelectorAll(".line.hl").forEach(function (line) {{ line.classList.remove("hl"); }});
+  var range = /^#L(\d+)-L?(\d+)$/.exec(location.hash);
+  if (!range) return;
+  var start = Math.min(+range[1], +range[2]), end = Math.max(+range[1], +range[2]);
+  for (var n = start; n <= end; n++) {{
+    var line = document.getElementById("L" + n);
+    if (!line) break;
+    line.classList.add("hl");
+  }}
+  var first = document.getElementById("L" + start);
+  if (first) first.scrollIntoView();
+}}
+window.addEventListener("hashchange", highlightLines);
+highlightLines();
+// Shift-click a line number to select a range from the current line
+paste.addEventListener("click", function (event) {{
+  var number = event.target.closest(".ln");
+  var current = /^#L(\d+)/.exec(location.hash);
+  if (!number || !event.shiftKey || !current) return;
+  event.preventDefault();
+  location.hash = "#L" + current[1] + "-L" + number.dataset.line;
+}});
+</script>
+</body>
+</html>
+"##,
+        id = escape(id),
+        extra_css = extra_css,
+        body = number_lines(body),
+        footer = footer,
+    )
+}
+
+/// Wraps every line of escaped (and possibly highlighted) markup in a
+/// `<span class="line" id="Ln">` with a line number link. Highlighting spans
+/// may continue over several lines; they are closed at the end of each line
+/// and reopened on the next so the line elements nest properly.
+fn number_lines(markup: &str) -> String {
+    let mut out = String::with_capacity(markup.len() * 2);
+    // Highlighting spans open at the current position
+    let mut open: Vec<&str> = Vec::new();
+    let mut line = 0;
+    let mut in_line = false;
+    let mut rest = markup;
+    while !rest.is_empty() {
+        let (token, is_tag) = if rest.starts_with('<') {
+            let end = rest.find('>').map_or(rest.len(), |i| i + 1);
+            (&rest[..end], true)
+        } else {
+            let end = rest.find(['<', '\n']).map_or(rest.len(), |i| i.max(1));
+            (&rest[..end], false)
+        };
+        rest = &rest[token.len()..];
+
+        if is_tag && token.starts_with("</") {
+            open.pop();
+            if in_line {
+                out.push_str(token);
+            }
+            continue;
+        }
+        if !in_line {
+            line += 1;
+            in_line = true;
+            let _ = write!(
+                out,
+                "<span class=\"line\" id=\"L{0}\"><a class=\"ln\" href=\"#L{0}\" data-line=\"{0}\"></a>",
+                line
+            );
+            out.extend(open.iter().copied());
+        }
+        out.push_str(token);
+        if is_tag {
+            open.push(token);
+        } else if token == "\n" {
+            for _ in &open {
+                out.push_str("</span>");
+            }
+            out.push_str("</span>");
+            in_line = false;
+        }
+    }
+    if in_line {
+        for _ in &open {
+            out.push_str("</span>");
+        }
+        out.push_str("</span>");
+    }
+    out
+}
+
+/// Asks for the password of a protected paste. The form posts back to the
+/// current URL, so highlighted views (`/:id.rs`) stay highlighted once unlocked.
+pub fn password_page(id: &str, wrong_password: bool) -> String {
+    let notice = if wrong_password {
+        "<p class=\"error\">Wrong password, please try again.</p>"
+    } else {
+        ""
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{id}</title>
+<style>
+body {{ margin: 0; padding: 2em; background: #2b303b; color: #c0c5ce; font-family: sans-serif; }}
+.error {{ color: #bf616a; }}
+</style>
+</head>
+<body>
+<p>Paste <strong>{id}</strong> is password protected.</p>
+{notice}
+<form method="post">
+<input type="password" name="password" placeholder="Password" autofocus required>
+<button type="submit">Unlock</button>
+</form>
+</body>
+</html>
+"#,
+        id = escape(id),
+        notice = notice,
+    )
+}
+
+/// Landing page for browsers with a form posting a new paste to `/`, the same
+/// multipart fields curl users send. `default_ttl` is RBIN_DEFAULT_TTL,
+/// `field_name` RBIN_FIELD_NAME.
+pub fn index_page(default_ttl: Option<Duration>, field_name: &str) -> String {
+    let default_expiry = match default_ttl {
+        Some(ttl) => format!("Default ({})", humantime::format_duration(ttl)),
+        None => "Never".to_string(),
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>rbin</title>
+<style>
+body {{ margin: 0; padding: 2em; background: #2b303b; color: #c0c5ce; font-family: sans-serif; }}
+textarea {{ box-sizing: border-box; width: 100%; height: 60vh; padding: 0.5em; background: #1f232b; color: #c0c5ce; border: 1px solid #65737e; font-family: monospace; font-size: 14px; }}
+.options {{ margin-top: 0.5em; }}
+a {{ color: #8fa1b3; }}
+</style>
+</head>
+<body>
+<form method="post" enctype="multipart/form-data">
+<textarea name="{field_name}" placeholder="Paste your text here" autofocus required></textarea>
+<div class="options">
+<label>Expires <select name="expires">
+<option value="">{default_expiry}</option>
+<option value="10m">10 minutes</option>
+<option value="1h">1 hour</option>
+<option value="1d">1 day</option>
+<option value="7d">7 days</option>
+<option value="30d">30 days</option>
+</select></label>
+<input type="password" name="password" placeholder="Password (optional)">
+<button type="submit">Paste</button>
+</div>
+</form>
+<p>Prefer the command line? Run <code>curl</code> on this page for instructions.</p>
+</body>
+</html>
+"#,
+        default_expiry = escape(&default_expiry),
+    )
+}
+
+/// Shown after a paste was created from the landing page, with the link and the
+/// deletion, edit and append tokens, which are only ever handed out here.
+pub fn created_page(url: &str, delete_token: &str, edit_token: &str, append_token: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Paste created</title>
+<style>
+body {{ margin: 0; padding: 2em; background: #2b303b; color: #c0c5ce; font-family: sans-serif; }}
+a {{ color: #8fa1b3; }}
+</style>
+</head>
+<body>
+<p>Your paste is available at <a href="{url}">{url}</a></p>
+<p>Keep this token to delete it again: <code>{delete_token}</code></p>
+<p>And this one to edit it: <code>{edit_token}</code></p>
+<p>Or this one to append to it: <code>{append_token}</code></p>
+<p><a href="">New paste</a></p>
+</body>
+</html>
+"#,
+        url = escape(url),
+        delete_token = escape(delete_token),
+        edit_token = escape(edit_token),
+        append_token = escape(append_token),
+    )
+}
+
+/// An error `message` as a page, one paragraph per line. `title` is the status,
+/// e.g. `404 Not Found`.
+pub fn error_page(title: &str, message: &str) -> String {
+    let mut paragraphs = String::new();
+    for line in message.lines().filter(|line| !line.trim().is_empty()) {
+        let _ = writeln!(paragraphs, "<p>{}</p>", escape(line));
+    }
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ margin: 0; padding: 2em; background: #2b303b; color: #c0c5ce; font-family: sans-serif; }}
+h1 {{ font-size: 1.4em; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{paragraphs}</body>
+</html>
+"#,
+        title = escape(title),
+    )
+}
+
+/// File list of a multi-file paste, served at `/:id/`. Links are relative to
+/// that URL. `files` are name and size pairs.
+pub fn bundle_page(id: &str, files: &[(&str, u64)], created: Option<SystemTime>) -> String {
+    let mut rows = String::new();
+    for (name, size) in files {
+        let _ = writeln!(
+            rows,
+            "<tr><td><a href=\"{name}\">{name}</a></td><td class=\"size\">{size} bytes</td></tr>",
+            name = escape(name),
+            size = size,
+        );
+    }
+    let footer = created
+        .map(|time| {
+            format!(
+                "<footer>Created {}</footer>\n",
+                humantime::format_rfc3339_seconds(time)
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{id}</title>
+<style>
+body {{ margin: 0; padding: 2em; background: #2b303b; color: #c0c5ce; font-family: sans-serif; }}
+a {{ color: #8fa1b3; }}
+td {{ padding: 0.2em 1em 0.2em 0; }}
+.size {{ color: #65737e; text-align: right; }}
+footer {{ margin-top: 1em; color: #65737e; font-size: 12px; }}
+</style>
+</head>
+<body>
+<p>Paste <strong>{id}</strong> has {count} files:</p>
+<table>
+{rows}</table>
+{footer}</body>
+</html>
+"#,
+        id = escape(id),
+        count = files.len(),
+        rows = rows,
+        footer = footer,
+    )
+}
+
+/// Escapes text for safe inclusion in HTML element content and attribute values.
+pub fn escape(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
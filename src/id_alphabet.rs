@@ -0,0 +1,137 @@
+use rand::{distributions::Slice, Rng};
+use std::fmt;
+
+// --- ID Alphabet ---
+// The characters generated paste IDs are made of, set with RBIN_ID_ALPHABET
+// to one of the presets below or the characters themselves. They are limited
+// to those valid in any ID. Lookups are held to them as well, together with
+// `-`, `_` and the characters of RBIN_ID_PREFIX and RBIN_ID_SCHEME, see
+// `validate_id`. Custom IDs from `PUT /:id` are held to the same.
+
+const PRESETS: &[(&str, &str)] = &[
+    (
+        "alphanumeric",
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+    ),
+    // Leaves out 0, O, I and l, which are easily confused
+    (
+        "base58",
+        "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz",
+    ),
+    ("lowercase", "abcdefghijklmnopqrstuvwxyz0123456789"),
+];
+
+#[derive(Debug)]
+pub struct IdAlphabet {
+    /// Name of the preset, if it is one
+    name: Option<&'static str>,
+    chars: Vec<char>,
+}
+
+impl IdAlphabet {
+    /// Parses a preset name (`alphanumeric`, `base58`, `lowercase`) or a set of
+    /// characters from `a-z`, `A-Z`, `0-9`, `-` and `_`, e.g. `0123456789abcdef`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        if let Some((name, chars)) = PRESETS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(spec))
+        {
+            return Ok(Self {
+                name: Some(name),
+                chars: chars.chars().collect(),
+            });
+        }
+        let mut chars: Vec<char> = Vec::new();
+        for c in spec.chars() {
+            if !(c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(format!(
+                    "invalid character '{}', use a-z, A-Z, 0-9, '-' and '_' or one of {}",
+                    c,
+                    preset_names()
+                ));
+            }
+            if !chars.contains(&c) {
+                chars.push(c);
+            }
+        }
+        if chars.len() < 2 {
+            return Err("needs at least two different characters".to_string());
+        }
+        Ok(Self { name: None, chars })
+    }
+
+    pub fn chars(&self) -> &[char] {
+        &self.chars
+    }
+
+    /// A random ID of `length` characters from the alphabet.
+    pub fn generate(&self, length: usize) -> String {
+        // `parse` guarantees the alphabet isn't empty
+        let chars = Slice::new(&self.chars).expect("ID alphabet is never empty");
+        rand::thread_rng().sample_iter(chars).take(length).collect()
+    }
+}
+
+impl Default for IdAlphabet {
+    fn default() -> Self {
+        Self::parse("alphanumeric").expect("presets are valid")
+    }
+}
+
+impl fmt::Display for IdAlphabet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name {
+            Some(name) => f.write_str(name),
+            None => write!(f, "{}", self.chars.iter().collect::<String>()),
+        }
+    }
+}
+
+fn preset_names() -> String {
+    PRESETS
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The sorted set of `chars` for messages, with runs shortened to ranges and
+/// letters and digits first, e.g. `a-z, A-Z, 0-9, '-' and '_'`.
+pub fn describe(chars: &[char]) -> String {
+    let mut runs: Vec<(char, char)> = Vec::new();
+    for &c in chars {
+        match runs.last_mut() {
+            Some((_, last)) if *last as u32 + 1 == c as u32 => *last = c,
+            _ => runs.push((c, c)),
+        }
+    }
+    runs.sort_by_key(|(first, _)| {
+        [
+            first.is_ascii_lowercase(),
+            first.is_ascii_uppercase(),
+            first.is_ascii_digit(),
+        ]
+        .iter()
+        .position(|&class| class)
+        .unwrap_or(3)
+    });
+    let mut parts: Vec<String> = Vec::new();
+    for (first, last) in runs {
+        let quote = |c: char| {
+            if c.is_ascii_alphanumeric() {
+                c.to_string()
+            } else {
+                format!("'{}'", c)
+            }
+        };
+        match last as u32 - first as u32 {
+            0 => parts.push(quote(first)),
+            1 => parts.extend([quote(first), quote(last)]),
+            _ => parts.push(format!("{}-{}", first, last)),
+        }
+    }
+    match parts.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+        _ => parts.concat(),
+    }
+}
@@ -0,0 +1,112 @@
+use crate::id_alphabet::IdAlphabet;
+use rand::{distributions::Slice, Rng};
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// --- ID Scheme ---
+// How generated paste IDs are made, set with RBIN_ID_SCHEME. All schemes stick
+// to the characters valid in any ID, so pastes of one keep working after
+// switching to another.
+
+/// Crockford's base32 in lowercase, which sorts like the numbers it encodes.
+const BASE32: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+/// Encoded milliseconds since the epoch, enough for the next 30,000 years.
+const TIMESTAMP_LENGTH: usize = 10;
+/// Random characters after the timestamp, for pastes of the same millisecond.
+const TIMESTAMP_SUFFIX_LENGTH: usize = 4;
+const UUID_LENGTH: usize = 36;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IdScheme {
+    /// RBIN_ID_LENGTH characters of RBIN_ID_ALPHABET
+    #[default]
+    Random,
+    /// A random (version 4) UUID like `0f8fad5b-d9cb-469f-a165-70867728950e`
+    Uuid,
+    /// The creation time in base32 and a short random suffix, sorting by age
+    Timestamp,
+}
+
+impl IdScheme {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "random" => Ok(Self::Random),
+            "uuid" => Ok(Self::Uuid),
+            "timestamp" => Ok(Self::Timestamp),
+            _ => Err("expected random, uuid or timestamp".to_string()),
+        }
+    }
+
+    /// Length of the generated IDs, `length` being RBIN_ID_LENGTH.
+    pub fn id_length(self, length: usize) -> usize {
+        match self {
+            Self::Random => length,
+            Self::Uuid => UUID_LENGTH,
+            Self::Timestamp => TIMESTAMP_LENGTH + TIMESTAMP_SUFFIX_LENGTH,
+        }
+    }
+
+    /// Characters of the generated IDs besides those of RBIN_ID_ALPHABET.
+    pub fn chars(self) -> &'static str {
+        match self {
+            Self::Random => "",
+            Self::Uuid => "0123456789abcdef-",
+            Self::Timestamp => std::str::from_utf8(BASE32).expect("base32 is ASCII"),
+        }
+    }
+
+    /// A new ID, random ones of `length` characters from `alphabet`.
+    pub fn generate(self, alphabet: &IdAlphabet, length: usize) -> String {
+        match self {
+            Self::Random => alphabet.generate(length),
+            Self::Uuid => uuid_v4(),
+            Self::Timestamp => timestamp_id(),
+        }
+    }
+}
+
+impl fmt::Display for IdScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Random => "random",
+            Self::Uuid => "uuid",
+            Self::Timestamp => "timestamp",
+        })
+    }
+}
+
+fn uuid_v4() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    // Version 4, variant 1 (RFC 9562)
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn timestamp_id() -> String {
+    let mut millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis());
+    let mut id = vec![b'0'; TIMESTAMP_LENGTH];
+    for place in id.iter_mut().rev() {
+        *place = BASE32[(millis % 32) as usize];
+        millis /= 32;
+    }
+    let chars = Slice::new(BASE32).expect("the base32 alphabet isn't empty");
+    id.extend(
+        rand::thread_rng()
+            .sample_iter(chars)
+            .take(TIMESTAMP_SUFFIX_LENGTH),
+    );
+    String::from_utf8(id).expect("base32 is ASCII")
+}
@@ -0,0 +1,66 @@
+use ipnetwork::IpNetwork;
+use std::net::IpAddr;
+
+// --- IP Filter ---
+// Restricts who may create pastes with RBIN_ALLOW_IPS and RBIN_DENY_IPS, lists
+// of networks like `10.0.0.0/8, 2001:db8::/32` (plain addresses work too).
+// Reading pastes is never restricted.
+#[derive(Debug)]
+pub struct IpFilter {
+    // Empty means everyone not denied
+    allow: Vec<IpNetwork>,
+    deny: Vec<IpNetwork>,
+}
+
+impl IpFilter {
+    /// A filter for the given lists, or `None` if both are empty and so
+    /// everyone is allowed.
+    pub fn new(allow: Vec<IpNetwork>, deny: Vec<IpNetwork>) -> Option<Self> {
+        (!allow.is_empty() || !deny.is_empty()).then_some(Self { allow, deny })
+    }
+
+    /// Parses a comma-separated list of networks, e.g. `10.0.0.0/8,::1`.
+    pub fn parse_list(spec: &str) -> Result<Vec<IpNetwork>, String> {
+        spec.split(',')
+            .map(str::trim)
+            .filter(|network| !network.is_empty())
+            .map(|network| {
+                network
+                    .parse()
+                    .map_err(|e| format!("invalid network '{}': {}", network, e))
+            })
+            .collect()
+    }
+
+    /// Whether `ip` may create pastes. The deny list wins over the allow list.
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        // Dual-stack sockets report IPv4 clients as ::ffff:a.b.c.d
+        let ip = ip.to_canonical();
+        if self.deny.iter().any(|network| network.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|network| network.contains(ip))
+    }
+}
+
+impl std::fmt::Display for IpFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |networks: &[IpNetwork]| {
+            networks
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match (self.allow.is_empty(), self.deny.is_empty()) {
+            (false, true) => write!(f, "allowing only {}", join(&self.allow)),
+            (true, false) => write!(f, "denying {}", join(&self.deny)),
+            _ => write!(
+                f,
+                "allowing only {}, except {}",
+                join(&self.allow),
+                join(&self.deny)
+            ),
+        }
+    }
+}
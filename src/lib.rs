@@ -0,0 +1,5388 @@
+//! rbin, a minimal pastebin. The `rbin` binary is a thin wrapper around
+//! `run`. To serve rbin as part of another axum app, build an `AppState` from
+//! a `Config` and mount `router` where it should live:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), String> {
+//! let state = rbin::AppState::new(rbin::Config::from_env()?).await?;
+//! let app = axum::Router::new().nest("/paste", rbin::router(state));
+//! # Ok(())
+//! # }
+//! ```
+
+use async_compression::tokio::bufread::{GzipDecoder, GzipEncoder};
+use axum::{
+    body::{Body, Bytes},
+    extract::{
+        connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo, DefaultBodyLimit, Form,
+        FromRequest, Multipart, Path, Query, Request, State,
+    },
+    http::{header, request::Parts, HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use blocklist::Blocklist;
+use cache::{CachedPaste, PasteCache};
+pub use cli::Args;
+pub use config::{Config, Source, Storage};
+use dotenvy::dotenv;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use encryption::Cipher;
+use error::ApiError;
+use futures_util::StreamExt;
+use id_alphabet::IdAlphabet;
+use id_scheme::IdScheme;
+use ip_filter::IpFilter;
+use meta::PasteMeta;
+use metrics::Metrics;
+use paste_limit::PasteLimit;
+use paste_lock::PasteLocks;
+use quota::DiskQuota;
+use rand::distributions::{Alphanumeric, DistString};
+use rate_limit::RateLimiter;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, SocketAddr},
+    process::ExitCode,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use storage::{FsStore, InMemoryStore, PasteStore, S3Store, SqliteStore};
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt, BufReader},
+};
+use tokio_util::{io::ReaderStream, sync::CancellationToken};
+use tower_http::{
+    compression::{predicate::DefaultPredicate, CompressionLayer, Predicate},
+    cors::{AllowOrigin, CorsLayer},
+    request_id::{MakeRequestId, PropagateRequestIdLayer, RequestId, SetRequestIdLayer},
+    set_header::SetResponseHeaderLayer,
+    timeout::{RequestBodyTimeoutLayer, TimeoutError, TimeoutLayer},
+    trace::TraceLayer,
+};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use webhook::Webhook;
+
+mod blocklist;
+mod cache;
+mod check;
+mod cli;
+mod config;
+mod content_type;
+mod encryption;
+mod error;
+mod highlight;
+mod html;
+mod id_alphabet;
+mod id_scheme;
+mod ip_filter;
+mod meta;
+mod metrics;
+mod negotiate;
+#[cfg(feature = "openapi")]
+mod openapi;
+mod paste_limit;
+mod paste_lock;
+mod qr;
+mod quota;
+mod rate_limit;
+mod storage;
+mod webhook;
+
+// --- Configuration Constants ---
+const DEFAULT_HOST: &str = "0.0.0.0";
+const DEFAULT_PORT: u16 = 3000;
+const DEFAULT_UNIX_SOCKET_MODE: u32 = 0o660;
+const DEFAULT_S3_REGION: &str = "us-east-1";
+const DEFAULT_PASTE_DIR: &str = "pastes";
+const DEFAULT_DB_PATH: &str = "rbin.db";
+const DEFAULT_ID_LENGTH: usize = 6;
+const MIN_ID_LENGTH: usize = 4;
+const MAX_ID_LENGTH: usize = 64;
+const MIN_CUSTOM_ID_LENGTH: usize = 1; // PUT /:id may pick any non-empty ID up to MAX_ID_LENGTH
+const MAX_ID_ATTEMPTS: usize = 10; // Regeneration attempts on ID collision
+const DEFAULT_CACHE_ENTRIES: usize = 0; // In-memory paste cache is off by default
+const DEFAULT_MAX_BATCH: u64 = 100; // Pastes per request of `POST /batch`
+const DEFAULT_FIELD_NAME: &str = "rbin"; // Form field of the paste content
+/// Response headers unless RBIN_EXTRA_HEADERS replaces them. Pastes are user
+/// content that browsers mustn't sniff into HTML, and their URLs shouldn't
+/// leak to the sites they link to.
+const DEFAULT_HEADERS: [(&str, &str); 2] = [
+    ("x-content-type-options", "nosniff"),
+    ("referrer-policy", "no-referrer"),
+];
+const DEFAULT_CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const QUOTA_RECOUNT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const TOKEN_LENGTH: usize = 32; // Length of secret deletion, edit and append tokens
+const PASSWORD_HEADER: &str = "X-Paste-Password";
+const EDIT_TOKEN_HEADER: &str = "X-Edit-Token";
+const APPEND_TOKEN_HEADER: &str = "X-Append-Token";
+const CHECKSUM_HEADER: &str = "x-content-sha256";
+const REQUEST_ID_HEADER: &str = "x-request-id";
+const REQUEST_ID_LENGTH: usize = 16; // Short enough to quote in a bug report
+const DEFAULT_LIST_LIMIT: usize = 100; // Pastes per page of `GET /admin/pastes`
+const MAX_LIST_LIMIT: usize = 1000;
+const SEARCH_LIMIT: usize = 50; // Results of `GET /admin/search`
+const MIN_SEARCH_LENGTH: usize = 3;
+const SEARCH_MAX_SIZE: u64 = 1024 * 1024; // Larger pastes are skipped when scanning for a search
+const SNIPPET_CONTEXT: usize = 40; // Bytes of text each side of a search match
+const PREVIEW_LENGTH: usize = 200; // Characters of content per paste in `GET /admin/pastes`
+const MAX_FILE_NAME_LENGTH: usize = 255; // Longer upload file names are truncated
+const PLAIN_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+const HTML_CONTENT_TYPE: &str = "text/html; charset=utf-8";
+// Forms of error messages, plain text unless the client prefers another
+const ERROR_FORMATS: &[&str] = &["text/plain", "text/html", "application/json"];
+const GZIP_MIN_SIZE: usize = 1024; // Smaller responses aren't worth compressing
+const STREAM_MIN_SIZE: u64 = 64 * 1024; // Larger plain text pastes are streamed from disk
+const PASTE_CACHE_CONTROL: &str = "public, max-age=60, must-revalidate"; // Briefly, pastes may be edited
+const FAVICON: &[u8] = include_bytes!("favicon.ico");
+const FAVICON_CACHE_CONTROL: &str = "public, max-age=604800"; // A week
+const TAIL_POLL_INTERVAL: Duration = Duration::from_secs(1); // How often `GET /:id/tail` checks for changes
+const TAIL_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60); // Unchanged pastes stop being followed
+const DEFAULT_MAX_SIZE: usize = 1024 * 1024 * 10; // 10 MiB
+const FORM_OVERHEAD: usize = 64 * 1024; // Room for multipart boundaries and other fields
+                                        // Default log level for tower_http requests if RUST_LOG is not set
+const DEFAULT_REQUEST_LOG_LEVEL: &str = "debug";
+
+// --- Application State ---
+#[derive(Clone)]
+pub struct AppState {
+    store: Arc<dyn PasteStore>,
+    id_length: usize,
+    id_alphabet: Arc<IdAlphabet>,
+    id_scheme: IdScheme,
+    id_prefix: Arc<str>,
+    // Characters valid in IDs, see `validate_id`
+    id_chars: Arc<[char]>,
+    max_size: usize,
+    max_lines: Option<u64>,
+    max_line_length: Option<u64>,
+    min_length: Option<u64>,
+    allow_empty: bool,
+    // Form field of the paste content
+    field_name: Arc<str>,
+    default_ttl: Option<Duration>,
+    max_batch: u64,
+    metrics: Arc<Metrics>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    ip_filter: Option<Arc<IpFilter>>,
+    blocklist: Option<Arc<Blocklist>>,
+    compress: bool,
+    cipher: Option<Arc<Cipher>>,
+    cache: Option<Arc<PasteCache>>,
+    base_url: Option<Arc<str>>,
+    allow_html: bool,
+    highlight_theme: Arc<str>,
+    light_theme: Option<Arc<str>>,
+    disable_index: bool,
+    allow_robots: bool,
+    read_only: bool,
+    tls: bool,
+    request_timeout: Option<Duration>,
+    // Whether to believe the X-Forwarded-* headers, see `base_url` and `client_ip`
+    trust_proxy: bool,
+    quota: Option<Arc<DiskQuota>>,
+    // Evicts the oldest pastes, see RBIN_MAX_PASTES
+    paste_limit: Option<Arc<PasteLimit>>,
+    // Enables `GET /admin/pastes` when set
+    admin_token: Option<Arc<str>>,
+    webhook: Option<Arc<Webhook>>,
+    // Guards read-modify-write updates of metadata sidecars, see `record_view`
+    paste_locks: Arc<PasteLocks>,
+    // For the uptime in `GET /stats`
+    started: Instant,
+    // Cancelled on shutdown, ends long-lived responses like `GET /:id/tail`
+    shutdown: CancellationToken,
+    // Cross-origin access, see RBIN_CORS_ORIGINS
+    cors: Option<CorsLayer>,
+    // Set on every response, see RBIN_EXTRA_HEADERS
+    extra_headers: Arc<[(HeaderName, HeaderValue)]>,
+}
+
+impl AppState {
+    /// Sets up the store and everything else `config` asks for. Also starts
+    /// the periodic cleanup of expired pastes and, with a disk quota, the
+    /// recount of the disk usage. The listener settings (RBIN_HOST, RBIN_PORT
+    /// and so on) are left to whoever serves the router.
+    pub async fn new(config: Config) -> Result<Self, String> {
+        let Config {
+            hosts: _,
+            port: _,
+            port_file: _,
+            unix_socket: _,
+            unix_socket_mode: _,
+            storage,
+            paste_dir,
+            id_length,
+            id_alphabet,
+            id_scheme,
+            id_prefix,
+            max_size,
+            max_lines,
+            max_line_length,
+            min_length,
+            allow_empty,
+            field_name,
+            default_ttl,
+            max_batch,
+            rate_limit: rate_limiter,
+            ip_filter,
+            blocklist,
+            compress,
+            cipher,
+            allow_html,
+            highlight_theme,
+            light_theme,
+            disable_index,
+            allow_robots,
+            read_only,
+            cache_entries,
+            base_url,
+            cleanup_interval,
+            request_timeout,
+            tls,
+            trust_proxy,
+            cors_origins,
+            extra_headers,
+            max_total_bytes: quota_limit,
+            max_pastes,
+            admin_token,
+            webhook_url,
+            request_log_level: _,
+            json_logs: _,
+        } = config;
+        let rate_limiter = rate_limiter.map(Arc::new);
+        let ip_filter = ip_filter.map(Arc::new);
+        let cache = PasteCache::new(cache_entries).map(Arc::new);
+        let base_url = base_url.map(Arc::from);
+        let cors = cors_origins.as_deref().map(cors_layer);
+        let admin_token = admin_token.map(Arc::from);
+        let webhook = match webhook_url.map(Webhook::new).transpose() {
+            Ok(webhook) => webhook.map(Arc::new),
+            Err(e) => {
+                tracing::error!("Failed to set up the webhook client: {}", e);
+                return Err(format!("Failed to set up the webhook client: {}", e));
+            }
+        };
+
+        let store = open_store(storage, paste_dir, cipher.is_some()).await?;
+        match id_scheme {
+            IdScheme::Random => {
+                tracing::info!("Using paste ID length: {}", id_length);
+                tracing::info!("Using paste ID alphabet: {}", id_alphabet);
+            }
+            scheme => tracing::info!("Using paste ID scheme: {}", scheme),
+        }
+        if !id_prefix.is_empty() {
+            tracing::info!("Using paste ID prefix: {}", id_prefix);
+        }
+        tracing::info!("Accepting pastes of up to {}", format_size(max_size));
+        if let Some(lines) = max_lines {
+            tracing::info!("Accepting text pastes of up to {} lines", lines);
+        }
+        if let Some(length) = max_line_length {
+            tracing::info!("Accepting text lines of up to {} characters", length);
+        }
+        if let Some(length) = min_length {
+            tracing::info!("Accepting text pastes of at least {} characters", length);
+        }
+        if allow_empty {
+            tracing::info!("Accepting empty pastes");
+        }
+        if field_name != DEFAULT_FIELD_NAME {
+            tracing::info!("Reading paste content from the '{}' form field", field_name);
+        }
+        match default_ttl {
+            Some(ttl) => tracing::info!(
+                "Pastes expire by default after {}",
+                humantime::format_duration(ttl)
+            ),
+            None => tracing::info!("Pastes do not expire by default"),
+        }
+        match &rate_limiter {
+            Some(limiter) => tracing::info!("Rate limiting new pastes to {} per client", limiter),
+            None => tracing::info!("Rate limiting disabled"),
+        }
+        if let Some(filter) = &ip_filter {
+            tracing::info!("Restricting new pastes by client IP: {}", filter);
+        }
+        if let Some(blocklist) = &blocklist {
+            tracing::info!(
+                "Rejecting new pastes matching {} blocklist patterns",
+                blocklist.pattern_count()
+            );
+        }
+        if compress {
+            tracing::info!("Storing new pastes gzip compressed");
+        }
+        if cipher.is_some() {
+            tracing::info!("Encrypting new pastes at rest");
+        }
+        if allow_html {
+            tracing::warn!("Pastes may be served as HTML, including any scripts they contain");
+        }
+        tracing::info!("Highlighting pastes with theme {}", highlight_theme);
+        if let Some(theme) = &light_theme {
+            tracing::info!("Highlighting pastes with theme {} in light mode", theme);
+        }
+        if disable_index {
+            tracing::info!("Index page disabled, GET / answers 404");
+        }
+        if allow_robots {
+            tracing::info!("Allowing search engines to index pastes");
+        }
+        if read_only {
+            tracing::warn!("Read-only mode, new pastes, edits and deletions are rejected");
+        }
+        match &cache {
+            Some(_) => tracing::info!("Caching up to {} pastes in memory", cache_entries),
+            None => tracing::info!("Paste cache disabled"),
+        }
+        match &base_url {
+            Some(url) => tracing::info!("Using base URL {} for paste links", url),
+            None => tracing::info!("Deriving paste links from the request Host header"),
+        }
+        if trust_proxy {
+            tracing::info!("Trusting X-Forwarded-For, X-Forwarded-Host and X-Forwarded-Proto");
+        }
+        match cleanup_interval {
+            Some(interval) => tracing::info!(
+                "Removing expired pastes every {}",
+                humantime::format_duration(interval)
+            ),
+            None => tracing::info!("Periodic cleanup of expired pastes disabled"),
+        }
+        match request_timeout {
+            Some(timeout) => tracing::info!(
+                "Timing out requests after {}",
+                humantime::format_duration(timeout)
+            ),
+            None => tracing::info!("Request timeout disabled"),
+        }
+        if admin_token.is_some() {
+            tracing::info!("Admin endpoints enabled");
+        }
+        // The URL itself is often a secret (Slack, Discord), keep it out of the log
+        if webhook.is_some() {
+            tracing::info!("Webhook notifications for new pastes enabled");
+        }
+
+        let existing = store.list().await.unwrap_or_else(|e| {
+            tracing::warn!("Failed to count existing pastes: {}", e);
+            Vec::new()
+        });
+        let stored_pastes = existing.len() as u64;
+        tracing::info!("Found {} existing pastes", stored_pastes);
+        let quota = match quota_limit {
+            Some(limit) => {
+                let used = store.usage().await.unwrap_or_else(|e| {
+                    tracing::warn!("Failed to measure disk usage: {}", e);
+                    0
+                });
+                tracing::info!(
+                    "Disk quota: {} of {} used",
+                    format_size(used as usize),
+                    format_size(limit as usize)
+                );
+                if used >= limit {
+                    tracing::warn!("Disk quota exhausted, new pastes will be rejected");
+                }
+                Some(Arc::new(DiskQuota::new(limit, used)))
+            }
+            None => None,
+        };
+        let paste_limit = match max_pastes {
+            Some(max) => {
+                let mut pastes = Vec::with_capacity(existing.len());
+                for id in existing {
+                    let paste_meta = store.read_meta(&id).await.unwrap_or_else(|e| {
+                        tracing::warn!("Failed to read metadata for paste {}: {}", id, e);
+                        None
+                    });
+                    // Pastes without any time are the first to go
+                    let created = creation_time(&*store, cipher.as_ref(), &id, paste_meta.as_ref())
+                        .await
+                        .unwrap_or(SystemTime::UNIX_EPOCH);
+                    let evictable = paste_meta.is_none_or(|m| m.views_left.is_none());
+                    pastes.push((created, id, evictable));
+                }
+                tracing::info!("Keeping up to {} pastes, evicting the oldest", max);
+                if pastes.len() as u64 > max {
+                    tracing::warn!(
+                        "{} pastes stored, more than RBIN_MAX_PASTES, the oldest are evicted with the next new ones",
+                        pastes.len()
+                    );
+                }
+                Some(Arc::new(PasteLimit::new(max, pastes)))
+            }
+            None => None,
+        };
+
+        let mut id_chars: Vec<char> = id_alphabet.chars().to_vec();
+        id_chars.extend(id_prefix.chars().chain(id_scheme.chars().chars()));
+        id_chars.extend(['-', '_']);
+        id_chars.sort_unstable();
+        id_chars.dedup();
+
+        let app_state = AppState {
+            store,
+            id_length,
+            id_alphabet: Arc::new(id_alphabet),
+            id_chars: id_chars.into(),
+            id_scheme,
+            id_prefix: Arc::from(id_prefix),
+            max_size,
+            max_lines,
+            max_line_length,
+            min_length,
+            allow_empty,
+            field_name: Arc::from(field_name),
+            default_ttl,
+            max_batch,
+            metrics: Arc::new(Metrics::new(stored_pastes)),
+            rate_limiter,
+            ip_filter,
+            blocklist: blocklist.map(Arc::new),
+            compress,
+            cipher: cipher.map(Arc::new),
+            cache,
+            base_url,
+            allow_html,
+            highlight_theme: Arc::from(highlight_theme),
+            light_theme: light_theme.map(Arc::from),
+            disable_index,
+            allow_robots,
+            read_only,
+            tls: tls.is_some(),
+            request_timeout,
+            trust_proxy,
+            quota,
+            paste_limit,
+            admin_token,
+            webhook,
+            paste_locks: Arc::default(),
+            started: Instant::now(),
+            shutdown: CancellationToken::new(),
+            cors,
+            extra_headers: extra_headers.into(),
+        };
+        if let Some(interval) = cleanup_interval {
+            tokio::spawn(cleanup_expired_pastes(app_state.clone(), interval));
+        }
+        if let Some(quota) = &app_state.quota {
+            tokio::spawn(recount_disk_usage(app_state.store.clone(), quota.clone()));
+        }
+        Ok(app_state)
+    }
+
+    /// Ends long-lived responses like `GET /:id/tail`, for a graceful shutdown.
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+}
+
+/// What the `rbin` binary does: reads the configuration from `args`, the
+/// environment and the config file, sets up logging and serves until Ctrl+C or
+/// SIGTERM. With `--check` it only validates the configuration. Fails if
+/// anything is wrong with it or the server can't start or stops with an error.
+pub async fn run(args: Args) -> ExitCode {
+    // Load .env file if present
+    if let Ok(path) = dotenv() {
+        println!("Loaded .env file from: {:?}", path); // Use println as logging isn't up yet
+    }
+    if args.check {
+        return if check::run(&args).await {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        };
+    }
+
+    // Read Configuration
+    // Settings come from the environment and the optional RBIN_CONFIG file.
+    // Read them first, the request log level is needed to set up logging.
+    let config = match Config::from_args(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {}", e); // Logging isn't up yet
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // --- Initialize Logging ---
+
+    // The request log level (RBIN_REQUEST_LOG_LEVEL) controls tower_http
+    // *only* if RUST_LOG is not set.
+    let request_log_level = config.request_log_level.clone();
+
+    // Set up the log filter:
+    // 1. Try to use RUST_LOG environment variable if set.
+    // 2. If RUST_LOG is not set, construct a default filter using:
+    //    - "info" for the application crate (`rbin`)
+    //    - The level from RBIN_REQUEST_LOG_LEVEL for `tower_http`
+    let log_filter = EnvFilter::try_from_default_env()
+        .or_else(|_| {
+            // RUST_LOG was not set, build the default filter string
+            let default_app_level = "info"; // Default level for our app
+            let default_filter_str = format!(
+                "{},tower_http={}", // Comma-separated directives
+                default_app_level,
+                request_log_level // Use the configured level for requests
+            );
+            EnvFilter::try_new(default_filter_str) // Parse the constructed default
+        })
+        .expect("Failed to parse log filter configuration"); // Panic if parsing fails
+
+    // Initialize the tracing subscriber
+    // RBIN_LOG_FORMAT=json writes one JSON object per line, for log aggregation
+    let json_logs = config.json_logs;
+    tracing_subscriber::registry()
+        .with(log_filter) // Apply the determined filter
+        .with(json_logs.then(|| tracing_subscriber::fmt::layer().json()))
+        .with((!json_logs).then(tracing_subscriber::fmt::layer)) // Format logs for printing
+        .init(); // Set as the global default subscriber
+
+    // Log service start (now respects the filter)
+    tracing::info!("Starting rbin...");
+    tracing::info!("Default request log level set to: {}", request_log_level); // Log the request level being used in default config
+
+    // The listeners are set up here, the rest of the settings go to the state
+    let hosts = config.hosts.clone();
+    let mut port = config.port;
+    let port_file = config.port_file.clone();
+    let unix_socket = config.unix_socket.clone();
+    let unix_socket_mode = config.unix_socket_mode;
+    let tls_paths = config.tls.clone();
+    let app_state = match AppState::new(config).await {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let shutdown = app_state.shutdown.clone();
+    let app = router(app_state);
+
+    // Load the TLS certificate before binding so a bad one fails startup right away
+    let tls_config = match tls_paths {
+        Some((cert, key)) => {
+            // Only one crypto provider is compiled in, so this can't conflict
+            let _ = rustls::crypto::ring::default_provider().install_default();
+            match RustlsConfig::from_pem_file(&cert, &key).await {
+                Ok(config) => {
+                    tracing::info!("Serving HTTPS with certificate {}", cert);
+                    Some(config)
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to load TLS certificate {} / key {}: {}",
+                        cert,
+                        key,
+                        e
+                    );
+                    eprintln!(
+                        "Error: Could not load the TLS certificate {} or key {}: {}",
+                        cert, key, e
+                    );
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Behind a proxy on the same host, a Unix socket replaces TCP altogether
+    #[cfg(unix)]
+    if let Some(path) = unix_socket {
+        tracing::info!(
+            "rbin configured. Attempting to listen on {}",
+            path.display()
+        );
+        let listener = match bind_unix(&path, unix_socket_mode) {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::error!("Failed to bind to socket {}: {}", path.display(), e);
+                eprintln!("Error: Could not bind to socket {}: {}", path.display(), e);
+                return ExitCode::FAILURE;
+            }
+        };
+        tracing::info!("Successfully bound to {}. rbin is running.", path.display());
+        tokio::spawn(shutdown_signal(shutdown.clone()));
+        serve_unix(listener, app, shutdown).await;
+        if let Err(e) = std::fs::remove_file(&path) {
+            tracing::warn!("Failed to remove socket {}: {}", path.display(), e);
+        }
+        tracing::info!("rbin stopped");
+        return ExitCode::SUCCESS;
+    }
+    #[cfg(not(unix))]
+    let _ = (unix_socket, unix_socket_mode);
+
+    // Start Server, on every address of RBIN_HOST
+    let mut listeners = Vec::with_capacity(hosts.len());
+    // A lone `::` keeps taking IPv4 connections where the system allows it
+    let only_v6 = hosts.len() > 1;
+    for host in hosts {
+        let addr = SocketAddr::from((host, port));
+        tracing::info!("rbin configured. Attempting to listen on {}", addr);
+        match bind(addr, only_v6) {
+            Ok(listener) => {
+                let bound = listener.local_addr().unwrap_or(addr);
+                tracing::info!("Successfully bound to {}", bound);
+                // With RBIN_PORT=0, the other addresses get the same free port
+                port = bound.port();
+                listeners.push(listener);
+            }
+            Err(e) => {
+                tracing::error!("Failed to bind to address {}: {}", addr, e);
+                eprintln!("Error: Could not bind to address {}. Is the port already in use or the IP address valid?", addr);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if let Some(path) = &port_file {
+        if let Err(e) = write_port_file(path, &listeners) {
+            tracing::error!("Failed to write port file {}: {}", path.display(), e);
+            eprintln!("Error: Could not write port file {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+    }
+    tracing::info!("rbin is running.");
+    tokio::spawn(shutdown_signal(shutdown.clone()));
+
+    // Connection info provides the client address for rate limiting
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    let mut servers = tokio::task::JoinSet::new();
+    for listener in listeners {
+        let service = service.clone();
+        let shutdown = shutdown.clone();
+        let tls_config = tls_config.clone();
+        servers.spawn(async move {
+            match tls_config {
+                Some(config) => serve_tls(listener, config, service, shutdown).await,
+                // In-flight requests (and so paste writes) finish before the server exits
+                None => {
+                    axum::serve(listener, service)
+                        .with_graceful_shutdown(shutdown.cancelled_owned())
+                        .await
+                }
+            }
+        });
+    }
+    let mut status = ExitCode::SUCCESS;
+    while let Some(result) = servers.join_next().await {
+        let result = result.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        if let Err(e) = result {
+            tracing::error!("Server error: {}", e);
+            eprintln!("Server encountered an error: {}", e);
+            status = ExitCode::FAILURE;
+        }
+    }
+    if let Some(path) = &port_file {
+        if let Err(e) = std::fs::remove_file(path) {
+            tracing::warn!("Failed to remove port file {}: {}", path.display(), e);
+        }
+    }
+    tracing::info!("rbin stopped");
+    status
+}
+
+/// Opens the paste store of RBIN_STORAGE, creating RBIN_PASTE_DIR for the
+/// filesystem store. Full-text search in SQLite needs `encrypted` to be unset.
+async fn open_store(
+    storage: Storage,
+    paste_dir: std::path::PathBuf,
+    encrypted: bool,
+) -> Result<Arc<dyn PasteStore>, String> {
+    let store: Arc<dyn PasteStore> = match storage {
+        Storage::Filesystem => {
+            // Ensure Paste Directory Exists
+            if let Err(e) = fs::create_dir_all(&paste_dir).await {
+                tracing::error!("Failed to create paste directory {:?}: {}", paste_dir, e);
+                return Err(format!(
+                    "Could not create paste directory at {:?}. Please check permissions.",
+                    paste_dir
+                ));
+            }
+            tracing::info!("Using paste directory: {:?}", paste_dir);
+            Arc::new(FsStore::new(paste_dir))
+        }
+        Storage::Memory => {
+            tracing::warn!("Keeping pastes in memory, they are lost on restart");
+            Arc::new(InMemoryStore::new())
+        }
+        Storage::S3(s3) => match S3Store::new(&s3) {
+            Ok(store) => {
+                tracing::info!("Storing pastes in S3 bucket: {}", s3.bucket);
+                Arc::new(store)
+            }
+            Err(e) => {
+                tracing::error!("Failed to set up the S3 store: {}", e);
+                return Err(format!("Failed to set up the S3 store: {}", e));
+            }
+        },
+        Storage::Sqlite(path) => match SqliteStore::open(&path, !encrypted) {
+            Ok(store) => {
+                tracing::info!("Using paste database: {:?}", path);
+                Arc::new(store)
+            }
+            Err(e) => {
+                tracing::error!("Failed to open paste database {:?}: {}", path, e);
+                return Err(format!(
+                    "Could not open the paste database at {:?}: {}",
+                    path, e
+                ));
+            }
+        },
+    };
+    Ok(store)
+}
+
+/// Writes the bound addresses to RBIN_PORT_FILE, one per line like
+/// `127.0.0.1:41237`. Renamed into place, so whoever waits for the file never
+/// reads half of it.
+fn write_port_file(
+    path: &std::path::Path,
+    listeners: &[tokio::net::TcpListener],
+) -> std::io::Result<()> {
+    let mut addrs = String::new();
+    for listener in listeners {
+        addrs.push_str(&format!("{}\n", listener.local_addr()?));
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    std::fs::write(&tmp_path, addrs)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+/// The routes and layers of rbin, everything but binding a listener. Serve it
+/// with `into_make_service_with_connect_info::<SocketAddr>()`, the client
+/// address is needed for rate limiting and RBIN_ALLOW_IPS/RBIN_DENY_IPS.
+pub fn router(state: AppState) -> Router {
+    let rate_limit = middleware::from_fn_with_state(state.clone(), rate_limit_middleware);
+    let ip_filter = middleware::from_fn_with_state(state.clone(), ip_filter_middleware);
+    let request_id_in_errors = middleware::from_fn(request_id_in_errors);
+
+    let mut app = Router::new()
+        .route("/", get(handle_root_get).fallback(method_not_allowed))
+        .route("/metrics", get(handle_metrics).fallback(method_not_allowed))
+        .route("/stats", get(handle_stats).fallback(method_not_allowed))
+        .route(
+            "/admin/pastes",
+            get(list_pastes).fallback(method_not_allowed),
+        )
+        .route(
+            "/admin/search",
+            get(search_pastes).fallback(method_not_allowed),
+        )
+        .route(
+            "/:id",
+            get(retrieve_paste)
+                .fallback(method_not_allowed)
+                .layer(request_id_in_errors.clone()),
+        )
+        .route("/batch", post(batch_pastes).fallback(method_not_allowed))
+        .route("/:id", delete(delete_paste))
+        .route("/:id", post(unlock_paste))
+        .route(
+            "/:id/raw",
+            get(retrieve_raw_paste).fallback(method_not_allowed),
+        )
+        .route(
+            "/:id/download",
+            get(download_paste).fallback(method_not_allowed),
+        )
+        .route("/:id/info", get(paste_info).fallback(method_not_allowed))
+        .route("/:id/qr", get(paste_qr).fallback(method_not_allowed))
+        .route("/:id/tail", get(tail_paste).fallback(method_not_allowed))
+        .route(
+            "/:id/",
+            get(bundle_index)
+                .post(unlock_bundle_index)
+                .fallback(method_not_allowed),
+        )
+        .route(
+            "/:id/:file",
+            get(bundle_file)
+                .post(unlock_bundle_file)
+                .fallback(method_not_allowed),
+        );
+    // Answered within RBIN_REQUEST_TIMEOUT, or 408. Streamed bodies like
+    // `GET /:id/tail` aren't cut off, only the wait for the response.
+    if let Some(timeout) = state.request_timeout {
+        app = app.layer(TimeoutLayer::new(timeout));
+    }
+    // Uploads may take longer, as long as the client keeps sending
+    app = app
+        .route(
+            "/",
+            post(handle_paste_submission)
+                .layer(rate_limit.clone())
+                .layer(ip_filter.clone())
+                .layer(request_id_in_errors.clone()),
+        )
+        .route(
+            "/:id",
+            put(handle_custom_paste)
+                .layer(rate_limit.clone())
+                .layer(ip_filter.clone())
+                .layer(request_id_in_errors.clone()),
+        )
+        .route(
+            "/:id/append",
+            post(append_paste)
+                .fallback(method_not_allowed)
+                .layer(rate_limit)
+                .layer(ip_filter.clone())
+                .layer(request_id_in_errors.clone()),
+        )
+        .fallback(strip_trailing_slash)
+        .layer(middleware::from_fn(error_pages));
+    // A request body that stalls for RBIN_REQUEST_TIMEOUT fails, see `read_failed`
+    if let Some(timeout) = state.request_timeout {
+        app = app.layer(RequestBodyTimeoutLayer::new(timeout));
+    }
+    #[cfg(feature = "openapi")]
+    {
+        app = app.route(
+            "/openapi.json",
+            get(openapi::handle_openapi).fallback(method_not_allowed),
+        );
+    }
+    if !state.allow_robots {
+        app = app.layer(middleware::map_response(noindex));
+    }
+    if let Some(cors) = state.cors.clone() {
+        app = app.layer(cors);
+    }
+    // Paste responses come gzipped already where that pays off, and responses
+    // with a Content-Encoding or Content-Range are left alone by the layer
+    let compression = CompressionLayer::new().compress_when(DefaultPredicate::new().and(
+        |_: StatusCode,
+         _: axum::http::Version,
+         _: &HeaderMap,
+         extensions: &axum::http::Extensions| {
+            extensions.get::<Uncompressed>().is_none()
+        },
+    ));
+    app = app.layer(compression);
+    let mut app = app
+        // tower_http logging is controlled by the EnvFilter. The span is on
+        // our own target so its request ID shows up in all of rbin's logs.
+        .layer(
+            TraceLayer::new_for_http().make_span_with(|request: &Request| {
+                let request_id = request
+                    .headers()
+                    .get(REQUEST_ID_HEADER)
+                    .and_then(|h| h.to_str().ok())
+                    .unwrap_or("-");
+                tracing::info_span!(
+                    "request",
+                    method = %request.method(),
+                    uri = %request.uri(),
+                    request_id = %request_id,
+                )
+            }),
+        )
+        // Outside the trace layer, which needs the ID for its span
+        .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+            REQUEST_ID_HEADER,
+        )))
+        .layer(SetRequestIdLayer::new(
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            MakeRequestToken,
+        ))
+        // Leave the exact limit to `write_chunk`, which reports it properly
+        .layer(DefaultBodyLimit::max(
+            state.max_size.saturating_add(FORM_OVERHEAD),
+        ))
+        // Probes and the favicon are added after the layers so they don't
+        // flood the request log
+        .route("/health", get(handle_health).fallback(method_not_allowed))
+        .route("/ready", get(handle_ready).fallback(method_not_allowed))
+        .route(
+            "/favicon.ico",
+            get(handle_favicon).fallback(method_not_allowed),
+        )
+        .route(
+            "/robots.txt",
+            get(handle_robots).fallback(method_not_allowed),
+        );
+    // Around the probes too, so really every response gets them. Handlers
+    // that set a default header themselves keep their value.
+    for (name, value) in DEFAULT_HEADERS {
+        let name = HeaderName::from_static(name);
+        if !state.extra_headers.iter().any(|(extra, _)| *extra == name) {
+            let value = HeaderValue::from_static(value);
+            app = app.layer(SetResponseHeaderLayer::if_not_present(name, value));
+        }
+    }
+    for (name, value) in state.extra_headers.iter() {
+        // An empty value only drops the default
+        if !value.is_empty() {
+            let layer = SetResponseHeaderLayer::overriding(name.clone(), value.clone());
+            app = app.layer(layer);
+        }
+    }
+    app.with_state(state)
+}
+
+/// Binds a listener on `addr`. With `only_v6`, an IPv6 listener doesn't take
+/// IPv4 connections too, so `0.0.0.0` and `::` can be bound side by side.
+fn bind(addr: SocketAddr, only_v6: bool) -> std::io::Result<tokio::net::TcpListener> {
+    let socket = socket2::Socket::new(
+        socket2::Domain::for_address(addr),
+        socket2::Type::STREAM,
+        Some(socket2::Protocol::TCP),
+    )?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(only_v6)?;
+    }
+    // Like std, so restarts don't wait for old connections to time out
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    tokio::net::TcpListener::from_std(socket.into())
+}
+
+/// Binds a Unix socket at `path` with permissions `mode`. A socket left behind
+/// by an earlier run is replaced, any other file is left alone.
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path, mode: u32) -> std::io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "a file that isn't a socket is in the way",
+            ))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok(listener)
+}
+
+/// Serves plain HTTP on a Unix socket until `shutdown`, then lets open
+/// connections finish like the TCP server does.
+#[cfg(unix)]
+async fn serve_unix(listener: tokio::net::UnixListener, app: Router, shutdown: CancellationToken) {
+    use hyper_util::{
+        rt::{TokioExecutor, TokioIo},
+        server::{conn::auto, graceful::GracefulShutdown},
+        service::TowerToHyperService,
+    };
+
+    // Socket peers have no IP address, they are local processes. Behind a
+    // proxy, RBIN_TRUST_PROXY gives the real client address.
+    let app = app.layer(axum::Extension(ConnectInfo(SocketAddr::from((
+        std::net::Ipv4Addr::LOCALHOST,
+        0,
+    )))));
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let graceful = GracefulShutdown::new();
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    // E.g. out of file descriptors, give connections time to close
+                    tracing::error!("Failed to accept connection: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            },
+            () = shutdown.cancelled() => break,
+        };
+        let service = TowerToHyperService::new(app.clone());
+        let connection = builder
+            .serve_connection(TokioIo::new(stream), service)
+            .into_owned();
+        let connection = graceful.watch(connection);
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::debug!("Connection error: {}", e);
+            }
+        });
+    }
+    drop(listener);
+    graceful.shutdown().await;
+}
+
+/// Serves HTTPS on an already bound listener, shutting down like the plain server.
+async fn serve_tls(
+    listener: tokio::net::TcpListener,
+    config: RustlsConfig,
+    service: IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    shutdown: CancellationToken,
+) -> std::io::Result<()> {
+    let handle = axum_server::Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.cancelled().await;
+            handle.graceful_shutdown(None);
+        }
+    });
+    axum_server::from_tcp_rustls(listener.into_std()?, config)
+        .handle(handle)
+        .serve(service)
+        .await
+}
+
+/// Builds the CORS layer for a comma-separated origin allowlist, or `*` for any
+/// origin. Invalid origins are skipped with a warning.
+fn cors_layer(origins: &str) -> CorsLayer {
+    let allow_origin = if origins.trim() == "*" {
+        tracing::info!("Allowing cross-origin requests from any origin");
+        AllowOrigin::any()
+    } else {
+        let origins: Vec<HeaderValue> = origins
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .filter_map(|origin| match HeaderValue::from_str(origin) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    tracing::warn!("Ignoring invalid CORS origin '{}': {}", origin, e);
+                    None
+                }
+            })
+            .collect();
+        tracing::info!("Allowing cross-origin requests from {:?}", origins);
+        AllowOrigin::list(origins)
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            HeaderName::from_static("x-delete-token"),
+            HeaderName::from_static("x-edit-token"),
+            HeaderName::from_static("x-append-token"),
+            HeaderName::from_static("x-paste-password"),
+        ])
+        .expose_headers([
+            header::LOCATION,
+            HeaderName::from_static("x-delete-token"),
+            HeaderName::from_static("x-edit-token"),
+            HeaderName::from_static("x-append-token"),
+        ])
+}
+
+/// Completes on Ctrl-C or SIGTERM (e.g. `docker stop`), cancelling `shutdown`
+/// so requests that would never finish by themselves end too.
+async fn shutdown_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+    tracing::info!("Shutting down gracefully");
+    shutdown.cancel();
+}
+
+// --- Expired Paste Cleanup ---
+// Expired pastes are removed lazily when requested, this catches the rest.
+async fn cleanup_expired_pastes(state: AppState, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        match remove_expired_pastes(&state).await {
+            Ok(removed) => tracing::info!("Cleanup removed {} expired pastes", removed),
+            Err(e) => tracing::error!("Cleanup of expired pastes failed: {}", e),
+        }
+    }
+}
+
+async fn remove_expired_pastes(state: &AppState) -> std::io::Result<u64> {
+    let mut removed = 0;
+    for id in state.store.list().await? {
+        match state.store.read_meta(&id).await {
+            Ok(Some(paste_meta)) if paste_meta.is_expired() => {
+                tracing::debug!("Removing expired paste {}", id);
+                remove_paste(state, &id).await;
+                removed += 1;
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Skipping paste {} in cleanup: {}", id, e),
+        }
+    }
+    Ok(removed)
+}
+
+// --- Disk Quota Recount ---
+// Corrects drift of the tracked disk usage, e.g. from files removed by hand.
+async fn recount_disk_usage(store: Arc<dyn PasteStore>, quota: Arc<DiskQuota>) {
+    let mut ticker = tokio::time::interval(QUOTA_RECOUNT_INTERVAL);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // The first tick completes immediately, right after the startup scan
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        match store.usage().await {
+            Ok(used) => {
+                tracing::debug!("Recounted disk usage: {} bytes", used);
+                quota.recount(used);
+            }
+            Err(e) => tracing::error!("Failed to recount disk usage: {}", e),
+        }
+    }
+}
+
+// --- Handler for GET / ---
+// Browsers get a form to paste from, curl and friends the usage help.
+pub async fn handle_root_get(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    // Private instances needn't explain themselves
+    if state.disable_index {
+        return (StatusCode::NOT_FOUND, "Not found.").into_response();
+    }
+    let vary = [(header::VARY, HeaderValue::from_static("accept"))];
+    if negotiate::preferred(&headers, &["text/plain", "text/html"]) == "text/html" {
+        tracing::debug!("Serving root HTML form.");
+        return (
+            vary,
+            Html(html::index_page(state.default_ttl, &state.field_name)),
+        )
+            .into_response();
+    }
+    tracing::debug!("Serving root plain text info.");
+    let field = &state.field_name;
+    let id_chars = id_alphabet::describe(&state.id_chars);
+    let plain_text_content = format!(
+        r#"rbin - Simple Command-Line Pastebin
+===================================
+
+Usage:
+------
+Pipe text using curl (or similar tools) with the form field name '{field}':
+
+  echo "Your text here" | curl -F '{field}=<-' http://<host>:<port>/
+
+Or paste from a file:
+
+  cat your_file.txt | curl -F '{field}=<-' http://<host>:<port>/
+
+Non-form request bodies are stored as-is:
+
+  curl --data-binary @your_file.txt http://<host>:<port>/
+
+Urlencoded forms with the same fields work too:
+
+  curl --data-urlencode '{field}=Your text here' http://<host>:<port>/
+
+Binary files (images, archives, ...) are served back as-is with their type:
+
+  curl -F '{field}=@screenshot.png' http://<host>:<port>/
+
+rbin will respond with a URL like http://<host>:<port>/<id>
+and secret deletion, edit and append tokens in the X-Delete-Token,
+X-Edit-Token and X-Append-Token response headers.
+
+Set an expiry with the optional 'expires' field (e.g. 30m, 1h, 7d):
+
+  echo "Temporary text" | curl -F '{field}=<-' -F 'expires=1h' http://<host>:<port>/
+
+Or have it removed after a number of reads with 'max_views':
+
+  echo "hunter2" | curl -F '{field}=<-' -F 'max_views=3' http://<host>:<port>/
+
+Append a file extension to the paste URL for a syntax highlighted HTML view:
+
+  http://<host>:<port>/<id>.rs
+
+Choose your own ID ({id_chars}) with PUT:
+
+  echo "My notes" | curl -X PUT -F '{field}=<-' http://<host>:<port>/my-notes
+
+Protect a paste with the optional 'password' field. Readers then have to send
+it in the X-Paste-Password header (browsers get a password form):
+
+  echo "Secret" | curl -F '{field}=<-' -F 'password=hunter2' http://<host>:<port>/
+  curl -H 'X-Paste-Password: hunter2' http://<host>:<port>/<id>
+
+Prometheus metrics are available at /metrics, an overview as JSON at /stats,
+liveness and readiness probes at /health and /ready.
+
+Pastes are served with the type given in the optional 'content_type' field, or
+guessed from the uploaded file name, e.g. application/json:
+
+  curl -F '{field}=@data.json' http://<host>:<port>/
+
+Use /<id>/raw to always get the plain text, whatever your client asks for, and
+/<id>/download to download it under the uploaded file name. Add ?charset= to
+/<id>/raw to transcode text, e.g. ?charset=windows-1252. /<id>/info shows
+how often a paste was viewed, its size and creation time as JSON. /<id>/qr is
+a QR code of the paste's URL, ?size= sets its pixels per module.
+
+Send several '{field}' fields to upload multiple files as one paste. /<id>/
+lists them and /<id>/<name> serves each file:
+
+  curl -F '{field}=@main.rs' -F '{field}=@Cargo.toml' http://<host>:<port>/
+
+Scripts can ask for JSON instead, both when uploading (id, url, delete_token,
+edit_token and append_token) and when reading a paste (id, content, created,
+...):
+
+  echo "Hi" | curl -H 'Accept: application/json' -F '{field}=<-' http://<host>:<port>/
+
+Or for just the paste ID, add ?format=id (or the X-Rbin-Format: id header):
+
+  echo "Hi" | curl -F '{field}=<-' 'http://<host>:<port>/?format=id'
+
+The response has no trailing newline, ready for pbcopy or xclip. Add
+?newline=1 to get one.
+
+Read several pastes as JSON at once, e.g. for a dashboard:
+
+  curl -H 'Content-Type: application/json' -d '["<id>", "<id>"]' http://<host>:<port>/batch
+
+Mark content you encrypted yourself with ?encrypted=1, it is then always
+served as-is, never rendered or cached.
+
+Shorten a link: with ?shorten=1 the paste must be an http(s) URL, which
+opening the paste redirects to:
+
+  echo "https://example.com/" | curl --data-binary @- 'http://<host>:<port>/?shorten=1'
+
+Delete a paste with its deletion token:
+
+  curl -X DELETE -H 'X-Delete-Token: <token>' http://<host>:<port>/<id>
+
+Replace its content with the edit token:
+
+  echo "New text" | curl -X PUT -H 'X-Edit-Token: <token>' -F '{field}=<-' http://<host>:<port>/<id>
+
+Add content to its end with the append token, and follow it as it grows like
+tail -f:
+
+  echo "More text" | curl -H 'X-Append-Token: <token>' --data-binary @- http://<host>:<port>/<id>/append
+
+  curl -N http://<host>:<port>/<id>/tail
+
+Configuration (Environment Variables):
+--------------------------------------
+RBIN_CONFIG             : TOML or JSON file with any of the settings below, e.g. port = 8080 (Default: none)
+RBIN_HOST               : Listen IP address, or several comma-separated (Default: {})
+RBIN_PORT               : Listen port, 0 for any free one (Default: {})
+RBIN_PORT_FILE          : Write the addresses listened on to this file (Default: none)
+RBIN_UNIX_SOCKET        : Listen on this Unix socket instead of TCP (Default: none)
+RBIN_UNIX_SOCKET_MODE   : Octal permissions of the socket (Default: 660)
+RBIN_STORAGE            : Where pastes are kept, fs, memory, s3 or sqlite (Default: fs)
+RBIN_DB_PATH            : Database file for RBIN_STORAGE=sqlite (Default: "{}")
+RBIN_S3_BUCKET          : Bucket for RBIN_STORAGE=s3 (Default: none)
+RBIN_S3_REGION          : Region of the bucket (Default: us-east-1)
+RBIN_S3_ENDPOINT        : URL of an S3 compatible service other than AWS (Default: AWS)
+RBIN_S3_ACCESS_KEY      : Access key ID for the bucket (Default: AWS_ACCESS_KEY_ID)
+RBIN_S3_SECRET_KEY      : Secret access key for the bucket (Default: AWS_SECRET_ACCESS_KEY)
+RBIN_PASTE_DIR          : Directory for storing pastes (Default: "{}")
+RBIN_ID_LENGTH          : Length of generated paste IDs, {}-{} (Default: {})
+RBIN_ID_ALPHABET        : Characters of generated IDs: alphanumeric, base58, lowercase or the characters themselves (Default: alphanumeric)
+RBIN_ID_SCHEME          : How to generate IDs: random, uuid or timestamp (sortable) (Default: random)
+RBIN_ID_PREFIX          : Start of all new IDs, e.g. a- (Default: none)
+RBIN_MAX_SIZE           : Maximum paste size, e.g. 512k or 5M (Default: {})
+RBIN_MAX_LINES          : Maximum number of lines of a text paste (Default: unlimited)
+RBIN_MAX_LINE_LENGTH    : Maximum characters per line of a text paste (Default: unlimited)
+RBIN_MIN_LENGTH         : Minimum characters of a text paste, not counting surrounding whitespace (Default: anything non-empty)
+RBIN_ALLOW_EMPTY        : Set to 1 to store empty pastes instead of rejecting them (Default: off)
+RBIN_FIELD_NAME         : Form field of the paste content, e.g. file (Default: rbin)
+RBIN_MAX_TOTAL_BYTES    : Disk quota for all pastes together, e.g. 5G (Default: unlimited)
+RBIN_MAX_PASTES         : Maximum number of pastes, the oldest are evicted for new ones (Default: unlimited)
+RBIN_DEFAULT_TTL        : Lifetime of pastes without an 'expires' field, e.g. 7d (Default: never expire)
+RBIN_MAX_BATCH          : Maximum number of pastes per POST /batch request (Default: {})
+RBIN_RATE_LIMIT         : Max new pastes per client IP, e.g. 10/min or 100/hour (Default: unlimited)
+RBIN_ALLOW_IPS          : Comma-separated networks (CIDR) allowed to create pastes (Default: all)
+RBIN_DENY_IPS           : Comma-separated networks (CIDR) not allowed to create pastes (Default: none)
+RBIN_BLOCKLIST_FILE     : Substrings or /regexes/ new pastes must not contain, one per line (Default: none)
+RBIN_COMPRESS           : Set to 1 to store new pastes gzip compressed (Default: off)
+RBIN_ENCRYPTION_KEY     : Base64 32 byte key to encrypt new pastes at rest, e.g. `openssl rand -base64 32` (Default: off)
+RBIN_BASE_URL           : Public URL prefix for paste links, e.g. https://paste.example.com/sub (Default: from Host header)
+RBIN_ALLOW_HTML         : Set to 1 to allow pastes to be served as text/html or SVG (Default: off)
+RBIN_HIGHLIGHT_THEME    : Color theme of the HTML view, e.g. InspiredGitHub (Default: base16-ocean.dark)
+RBIN_LIGHT_THEME        : Theme for browsers preferring a light color scheme (Default: always RBIN_HIGHLIGHT_THEME)
+RBIN_DISABLE_INDEX      : Set to 1 to answer GET / with 404 instead of this help (Default: off)
+RBIN_READ_ONLY          : Set to 1 to serve existing pastes but reject new ones, edits and deletions (Default: off)
+RBIN_ROBOTS             : Whether search engines may index pastes, allow or disallow (Default: disallow)
+RBIN_CLEANUP_INTERVAL   : How often to remove expired pastes, 0 disables (Default: 1h)
+RBIN_REQUEST_TIMEOUT    : Time to answer a request or for an upload to stall, 0 disables (Default: 30s)
+RBIN_TLS_CERT           : PEM certificate chain, serve HTTPS together with RBIN_TLS_KEY (Default: HTTP)
+RBIN_TLS_KEY            : PEM private key for RBIN_TLS_CERT
+RBIN_TRUST_PROXY        : Set to 1 to honor X-Forwarded-For/-Host/-Proto from a reverse proxy (Default: off)
+RBIN_CORS_ORIGINS       : Comma-separated origins allowed to make cross-origin requests, or * (Default: none)
+RBIN_EXTRA_HEADERS      : Response headers as 'Name: value', separated by | (Default: nosniff, no referrer)
+RBIN_ADMIN_TOKEN        : Enables GET /admin/pastes?token=<token> to list stored pastes and /admin/search?q= (Default: disabled)
+RBIN_WEBHOOK_URL        : URL to POST a JSON notification to for every new paste (Default: none)
+RBIN_CACHE_ENTRIES      : Number of recently read pastes to keep in memory, 0 disables (Default: {})
+RBIN_REQUEST_LOG_LEVEL  : Log level for HTTP requests (tower_http) if RUST_LOG is not set (Default: {})
+RBIN_LOG_FORMAT         : Log format, text or json (Default: text)
+RUST_LOG                : Overrides all log levels (e.g., "info", "rbin=debug,tower_http=warn")
+
+Place these in a .env file or set them in your environment. Environment
+variables take precedence over the RBIN_CONFIG file, options on the command
+line (see rbin --help) over both.
+"#,
+        DEFAULT_HOST,
+        DEFAULT_PORT,
+        DEFAULT_DB_PATH,
+        DEFAULT_PASTE_DIR,
+        MIN_ID_LENGTH,
+        MAX_ID_LENGTH,
+        DEFAULT_ID_LENGTH,
+        format_size(DEFAULT_MAX_SIZE),
+        DEFAULT_MAX_BATCH,
+        DEFAULT_CACHE_ENTRIES,
+        DEFAULT_REQUEST_LOG_LEVEL // Added new env var to help text
+    );
+    (
+        StatusCode::OK,
+        vary,
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; charset=utf-8"),
+        )],
+        plain_text_content,
+    )
+        .into_response()
+}
+
+// --- Handler for GET /metrics ---
+pub async fn handle_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let mut body = state.metrics.render();
+    if let Some(quota) = &state.quota {
+        metrics::render_gauge(
+            &mut body,
+            "rbin_disk_quota_bytes",
+            "Disk quota for paste content in bytes.",
+            quota.limit(),
+        );
+        metrics::render_gauge(
+            &mut body,
+            "rbin_disk_used_bytes",
+            "Bytes of paste content stored or being uploaded.",
+            quota.used(),
+        );
+    }
+    if let Some(limit) = &state.paste_limit {
+        metrics::render_gauge(
+            &mut body,
+            "rbin_max_pastes",
+            "Number of pastes kept before the oldest are evicted.",
+            limit.max(),
+        );
+    }
+    (
+        StatusCode::OK,
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+        )],
+        body,
+    )
+}
+
+// --- Handler for GET /stats ---
+// An overview of the instance for humans, where /metrics is for Prometheus.
+// Takes a scan of the paste directory and every paste's metadata.
+pub async fn handle_stats(State(state): State<AppState>) -> Response {
+    let (ids, bytes) = match tokio::try_join!(state.store.list(), state.store.usage()) {
+        Ok(scan) => scan,
+        Err(e) => {
+            tracing::error!("Failed to scan pastes: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error reading pastes.").into_response();
+        }
+    };
+    let mut oldest: Option<SystemTime> = None;
+    let mut newest: Option<SystemTime> = None;
+    for id in &ids {
+        let paste_meta = state.store.read_meta(id).await.unwrap_or_else(|e| {
+            tracing::warn!("Failed to read metadata for paste {}: {}", id, e);
+            None
+        });
+        let created = creation_time(
+            &*state.store,
+            state.cipher.as_deref(),
+            id,
+            paste_meta.as_ref(),
+        )
+        .await;
+        if let Some(created) = created {
+            oldest = Some(oldest.map_or(created, |oldest| oldest.min(created)));
+            newest = Some(newest.map_or(created, |newest| newest.max(created)));
+        }
+    }
+    let format = |time: SystemTime| humantime::format_rfc3339_seconds(time).to_string();
+    let uptime = Duration::from_secs(state.started.elapsed().as_secs());
+    Json(Stats {
+        pastes: ids.len(),
+        bytes,
+        oldest: oldest.map(format),
+        newest: newest.map(format),
+        uptime: humantime::format_duration(uptime).to_string(),
+        uptime_seconds: uptime.as_secs(),
+    })
+    .into_response()
+}
+
+/// When a paste was created. Pastes from before metadata sidecars only have
+/// their file's time.
+async fn creation_time(
+    store: &dyn PasteStore,
+    cipher: Option<&Cipher>,
+    id: &str,
+    paste_meta: Option<&PasteMeta>,
+) -> Option<SystemTime> {
+    if let Some(created) = paste_meta.and_then(|m| m.created) {
+        return Some(created);
+    }
+    storage::open(store, id, cipher)
+        .await
+        .ok()
+        .and_then(|file| file.modified)
+}
+
+/// JSON response of `GET /stats`.
+#[derive(Serialize)]
+struct Stats {
+    pastes: usize,
+    /// Content on disk, i.e. compressed or encrypted
+    bytes: u64,
+    /// Creation times of the oldest and newest paste
+    oldest: Option<String>,
+    newest: Option<String>,
+    /// Human-readable, e.g. `3days 4h 12m 5s`
+    uptime: String,
+    uptime_seconds: u64,
+}
+
+// --- Rate Limiting Middleware ---
+// Applied to the routes that create pastes, a no-op when RBIN_RATE_LIMIT is unset.
+async fn rate_limit_middleware(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(limiter) = &state.rate_limiter {
+        let ip = client_ip(&state, request.headers(), addr);
+        if let Err(retry_after) = limiter.check(ip) {
+            // Round up so clients never retry too early
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            tracing::warn!("Rate limit exceeded for {}, retry after {}s", ip, secs);
+            return (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, secs.to_string())],
+                format!("Rate limit exceeded, try again in {} seconds", secs),
+            )
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// The form of error messages a client prefers, one of `ERROR_FORMATS`.
+fn error_format(headers: &HeaderMap) -> &'static str {
+    negotiate::preferred(headers, ERROR_FORMATS)
+}
+
+/// Error messages are plain text, which is what curl and scripts should get.
+/// Clients preferring HTML (browsers) get them wrapped in a page instead, those
+/// preferring JSON `{"error": "...", "code": "..."}` with the code of the
+/// `ApiError`.
+async fn error_pages(request: Request, next: Next) -> Response {
+    let format = error_format(request.headers());
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string);
+    let response = next.run(request).await;
+    let status = response.status();
+    let is_plain = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/plain"));
+    if format == "text/plain"
+        || !is_plain
+        || !(status.is_client_error() || status.is_server_error())
+    {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let message = match axum::body::to_bytes(body, 64 * 1024).await {
+        Ok(body) => String::from_utf8_lossy(&body).into_owned(),
+        Err(e) => {
+            tracing::warn!("Failed to read error response body: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let vary = HeaderValue::from_static("accept");
+    parts.headers.append(header::VARY, vary);
+    if format == "application/json" {
+        let code = parts
+            .extensions
+            .get::<error::ErrorCode>()
+            .map_or_else(|| error::status_code(status), |code| code.0);
+        let error = error::ErrorJson {
+            error: message.trim_end(),
+            code,
+            request_id: request_id.as_deref(),
+        };
+        parts.headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        let body = serde_json::to_string(&error).unwrap_or_default();
+        return Response::from_parts(parts, Body::from(body));
+    }
+    let title = format!(
+        "{} {}",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or("Error")
+    );
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(HTML_CONTENT_TYPE),
+    );
+    Response::from_parts(parts, Body::from(html::error_page(&title, &message)))
+}
+
+// --- Request IDs ---
+// Every request gets an `X-Request-Id`, unless the client or a proxy already
+// sent one, that is echoed back and logged with everything the request does.
+
+#[derive(Clone)]
+struct MakeRequestToken;
+
+impl MakeRequestId for MakeRequestToken {
+    fn make_request_id<B>(&mut self, _request: &axum::http::Request<B>) -> Option<RequestId> {
+        let id = Alphanumeric.sample_string(&mut rand::thread_rng(), REQUEST_ID_LENGTH);
+        HeaderValue::from_str(&id).ok().map(RequestId::new)
+    }
+}
+
+/// Appends the request ID to plain text and HTML error messages, so users can
+/// quote it when reporting problems. JSON errors carry it in a field of their
+/// own, see `error_pages`.
+async fn request_id_in_errors(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string)
+        .filter(|_| error_format(request.headers()) != "application/json");
+    let response = next.run(request).await;
+    let Some(request_id) = request_id else {
+        return response;
+    };
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("");
+    let is_html = content_type.starts_with("text/html");
+    if !(status.is_client_error() || status.is_server_error())
+        || !(is_html || content_type.starts_with("text/plain"))
+    {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    // Error messages are short, anything else is passed on as it was
+    let body = match axum::body::to_bytes(body, 64 * 1024).await {
+        Ok(body) => String::from_utf8_lossy(&body).into_owned(),
+        Err(e) => {
+            tracing::warn!("Failed to read error response body: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    let note = format!("Request ID: {}", request_id);
+    let body = match body.rfind("</body>").filter(|_| is_html) {
+        // Clients may choose their own IDs, don't trust them in markup
+        Some(end) => format!(
+            "{}<p>{}</p>\n{}",
+            &body[..end],
+            html::escape(&note),
+            &body[end..]
+        ),
+        // Most HTML errors are a bare message, readable as plain text as well
+        None if is_html => format!("{}\n{}", body.trim_end(), html::escape(&note)),
+        None => format!("{}\n{}", body.trim_end(), note),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
+
+// --- IP Filter Middleware ---
+// Applied outside the rate limit so refused clients don't use up tokens, a
+// no-op unless RBIN_ALLOW_IPS or RBIN_DENY_IPS is set.
+async fn ip_filter_middleware(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Some(filter) = &state.ip_filter {
+        let ip = client_ip(&state, request.headers(), addr);
+        if !filter.permits(ip) {
+            tracing::warn!("Refused new paste from {}", ip);
+            return (
+                StatusCode::FORBIDDEN,
+                "You are not allowed to create pastes".to_string(),
+            )
+                .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+// --- Handler for GET /favicon.ico ---
+// Browsers ask for it with every paste they show, answered without a lookup.
+pub async fn handle_favicon() -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "image/x-icon"),
+            (header::CACHE_CONTROL, FAVICON_CACHE_CONTROL),
+        ],
+        FAVICON,
+    )
+}
+
+// --- Handler for GET /robots.txt ---
+// Keeps crawlers away from all pastes unless RBIN_ROBOTS=allow.
+pub async fn handle_robots(State(state): State<AppState>) -> impl IntoResponse {
+    let rules = if state.allow_robots {
+        "User-agent: *\nDisallow:\n"
+    } else {
+        "User-agent: *\nDisallow: /\n"
+    };
+    ([(header::CONTENT_TYPE, PLAIN_CONTENT_TYPE)], rules)
+}
+
+/// Asks search engines that don't read robots.txt, or reach a paste through a
+/// link, not to index it either. Only with RBIN_ROBOTS=disallow.
+async fn noindex(mut response: Response) -> Response {
+    response.headers_mut().insert(
+        HeaderName::from_static("x-robots-tag"),
+        HeaderValue::from_static("noindex, nofollow"),
+    );
+    response
+}
+
+// --- Handler for GET /health ---
+// Liveness: the process is up and serving requests.
+pub async fn handle_health() -> &'static str {
+    tracing::trace!("Health check");
+    "ok"
+}
+
+// --- Handler for GET /ready ---
+// Readiness: the paste store accepts writes.
+pub async fn handle_ready(State(state): State<AppState>) -> (StatusCode, &'static str) {
+    match state.store.check_writable().await {
+        Ok(()) => {
+            tracing::trace!("Readiness check passed");
+            (StatusCode::OK, "ready")
+        }
+        Err(e) => {
+            tracing::warn!("Readiness check failed, pastes can't be written: {}", e);
+            (StatusCode::SERVICE_UNAVAILABLE, "paste store not writable")
+        }
+    }
+}
+
+// --- Handler for POST / ---
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/",
+    tag = "pastes",
+    params(
+        ("format" = Option<String>, Query, description = "`id` to answer with the bare ID instead of the URL"),
+        ("newline" = Option<bool>, Query, description = "End the plain text answer with a newline"),
+        ("shorten" = Option<bool>, Query, description = "Store a URL as a short link"),
+        ("encrypted" = Option<bool>, Query, description = "The content is encrypted by the client"),
+    ),
+    request_body(
+        description = "The raw content, or a form with its fields",
+        content(
+            (String = "text/plain"),
+            (openapi::PasteForm = "multipart/form-data"),
+            (openapi::PasteForm = "application/x-www-form-urlencoded"),
+        )
+    ),
+    responses(
+        (status = 201, description = "Created, the URL as plain text or the tokens as JSON", content(
+            (String = "text/plain"),
+            (CreatedPaste = "application/json"),
+        )),
+        (status = 400, description = "`EMPTY_BODY`, `TOO_SHORT`, `INVALID_ID` or `BAD_REQUEST`", body = error::ErrorJson),
+        (status = 403, description = "`FORBIDDEN` in read-only mode (RBIN_READ_ONLY)", body = error::ErrorJson),
+        (status = 413, description = "`TOO_LARGE`", body = error::ErrorJson),
+        (status = 422, description = "`BLOCKED` by RBIN_BLOCKLIST_FILE", body = error::ErrorJson),
+    )
+))]
+pub async fn handle_paste_submission(
+    State(state): State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    request: Request,
+) -> Result<Response, ApiError> {
+    tracing::debug!("Received paste submission request.");
+    check_writable(&state)?;
+    let headers = with_host(headers, &request);
+    let reply = PlainReply::new(&params, &headers);
+    let submission = read_submission(&state, &headers, &params, request).await?;
+    let id = reserve_generated_id(&state).await?;
+    tracing::info!("Generated ID: {}", id);
+    store_paste(&state, &headers, id, submission, reply).await
+}
+
+/// Rejects creating, changing and deleting pastes with RBIN_READ_ONLY.
+fn check_writable(state: &AppState) -> Result<(), ApiError> {
+    if state.read_only {
+        tracing::debug!("Rejecting write, the server is read-only");
+        return Err(ApiError::Other(
+            StatusCode::FORBIDDEN,
+            "The server is read-only, pastes can't be created, changed or deleted".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The plain text answer to an upload: the paste URL, or just its ID with
+/// `?format=id` or `X-Rbin-Format: id`. There's no trailing newline unless
+/// asked for with `?newline=1`, so it can be piped into `pbcopy` and the like.
+#[derive(Clone, Copy)]
+struct PlainReply {
+    id_only: bool,
+    newline: bool,
+}
+
+impl PlainReply {
+    fn new(params: &HashMap<String, String>, headers: &HeaderMap) -> Self {
+        let id_only = params
+            .get("format")
+            .map(String::as_str)
+            .or_else(|| headers.get("X-Rbin-Format").and_then(|h| h.to_str().ok()))
+            .is_some_and(|format| format.trim().eq_ignore_ascii_case("id"));
+        Self {
+            id_only,
+            newline: query_flag(params, "newline"),
+        }
+    }
+
+    fn body(self, id: &str, url: String) -> String {
+        let mut body = if self.id_only { id.to_string() } else { url };
+        if self.newline {
+            body.push('\n');
+        }
+        body
+    }
+}
+
+/// Whether a flag such as `?shorten=1` is set, a bare `?shorten` counts too.
+fn query_flag(params: &HashMap<String, String>, name: &str) -> bool {
+    params.get(name).is_some_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "" | "1" | "true" | "yes" | "on"
+        )
+    })
+}
+
+// --- Handler for PUT /:id ---
+// Like POST /, but with a caller-chosen ID that must not be taken yet.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    put,
+    path = "/{id}",
+    tag = "pastes",
+    params(
+        ("id" = String, Path, description = "The ID to create, or to edit with `X-Edit-Token`"),
+        ("X-Edit-Token" = Option<String>, Header, description = "Replaces the content of an existing paste"),
+        ("format" = Option<String>, Query, description = "`id` to answer with the bare ID instead of the URL"),
+        ("newline" = Option<bool>, Query, description = "End the plain text answer with a newline"),
+        ("shorten" = Option<bool>, Query, description = "Store a URL as a short link"),
+        ("encrypted" = Option<bool>, Query, description = "The content is encrypted by the client"),
+    ),
+    request_body(
+        description = "The raw content, or a form with its fields",
+        content(
+            (String = "text/plain"),
+            (openapi::PasteForm = "multipart/form-data"),
+            (openapi::PasteForm = "application/x-www-form-urlencoded"),
+        )
+    ),
+    responses(
+        (status = 201, description = "Created, the URL as plain text or the tokens as JSON", content(
+            (String = "text/plain"),
+            (CreatedPaste = "application/json"),
+        )),
+        (status = 400, description = "`EMPTY_BODY`, `TOO_SHORT`, `INVALID_ID` or `BAD_REQUEST`", body = error::ErrorJson),
+        (status = 403, description = "`FORBIDDEN` in read-only mode (RBIN_READ_ONLY)", body = error::ErrorJson),
+        (status = 413, description = "`TOO_LARGE`", body = error::ErrorJson),
+        (status = 422, description = "`BLOCKED` by RBIN_BLOCKLIST_FILE", body = error::ErrorJson),
+        (status = 409, description = "`ID_TAKEN`", body = error::ErrorJson),
+    )
+))]
+pub async fn handle_custom_paste(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    request: Request,
+) -> Result<Response, ApiError> {
+    tracing::debug!("Received custom paste submission for ID: {}", id);
+    check_writable(&state)?;
+    let headers = with_host(headers, &request);
+    let reply = PlainReply::new(&params, &headers);
+    if !validate_id(&state, &id) {
+        tracing::warn!("Invalid custom ID requested: {}", id);
+        return Err(ApiError::InvalidId(format!(
+            "Invalid paste ID: use {}-{} characters from {}",
+            MIN_CUSTOM_ID_LENGTH,
+            MAX_ID_LENGTH,
+            id_alphabet::describe(&state.id_chars)
+        )));
+    }
+    // With an edit token, the content of the existing paste is replaced instead
+    if let Some(token) = headers.get(EDIT_TOKEN_HEADER) {
+        let token = token.to_str().unwrap_or_default().to_string();
+        return edit_paste(&state, &headers, &id, &token, &params, request).await;
+    }
+    // So all new pastes can be routed by it, older ones may still lack it
+    if !id.starts_with(&*state.id_prefix) {
+        tracing::warn!("Custom ID without the prefix requested: {}", id);
+        return Err(ApiError::InvalidId(format!(
+            "Invalid paste ID: IDs must start with '{}'",
+            state.id_prefix
+        )));
+    }
+    let submission = read_submission(&state, &headers, &params, request).await?;
+    match state.store.reserve(&id).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            tracing::warn!("Custom ID already taken: {}", id);
+            return Err(ApiError::IdTaken(format!("Paste '{}' already exists", id)));
+        }
+        Err(e) => {
+            tracing::error!("Failed to reserve paste ID {}: {}", id, e);
+            return Err(ApiError::Internal(format!("Failed to save paste: {}", e)));
+        }
+    }
+    store_paste(&state, &headers, id, submission, reply).await
+}
+
+/// Replaces the content of paste `id` with a new upload, which is a short link
+/// or encrypted only if asked for again. Its creation time, expiry, password
+/// and tokens are kept, `expires` and `password` fields are ignored.
+/// Multi-file pastes can't be edited.
+async fn edit_paste(
+    state: &AppState,
+    headers: &HeaderMap,
+    id: &str,
+    token: &str,
+    params: &HashMap<String, String>,
+    request: Request,
+) -> Result<Response, ApiError> {
+    let reply = PlainReply::new(params, headers);
+    // Checked before the upload so a wrong token doesn't cost a whole body
+    check_token(state, id, token, TokenUse::Edit).await?;
+    let submission = read_submission(state, headers, params, request).await?;
+    let SubmittedContent::Single {
+        upload,
+        content_type,
+        binary,
+        file_name,
+    } = submission.content
+    else {
+        return Err(ApiError::BadRequest(format!(
+            "A paste can only be edited with a single '{}' field",
+            state.field_name
+        )));
+    };
+    let PendingUpload {
+        file, reservation, ..
+    } = *upload;
+    let size = file.size();
+    let sha256 = file.sha256();
+    {
+        // Keeps `record_view` from writing back stale metadata in between
+        let _guard = state.paste_locks.lock(id).await;
+        // The paste may have been deleted while the upload streamed in
+        let mut paste_meta = check_token(state, id, token, TokenUse::Edit).await?;
+        let failed = |e: std::io::Error| {
+            tracing::error!("Failed to edit paste {}: {}", id, e);
+            ApiError::Internal(format!("Failed to save paste: {}", e))
+        };
+        let (stored, freed) = file.replace(&*state.store, id).await.map_err(failed)?;
+        if let Some(quota) = &state.quota {
+            quota.release(freed);
+        }
+        reservation.commit(stored);
+        paste_meta.content_type = content_type;
+        paste_meta.binary = binary;
+        paste_meta.file_name = file_name;
+        paste_meta.redirect = submission.short_link;
+        paste_meta.encrypted = submission.encrypted;
+        paste_meta.sha256 = Some(sha256);
+        paste_meta.modified = Some(SystemTime::now());
+        state
+            .store
+            .write_meta(id, &paste_meta)
+            .await
+            .map_err(failed)?;
+        if let Some(cache) = &state.cache {
+            cache.invalidate(id);
+        }
+    }
+
+    let result_url = format!("{}/{}", base_url(state, headers), id);
+    tracing::info!("Paste edited successfully: {} ({} bytes)", result_url, size);
+    Ok((StatusCode::OK, reply.body(id, result_url)).into_response())
+}
+
+/// What a paste's secret token is checked for, see `check_token`.
+#[derive(Clone, Copy)]
+enum TokenUse {
+    Edit,
+    Append,
+}
+
+impl TokenUse {
+    fn name(self) -> &'static str {
+        match self {
+            TokenUse::Edit => "edit",
+            TokenUse::Append => "append",
+        }
+    }
+
+    fn expected(self, paste_meta: &PasteMeta) -> Option<&str> {
+        match self {
+            TokenUse::Edit => paste_meta.edit_token.as_deref(),
+            TokenUse::Append => paste_meta.append_token.as_deref(),
+        }
+    }
+}
+
+/// The metadata of paste `id` if `token` is its edit or append token.
+async fn check_token(
+    state: &AppState,
+    id: &str,
+    token: &str,
+    token_use: TokenUse,
+) -> Result<PasteMeta, ApiError> {
+    let not_found = || {
+        tracing::warn!("Request to {} unknown paste ID: {}", token_use.name(), id);
+        ApiError::NotFound(format!("Paste '{}' not found.", id))
+    };
+    if !state.store.exists(id).await.unwrap_or(false) {
+        return Err(not_found());
+    }
+    let paste_meta = match state.store.read_meta(id).await {
+        Ok(paste_meta) => paste_meta.unwrap_or_default(),
+        Err(e) => {
+            tracing::error!("Error reading metadata for paste {}: {}", id, e);
+            return Err(ApiError::Internal("Error updating paste.".to_string()));
+        }
+    };
+    if paste_meta.is_expired() {
+        return Err(not_found());
+    }
+    match token_use.expected(&paste_meta) {
+        Some(expected) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => {}
+        _ => {
+            tracing::warn!(
+                "Rejected {} for paste ID {}: bad or missing token",
+                token_use.name(),
+                id
+            );
+            return Err(ApiError::Other(
+                StatusCode::FORBIDDEN,
+                format!("Invalid or missing {} token.", token_use.name()),
+            ));
+        }
+    }
+    if !paste_meta.files.is_empty() {
+        let message = match token_use {
+            TokenUse::Edit => "Multi-file pastes can't be edited.",
+            TokenUse::Append => "Multi-file pastes can't be appended to.",
+        };
+        return Err(ApiError::BadRequest(message.to_string()));
+    }
+    Ok(paste_meta)
+}
+
+// --- Handler for POST /:id/append ---
+// Adds content to the end of a paste with its append token, e.g. for a log
+// that grows over time and is followed with `GET /:id/tail`.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/{id}/append",
+    tag = "pastes",
+    params(
+        ("id" = String, Path, description = "Paste ID"),
+        ("X-Append-Token" = String, Header, description = "Append token of the paste"),
+    ),
+    request_body(
+        description = "Content to add to the end, raw or as the RBIN_FIELD_NAME field (`rbin`) of a form",
+        content(
+            (String = "text/plain"),
+            (openapi::PasteForm = "multipart/form-data"),
+        )
+    ),
+    responses(
+        (status = 200, description = "Appended, the paste URL", body = String, content_type = "text/plain"),
+        (status = 400, description = "`EMPTY_BODY`, `INVALID_ID` or `BAD_REQUEST`", body = error::ErrorJson),
+        (status = 403, description = "Wrong or missing token, or read-only mode", body = error::ErrorJson),
+        (status = 404, description = "`NOT_FOUND`", body = error::ErrorJson),
+        (status = 413, description = "`TOO_LARGE`", body = error::ErrorJson),
+        (status = 422, description = "`BLOCKED` by RBIN_BLOCKLIST_FILE", body = error::ErrorJson),
+    )
+))]
+pub async fn append_paste(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    request: Request,
+) -> Result<Response, ApiError> {
+    tracing::debug!("Received append request for paste ID: {}", id);
+    check_writable(&state)?;
+    let headers = with_host(headers, &request);
+    if !validate_id(&state, &id) {
+        tracing::warn!("Invalid ID format received: {}", id);
+        return Err(ApiError::InvalidId("Invalid paste ID format.".to_string()));
+    }
+    let token = headers
+        .get(APPEND_TOKEN_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    // Checked before the upload so a wrong token doesn't cost a whole body
+    check_token(&state, &id, &token, TokenUse::Append).await?;
+    let data = read_append_body(&state, &headers, request).await?;
+    if data.is_empty() {
+        return Err(ApiError::BadRequest(
+            "Nothing to append, the content is empty".to_string(),
+        ));
+    }
+    check_blocklist(&state, &data)?;
+
+    // One append at a time, and `record_view` mustn't write back stale metadata
+    let _guard = state.paste_locks.lock(&id).await;
+    // The paste may have been deleted or expired while the body streamed in
+    let mut paste_meta = check_token(&state, &id, &token, TokenUse::Append).await?;
+    if paste_meta.redirect {
+        return Err(ApiError::BadRequest(
+            "Short links can't be appended to.".to_string(),
+        ));
+    }
+    if !paste_meta.binary && std::str::from_utf8(&data).is_err() {
+        return Err(ApiError::BadRequest(
+            "Content appended to a text paste must be valid UTF-8".to_string(),
+        ));
+    }
+    let mut reservation = quota::Reservation::new(state.quota.clone());
+    if !reservation.grow(data.len() as u64) {
+        tracing::warn!(
+            "Rejecting append to paste {}, the disk quota is exhausted",
+            id
+        );
+        return Err(ApiError::Other(
+            StatusCode::INSUFFICIENT_STORAGE,
+            "The server's storage quota is exhausted, please try again later".to_string(),
+        ));
+    }
+    let failed = |e: std::io::Error| {
+        tracing::error!("Failed to append to paste {}: {}", id, e);
+        ApiError::Internal(format!("Failed to save paste: {}", e))
+    };
+    // Held to the line limits as a whole, like an upload of all of it
+    let limit_lines = state.max_lines.is_some() || state.max_line_length.is_some();
+    if limit_lines && !paste_meta.binary && !paste_meta.encrypted {
+        let file = storage::open(&*state.store, &id, state.cipher.as_deref())
+            .await
+            .map_err(failed)?;
+        let mut lines = storage::LineCount::default();
+        lines.write(&file.read_all().await.map_err(failed)?);
+        lines.write(&data);
+        check_lines(&state, lines)?;
+    }
+    let max_len = state.max_size as u64;
+    match storage::append(&*state.store, &id, &data, max_len, state.cipher.as_deref()).await {
+        Ok(Some((stored, freed))) => {
+            if let Some(quota) = &state.quota {
+                quota.release(freed);
+            }
+            reservation.commit(stored);
+        }
+        Ok(None) => {
+            let limit = format_size(state.max_size);
+            tracing::warn!("Append would make paste {} exceed {}", id, limit);
+            return Err(ApiError::TooLarge(format!(
+                "Paste exceeds the maximum size of {}",
+                limit
+            )));
+        }
+        Err(e) => return Err(failed(e)),
+    }
+    // Hashes can't be continued from the stored digest, the whole content is hashed again
+    let content = storage::open(&*state.store, &id, state.cipher.as_deref())
+        .await
+        .map_err(failed)?
+        .read_all()
+        .await
+        .map_err(failed)?;
+    paste_meta.sha256 = tokio::task::spawn_blocking(move || storage::sha256(&content))
+        .await
+        .ok();
+    paste_meta.modified = Some(SystemTime::now());
+    state
+        .store
+        .write_meta(&id, &paste_meta)
+        .await
+        .map_err(failed)?;
+    if let Some(cache) = &state.cache {
+        cache.invalidate(&id);
+    }
+
+    let result_url = format!("{}/{}", base_url(&state, &headers), id);
+    tracing::info!("Appended {} bytes to paste {}", data.len(), id);
+    Ok((StatusCode::OK, result_url).into_response())
+}
+
+/// The content of an append request: the RBIN_FIELD_NAME field of a form like
+/// for new pastes, or else the raw request body. Held in memory so it can be
+/// added in one go, bodies beyond RBIN_MAX_SIZE are rejected.
+async fn read_append_body(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: Request,
+) -> Result<Bytes, ApiError> {
+    let mut data = Vec::new();
+    let mut add = |chunk: &[u8]| {
+        if data.len() + chunk.len() > state.max_size {
+            let limit = format_size(state.max_size);
+            return Err(ApiError::TooLarge(format!(
+                "Paste exceeds the maximum size of {}",
+                limit
+            )));
+        }
+        data.extend_from_slice(chunk);
+        Ok(())
+    };
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+    if content_type.starts_with("multipart/form-data") {
+        let mut multipart = Multipart::from_request(request, state).await.map_err(|e| {
+            tracing::error!("Error reading multipart request: {}", e);
+            (e.status(), e.body_text())
+        })?;
+        let failed = |e: axum::extract::multipart::MultipartError| {
+            tracing::error!("Error reading multipart field: {}", e);
+            read_failed(&e, format!("Error processing form data: {}", e))
+        };
+        while let Some(mut field) = multipart.next_field().await.map_err(failed)? {
+            if field.name() != Some(&*state.field_name) {
+                let _ = field.bytes().await;
+                continue;
+            }
+            while let Some(chunk) = field.chunk().await.map_err(failed)? {
+                add(&chunk)?;
+            }
+            return Ok(data.into());
+        }
+        return Err(missing_field(state));
+    }
+    let mut body = request.into_body().into_data_stream();
+    while let Some(chunk) = body.next().await {
+        add(&body_chunk(chunk)?)?;
+    }
+    // Urlencoded forms are only told apart from raw content like in `read_submission`
+    if content_type.starts_with("application/x-www-form-urlencoded")
+        && looks_like_form(state, &data)
+    {
+        if let Some(fields) = parse_form(state, &data) {
+            if let Some((_, content)) = fields
+                .into_iter()
+                .find(|(name, _)| *name == *state.field_name)
+            {
+                return Ok(content.into());
+            }
+        }
+    }
+    Ok(data.into())
+}
+
+/// Paste content and options parsed from a submission request.
+struct Submission {
+    content: SubmittedContent,
+    ttl: Option<Duration>,
+    password: Option<String>,
+    max_views: Option<u64>,
+    /// Checked to be a URL, see `check_short_link`
+    short_link: bool,
+    /// Encrypted by the client, never to be rendered
+    encrypted: bool,
+}
+
+enum SubmittedContent {
+    Single {
+        upload: Box<PendingUpload>,
+        content_type: Option<String>,
+        binary: bool,
+        file_name: Option<String>,
+    },
+    /// A multi-file paste, from several content fields (RBIN_FIELD_NAME)
+    Files(Vec<(PendingUpload, meta::BundleFile)>),
+}
+
+/// Reads a submission from either a multipart or urlencoded form, or a raw
+/// request body. Except for urlencoded forms, the content is streamed into a
+/// temporary upload file rather than held in memory. With `?shorten=1` the
+/// content must be a URL to make a short link of, `?encrypted=1` marks it as
+/// encrypted by the client.
+async fn read_submission(
+    state: &AppState,
+    headers: &HeaderMap,
+    params: &HashMap<String, String>,
+    request: Request,
+) -> Result<Submission, ApiError> {
+    let shorten = query_flag(params, "shorten");
+    let encrypted = query_flag(params, "encrypted");
+    if shorten && encrypted {
+        return Err(ApiError::BadRequest(
+            "A short link can't be encrypted".to_string(),
+        ));
+    }
+    let mut upload: Option<PendingUpload> = None;
+    let mut ttl = state.default_ttl;
+    let mut password = None;
+    let mut max_views = None;
+    let mut content_type = None;
+    // Only used without an explicit `content_type` field
+    let mut guessed_type = None;
+    let mut file_name = None;
+    // Files before the latest content field, with their guessed type and name
+    let mut earlier_files = Vec::new();
+    // The first file of another field, taken if there's no content field
+    let mut other_file = None;
+
+    let is_multipart = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("multipart/form-data"));
+    let is_urlencoded = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/x-www-form-urlencoded"));
+
+    if is_multipart {
+        let mut multipart = Multipart::from_request(request, state).await.map_err(|e| {
+            tracing::error!("Error reading multipart request: {}", e);
+            (e.status(), e.body_text())
+        })?;
+        while let Some(mut field) = multipart.next_field().await.map_err(|e| {
+            tracing::error!("Error reading multipart field: {}", e);
+            read_failed(&e, format!("Error processing form data: {}", e))
+        })? {
+            let name = field.name().unwrap_or("").to_string();
+            if name == *state.field_name {
+                if let Some(previous) = upload.take() {
+                    earlier_files.push((previous, guessed_type.take(), file_name.take()));
+                }
+                // Types we may not serve just fall back to plain text here
+                guessed_type = field
+                    .file_name()
+                    .and_then(content_type::from_file_name)
+                    .and_then(|ct| content_type::sanitize(ct, state.allow_html).ok());
+                file_name = field.file_name().and_then(sanitize_file_name);
+                let mut paste = start_upload(state, !encrypted).await?;
+                while let Some(chunk) = field.chunk().await.map_err(|e| {
+                    tracing::error!("Failed to read '{}' field data: {}", name, e);
+                    read_failed(&e, format!("Failed to read field data: {}", e))
+                })? {
+                    write_chunk(state, &mut paste, &chunk).await?;
+                }
+                upload = Some(paste);
+            } else if name == "expires" {
+                let value = field.text().await.map_err(|e| {
+                    tracing::error!("Failed to read 'expires' field data as text: {}", e);
+                    read_failed(&e, format!("Failed to read field data: {}", e))
+                })?;
+                if let Some(parsed) = parse_expires_field(&value)? {
+                    ttl = Some(parsed);
+                }
+            } else if name == "password" {
+                let value = field.text().await.map_err(|e| {
+                    tracing::error!("Failed to read 'password' field data as text: {}", e);
+                    read_failed(&e, format!("Failed to read field data: {}", e))
+                })?;
+                // An empty password field means "no password", e.g. from a blank form input
+                password = Some(value).filter(|p| !p.is_empty());
+            } else if name == "max_views" {
+                let value = field.text().await.map_err(|e| {
+                    tracing::error!("Failed to read 'max_views' field data as text: {}", e);
+                    read_failed(&e, format!("Failed to read field data: {}", e))
+                })?;
+                max_views = parse_max_views_field(&value)?;
+            } else if name == "content_type" {
+                let value = field.text().await.map_err(|e| {
+                    tracing::error!("Failed to read 'content_type' field data as text: {}", e);
+                    read_failed(&e, format!("Failed to read field data: {}", e))
+                })?;
+                content_type = Some(parse_content_type_field(state, &value)?);
+            } else if upload.is_none() && other_file.is_none() && field.file_name().is_some() {
+                // Generic upload tools name their file field `file` or the like
+                tracing::debug!("Reading file field '{}' in case there's no other", name);
+                let guessed = field
+                    .file_name()
+                    .and_then(content_type::from_file_name)
+                    .and_then(|ct| content_type::sanitize(ct, state.allow_html).ok());
+                let name = field.file_name().and_then(sanitize_file_name);
+                let mut paste = start_upload(state, !encrypted).await?;
+                while let Some(chunk) = field.chunk().await.map_err(|e| {
+                    tracing::error!("Failed to read file field data: {}", e);
+                    read_failed(&e, format!("Failed to read field data: {}", e))
+                })? {
+                    write_chunk(state, &mut paste, &chunk).await?;
+                }
+                other_file = Some((paste, guessed, name));
+            } else {
+                let _ = field.bytes().await;
+                tracing::debug!("Ignoring field '{}'", name);
+            }
+        }
+    } else {
+        let mut body = request.into_body().into_data_stream().fuse();
+        // Start of the body read ahead of the streaming below
+        let mut head = Vec::new();
+        let mut is_form = false;
+        if is_urlencoded {
+            // curl sends `--data-binary` uploads with this type too, so only
+            // bodies that start like one of our fields are read as a form
+            let sniff_len = FORM_SNIFF_LEN.max(state.field_name.len() + 1);
+            while head.len() < sniff_len && !head.contains(&b'=') {
+                match body.next().await {
+                    Some(chunk) => head.extend_from_slice(&body_chunk(chunk)?),
+                    None => break,
+                }
+            }
+            if looks_like_form(state, &head) {
+                while let Some(chunk) = body.next().await {
+                    head.extend_from_slice(&body_chunk(chunk)?);
+                }
+                if let Some(fields) = parse_form(state, &head) {
+                    is_form = true;
+                    for (name, value) in fields {
+                        match name.as_str() {
+                            name if name == &*state.field_name => {
+                                let mut paste = start_upload(state, !encrypted).await?;
+                                write_chunk(state, &mut paste, value.as_bytes()).await?;
+                                upload = Some(paste);
+                            }
+                            "expires" => {
+                                if let Some(parsed) = parse_expires_field(&value)? {
+                                    ttl = Some(parsed);
+                                }
+                            }
+                            "password" => password = Some(value).filter(|p| !p.is_empty()),
+                            "max_views" => max_views = parse_max_views_field(&value)?,
+                            _ => content_type = Some(parse_content_type_field(state, &value)?),
+                        }
+                    }
+                }
+            }
+        }
+        if !is_form {
+            // Anything that isn't a form is taken verbatim as the paste content,
+            // e.g. `curl --data-binary @file`. A supported Content-Type is kept
+            // like a `content_type` field, anything else (like curl's default
+            // form encoding) is ignored.
+            content_type = headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|h| h.to_str().ok())
+                .and_then(|ct| content_type::sanitize(ct, state.allow_html).ok());
+            let mut paste = start_upload(state, !encrypted).await?;
+            write_chunk(state, &mut paste, &head).await?;
+            while let Some(chunk) = body.next().await {
+                write_chunk(state, &mut paste, &body_chunk(chunk)?).await?;
+            }
+            upload = Some(paste);
+        }
+    }
+
+    if !earlier_files.is_empty() {
+        if shorten {
+            return Err(ApiError::BadRequest(
+                "A short link can't have multiple files".to_string(),
+            ));
+        }
+        earlier_files.extend(upload.map(|upload| (upload, guessed_type, file_name)));
+        let mut files = bundle_files(state, earlier_files)?;
+        if encrypted {
+            for (_, file) in &mut files {
+                file.content_type = encrypted_content_type(file.binary);
+            }
+        }
+        return Ok(Submission {
+            content: SubmittedContent::Files(files),
+            ttl,
+            password,
+            max_views,
+            short_link: false,
+            encrypted,
+        });
+    }
+
+    if upload.is_none() {
+        if let Some((other, guessed, name)) = other_file {
+            (upload, guessed_type, file_name) = (Some(other), guessed, name);
+        }
+    }
+    let upload = upload.ok_or_else(|| {
+        tracing::warn!("Missing '{}' field in submission.", state.field_name);
+        missing_field(state)
+    })?;
+
+    check_length(state, &upload)?;
+    check_blocklist(state, upload.scanned.as_deref().unwrap_or_default())?;
+
+    // Only explicitly requested types are held to this, guesses from the file
+    // name give way to the content
+    if let Some(requested) = content_type.as_deref().filter(|_| !encrypted) {
+        if content_type::is_text(requested) && !upload.file.is_text() {
+            tracing::warn!("Rejected non-UTF-8 paste with content type {}", requested);
+            return Err(ApiError::BadRequest(format!(
+                    "Paste content must be valid UTF-8 for content type '{}'; leave out the content type to upload a binary file.",
+                    requested
+                )));
+        }
+    }
+
+    if shorten {
+        check_short_link(&upload)?;
+    }
+
+    let (content_type, binary) = upload_content_type(&upload, content_type.or(guessed_type));
+    let content_type = match encrypted {
+        true => encrypted_content_type(binary),
+        false => content_type,
+    };
+    Ok(Submission {
+        content: SubmittedContent::Single {
+            upload: Box::new(upload),
+            content_type,
+            binary,
+            file_name,
+        },
+        ttl,
+        password,
+        max_views,
+        short_link: shorten,
+        encrypted,
+    })
+}
+
+/// Whatever type was asked for or guessed, encrypted content is opaque: plain
+/// text (e.g. base64) or bytes.
+fn encrypted_content_type(binary: bool) -> Option<String> {
+    binary.then(|| content_type::BINARY.to_string())
+}
+
+/// Checks that the content of a short link is a single http or https URL.
+/// They are short enough to be checked from the upload's head.
+fn check_short_link(upload: &PendingUpload) -> Result<(), ApiError> {
+    let complete = upload.file.size() <= storage::HEAD_LEN as u64 && upload.file.is_text();
+    let target = std::str::from_utf8(upload.file.head())
+        .ok()
+        .filter(|_| complete)
+        .and_then(short_link_target);
+    if target.is_none() {
+        tracing::warn!("Rejected short link that isn't a single http(s) URL");
+        return Err(ApiError::BadRequest(format!(
+            "A short link must be a single http:// or https:// URL of up to {}",
+            format_size(storage::HEAD_LEN)
+        )));
+    }
+    Ok(())
+}
+
+/// Where a short link with `content` redirects to, if it is a single http or
+/// https URL. Other schemes, `javascript:` in particular, are never allowed.
+fn short_link_target(content: &str) -> Option<url::Url> {
+    let content = content.trim();
+    if content.contains(char::is_whitespace) {
+        return None;
+    }
+    let url = url::Url::parse(content).ok()?;
+    (matches!(url.scheme(), "http" | "https") && url.host().is_some()).then_some(url)
+}
+
+/// The content type to store for an upload, along with whether it is binary.
+/// Without a `requested` type binary uploads are sniffed, text is plain text.
+fn upload_content_type(
+    upload: &PendingUpload,
+    requested: Option<String>,
+) -> (Option<String>, bool) {
+    let binary = !upload.file.is_text();
+    let content_type = if binary {
+        // A text type can only be a guess from the file name here, see
+        // `read_submission`
+        requested
+            .filter(|ct| !content_type::is_text(ct))
+            .or_else(|| content_type::sniff(upload.file.head()).map(str::to_string))
+            .unwrap_or_else(|| content_type::BINARY.to_string())
+    } else {
+        // Plain text is the default anyway
+        requested.unwrap_or_else(|| "text/plain".to_string())
+    };
+    (
+        Some(content_type).filter(|ct| binary || ct != "text/plain"),
+        binary,
+    )
+}
+
+/// Names of the routes below `/:id/` that files of a multi-file paste can't use.
+const RESERVED_FILE_NAMES: &[&str] = &["raw", "download", "info", "qr", "tail", "append"];
+
+/// Turns the uploads of a multi-file paste into its files, each with a name
+/// unique within the paste that is safe to use in its URL.
+fn bundle_files(
+    state: &AppState,
+    uploads: Vec<(PendingUpload, Option<String>, Option<String>)>,
+) -> Result<Vec<(PendingUpload, meta::BundleFile)>, ApiError> {
+    let mut taken: Vec<String> = Vec::new();
+    let mut files = Vec::new();
+    for (index, (upload, guessed_type, file_name)) in uploads.into_iter().enumerate() {
+        check_length(state, &upload)
+            .and_then(|()| check_blocklist(state, upload.scanned.as_deref().unwrap_or_default()))
+            .inspect_err(|_| {
+                tracing::warn!("Rejected file {} of multi-file paste.", index + 1);
+            })?;
+        let cleaned: String = file_name
+            .unwrap_or_default()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let base = match cleaned.trim_start_matches('.') {
+            "" => format!("file{}", index + 1),
+            base => base.to_string(),
+        };
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while RESERVED_FILE_NAMES.contains(&name.as_str()) || taken.contains(&name) {
+            name = format!("{}-{}", base, suffix);
+            suffix += 1;
+        }
+        taken.push(name.clone());
+        let (content_type, binary) = upload_content_type(&upload, guessed_type);
+        let file = meta::BundleFile {
+            name,
+            size: upload.file.size(),
+            sha256: Some(upload.file.sha256()),
+            content_type,
+            binary,
+        };
+        files.push((upload, file));
+    }
+    Ok(files)
+}
+
+/// Parses the `expires` form field, `None` if it is empty.
+fn parse_expires_field(value: &str) -> Result<Option<Duration>, ApiError> {
+    // Empty means the default, e.g. "Never" on the landing page
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    meta::parse_ttl(value).map(Some).map_err(|e| {
+        tracing::warn!("Invalid 'expires' value '{}': {}", value, e);
+        ApiError::BadRequest(format!("Invalid 'expires' value '{}': {}", value.trim(), e))
+    })
+}
+
+/// Parses the `max_views` form field, `None` if it is empty.
+fn parse_max_views_field(value: &str) -> Result<Option<u64>, ApiError> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    match value.trim().parse::<u64>() {
+        Ok(views) if views > 0 => Ok(Some(views)),
+        _ => {
+            tracing::warn!("Invalid 'max_views' value '{}'", value);
+            Err(ApiError::BadRequest(format!(
+                "Invalid 'max_views' value '{}': must be a positive number",
+                value.trim()
+            )))
+        }
+    }
+}
+
+fn parse_content_type_field(state: &AppState, value: &str) -> Result<String, ApiError> {
+    content_type::sanitize(value, state.allow_html).map_err(|e| {
+        tracing::warn!("Rejected 'content_type' value '{}': {}", value, e);
+        ApiError::BadRequest(format!("Invalid 'content_type' value: {}", e))
+    })
+}
+
+fn body_chunk(chunk: Result<Bytes, axum::Error>) -> Result<Bytes, ApiError> {
+    chunk.map_err(|e| {
+        tracing::error!("Error reading request body: {}", e);
+        read_failed(&e, format!("Failed to read request body: {}", e))
+    })
+}
+
+/// The error for a request body that couldn't be read, `408 Request Timeout`
+/// if it stalled for RBIN_REQUEST_TIMEOUT, else `message`.
+fn read_failed(error: &(dyn std::error::Error + 'static), message: String) -> ApiError {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if error.is::<TimeoutError>() {
+            return ApiError::Other(
+                StatusCode::REQUEST_TIMEOUT,
+                "Request body timed out, nothing was received for too long".to_string(),
+            );
+        }
+        source = error.source();
+    }
+    ApiError::BadRequest(message)
+}
+
+/// Option fields of a submission form, also accepted urlencoded, next to the
+/// content field named by RBIN_FIELD_NAME.
+pub(crate) const FORM_FIELDS: &[&str] = &["expires", "password", "max_views", "content_type"];
+// Enough of the body to see the first `name=` of a form
+const FORM_SNIFF_LEN: usize = "content_type=".len();
+
+fn is_form_field(state: &AppState, name: &str) -> bool {
+    name == &*state.field_name || FORM_FIELDS.contains(&name)
+}
+
+fn looks_like_form(state: &AppState, head: &[u8]) -> bool {
+    let fields = FORM_FIELDS.iter().copied();
+    fields.chain([&*state.field_name]).any(|field| {
+        head.strip_prefix(field.as_bytes())
+            .is_some_and(|rest| rest.starts_with(b"="))
+    })
+}
+
+/// The fields of an urlencoded form, or `None` if `data` has anything but
+/// form fields and so is more likely raw paste content.
+fn parse_form(state: &AppState, data: &[u8]) -> Option<Vec<(String, String)>> {
+    form_urlencoded::parse(data)
+        .map(|(name, value)| {
+            is_form_field(state, &name).then(|| (name.into_owned(), value.into_owned()))
+        })
+        .collect()
+}
+
+fn missing_field(state: &AppState) -> ApiError {
+    ApiError::BadRequest(format!("Missing '{}' form field", state.field_name))
+}
+
+/// An upload in progress and the disk quota it has claimed so far.
+struct PendingUpload {
+    file: storage::Upload,
+    reservation: quota::Reservation,
+    /// Held to RBIN_MAX_LINES and RBIN_MAX_LINE_LENGTH
+    limit_lines: bool,
+    /// The start of the content, collected for RBIN_BLOCKLIST_FILE
+    scanned: Option<Vec<u8>>,
+}
+
+/// With `limit_lines`, the upload is rejected once it is text exceeding
+/// RBIN_MAX_LINES or RBIN_MAX_LINE_LENGTH, and checked against the blocklist.
+async fn start_upload(state: &AppState, limit_lines: bool) -> Result<PendingUpload, ApiError> {
+    let file = storage::Upload::create(&*state.store, state.compress, state.cipher.as_deref())
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to create upload file: {}", e);
+            ApiError::Internal(format!("Failed to save paste: {}", e))
+        })?;
+    Ok(PendingUpload {
+        file,
+        reservation: quota::Reservation::new(state.quota.clone()),
+        limit_lines,
+        scanned: (limit_lines && state.blocklist.is_some()).then(Vec::new),
+    })
+}
+
+/// Streams a chunk of paste content to disk, enforcing `RBIN_MAX_SIZE` on the
+/// content itself rather than relying on the request body limit alone, the
+/// disk quota (RBIN_MAX_TOTAL_BYTES) and the line limits.
+async fn write_chunk(
+    state: &AppState,
+    upload: &mut PendingUpload,
+    chunk: &[u8],
+) -> Result<(), ApiError> {
+    if upload.file.size() + chunk.len() as u64 > state.max_size as u64 {
+        let limit = format_size(state.max_size);
+        tracing::warn!("Paste exceeds the maximum size of {}", limit);
+        return Err(ApiError::TooLarge(format!(
+            "Paste exceeds the maximum size of {}",
+            limit
+        )));
+    }
+    // Uncompressed, `store_paste` settles the claim with the size on disk
+    if !upload.reservation.grow(chunk.len() as u64) {
+        tracing::warn!("Rejecting paste, the disk quota is exhausted");
+        return Err(ApiError::Other(
+            StatusCode::INSUFFICIENT_STORAGE,
+            "The server's storage quota is exhausted, please try again later".to_string(),
+        ));
+    }
+    upload.file.write(chunk).await.map_err(|e| {
+        tracing::error!("Failed to write upload file: {}", e);
+        ApiError::Internal(format!("Failed to save paste: {}", e))
+    })?;
+    if let Some(scanned) = &mut upload.scanned {
+        let missing = blocklist::SCAN_LEN.saturating_sub(scanned.len());
+        scanned.extend_from_slice(&chunk[..missing.min(chunk.len())]);
+    }
+    // Binary content is never highlighted or shown as text
+    if upload.limit_lines && upload.file.is_text() {
+        check_lines(state, upload.file.lines())?;
+    }
+    Ok(())
+}
+
+/// Rejects empty content unless RBIN_ALLOW_EMPTY is set and, with
+/// RBIN_MIN_LENGTH, text that is too short once leading and trailing
+/// whitespace is trimmed.
+fn check_length(state: &AppState, upload: &PendingUpload) -> Result<(), ApiError> {
+    if upload.file.size() == 0 {
+        if state.allow_empty {
+            return Ok(());
+        }
+        tracing::warn!("Received empty paste content.");
+        return Err(ApiError::EmptyBody);
+    }
+    let Some(min) = state
+        .min_length
+        .filter(|_| upload.limit_lines && upload.file.is_text())
+    else {
+        return Ok(());
+    };
+    match upload.file.trimmed_len() {
+        0 => {
+            tracing::warn!("Received whitespace-only paste content.");
+            Err(ApiError::TooShort(
+                "Paste content cannot be only whitespace".to_string(),
+            ))
+        }
+        len if len < min => {
+            tracing::warn!("Received paste of {} characters, below {}", len, min);
+            Err(ApiError::TooShort(format!(
+                "Paste content must be at least {} characters long, not counting leading and trailing whitespace",
+                min
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Rejects content matching a pattern of RBIN_BLOCKLIST_FILE.
+fn check_blocklist(state: &AppState, content: &[u8]) -> Result<(), ApiError> {
+    let Some(pattern) = state
+        .blocklist
+        .as_ref()
+        .and_then(|blocklist| blocklist.find(content))
+    else {
+        return Ok(());
+    };
+    tracing::warn!("Rejected paste matching blocklist pattern '{}'", pattern);
+    Err(ApiError::Blocked(
+        "Paste content is not allowed on this server".to_string(),
+    ))
+}
+
+fn check_lines(state: &AppState, lines: storage::LineCount) -> Result<(), ApiError> {
+    if let Some(limit) = state.max_lines.filter(|&limit| lines.lines() > limit) {
+        tracing::warn!("Paste exceeds the maximum of {} lines", limit);
+        return Err(ApiError::BadRequest(format!(
+            "Paste exceeds the maximum of {} lines",
+            limit
+        )));
+    }
+    if let Some(limit) = state
+        .max_line_length
+        .filter(|&limit| lines.longest_line() > limit)
+    {
+        tracing::warn!("Paste has a line longer than {} characters", limit);
+        return Err(ApiError::BadRequest(format!(
+            "Paste has a line longer than the maximum of {} characters",
+            limit
+        )));
+    }
+    Ok(())
+}
+
+/// Reserves a fresh random ID so concurrent submissions can never claim the
+/// same one, regenerating the ID on collision.
+async fn reserve_generated_id(state: &AppState) -> Result<String, ApiError> {
+    for attempt in 1..=MAX_ID_ATTEMPTS {
+        let id = format!(
+            "{}{}",
+            state.id_prefix,
+            state
+                .id_scheme
+                .generate(&state.id_alphabet, state.id_length)
+        );
+        match state.store.reserve(&id).await {
+            Ok(()) => return Ok(id),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                tracing::warn!(
+                    "ID collision on {} (attempt {}/{}), regenerating",
+                    id,
+                    attempt,
+                    MAX_ID_ATTEMPTS
+                );
+            }
+            Err(e) => {
+                tracing::error!("Failed to reserve paste ID {}: {}", id, e);
+                return Err(ApiError::Internal(format!("Failed to save paste: {}", e)));
+            }
+        }
+    }
+    tracing::error!(
+        "Could not find a free paste ID after {} attempts",
+        MAX_ID_ATTEMPTS
+    );
+    Err(ApiError::Internal(
+        "Failed to generate a unique paste ID, please try again".to_string(),
+    ))
+}
+
+/// Writes the content and metadata for a reserved paste and builds the
+/// `201 Created` response pointing at it, as JSON, a page or the `reply`.
+async fn store_paste(
+    state: &AppState,
+    headers: &HeaderMap,
+    id: String,
+    submission: Submission,
+    reply: PlainReply,
+) -> Result<Response, ApiError> {
+    let delete_token = generate_token();
+    let edit_token = generate_token();
+    let append_token = generate_token();
+    let password_hash = match submission.password {
+        Some(password) => {
+            match tokio::task::spawn_blocking(move || meta::hash_password(&password)).await {
+                Ok(Ok(hash)) => Some(hash),
+                Ok(Err(e)) => return Err(failed_to_hash(state, &id, e).await),
+                Err(e) => return Err(failed_to_hash(state, &id, e.to_string()).await),
+            }
+        }
+        None => None,
+    };
+    let (uploads, content_type, binary, file_name, sha256, files) = match submission.content {
+        SubmittedContent::Single {
+            upload,
+            content_type,
+            binary,
+            file_name,
+        } => {
+            let sha256 = upload.file.sha256();
+            let uploads = vec![*upload];
+            (
+                uploads,
+                content_type,
+                binary,
+                file_name,
+                Some(sha256),
+                Vec::new(),
+            )
+        }
+        SubmittedContent::Files(files) => {
+            let (uploads, files) = files.into_iter().unzip();
+            (uploads, None, false, None, None, files)
+        }
+    };
+    let bundle = !files.is_empty();
+    let paste_meta = PasteMeta {
+        expires: submission.ttl.map(|ttl| SystemTime::now() + ttl),
+        delete_token: Some(delete_token.clone()),
+        edit_token: Some(edit_token.clone()),
+        append_token: Some(append_token.clone()),
+        password_hash,
+        content_type,
+        binary,
+        redirect: submission.short_link,
+        encrypted: submission.encrypted,
+        sha256,
+        file_name,
+        created: Some(SystemTime::now()),
+        modified: None,
+        views: 0,
+        views_left: submission.max_views,
+        files,
+    };
+    make_room(state, &id, &paste_meta).await?;
+    if let Err(e) = state.store.write_meta(&id, &paste_meta).await {
+        tracing::error!("Failed to write metadata for paste {}: {}", id, e);
+        release_id(state, &id).await;
+        return Err(ApiError::Internal(format!("Failed to save paste: {}", e)));
+    }
+    let size = uploads.iter().map(|upload| upload.file.size()).sum();
+    if let Err(e) = commit_uploads(state, &id, uploads, bundle).await {
+        release_id(state, &id).await;
+        tracing::error!("Failed to write paste file for {}: {}", id, e);
+        return Err(ApiError::Internal(format!("Failed to save paste: {}", e)));
+    }
+
+    // Multi-file pastes link to their file list
+    let slash = if bundle { "/" } else { "" };
+    let result_url = format!("{}/{}{}", base_url(state, headers), id, slash);
+
+    tracing::info!("Paste created successfully: {}", result_url);
+    state.metrics.paste_created(size);
+    if let Some(webhook) = &state.webhook {
+        let created = paste_meta.created.unwrap_or_else(SystemTime::now);
+        webhook.paste_created(&id, &result_url, size, created);
+    }
+    let (location, token_header, edit_header, append_header) = HeaderValue::from_str(&result_url)
+        .and_then(|location| {
+            Ok((
+                location,
+                HeaderValue::from_str(&delete_token)?,
+                HeaderValue::from_str(&edit_token)?,
+                HeaderValue::from_str(&append_token)?,
+            ))
+        })
+        .map_err(|e| {
+            tracing::error!("Failed to build response headers: {}", e);
+            ApiError::Internal("Failed to build response headers".to_string())
+        })?;
+    let headers_out = [
+        (header::LOCATION, location),
+        (HeaderName::from_static("x-delete-token"), token_header),
+        (HeaderName::from_static("x-edit-token"), edit_header),
+        (HeaderName::from_static("x-append-token"), append_header),
+    ];
+    if reply.id_only {
+        return Ok((
+            StatusCode::CREATED,
+            headers_out,
+            reply.body(&id, result_url),
+        )
+            .into_response());
+    }
+    // API clients can ask for JSON and browsers (the form on `GET /`) get a
+    // page, curl keeps getting the bare URL
+    match negotiate::preferred(headers, &["text/plain", "application/json", "text/html"]) {
+        "application/json" => {
+            let created = CreatedPaste {
+                id,
+                url: result_url,
+                delete_token,
+                edit_token,
+                append_token,
+            };
+            Ok((StatusCode::CREATED, headers_out, Json(created)).into_response())
+        }
+        "text/html" => {
+            let page = html::created_page(&result_url, &delete_token, &edit_token, &append_token);
+            Ok((StatusCode::CREATED, headers_out, Html(page)).into_response())
+        }
+        _ => Ok((
+            StatusCode::CREATED,
+            headers_out,
+            reply.body(&id, result_url),
+        )
+            .into_response()),
+    }
+}
+
+/// Puts finished uploads into the store as paste `id`, a single file or the
+/// files of a multi-file paste, and settles their disk quota claims.
+async fn commit_uploads(
+    state: &AppState,
+    id: &str,
+    uploads: Vec<PendingUpload>,
+    bundle: bool,
+) -> std::io::Result<()> {
+    if !bundle {
+        for PendingUpload {
+            file, reservation, ..
+        } in uploads
+        {
+            let stored = file.commit(&*state.store, id).await?;
+            reservation.commit(stored);
+        }
+        return Ok(());
+    }
+    let mut files = Vec::new();
+    let mut committed = Vec::new();
+    for PendingUpload {
+        file, reservation, ..
+    } in uploads
+    {
+        let file = file.finish().await?;
+        committed.push((reservation, file.len));
+        files.push(file);
+    }
+    state.store.put_bundle(id, files).await?;
+    for (reservation, stored) in committed {
+        reservation.commit(stored);
+    }
+    Ok(())
+}
+
+/// JSON response for a new paste.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+struct CreatedPaste {
+    id: String,
+    url: String,
+    delete_token: String,
+    edit_token: String,
+    append_token: String,
+}
+
+/// JSON view of a paste for `GET /:id` with `Accept: application/json`.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+struct PasteJson<'a> {
+    id: &'a str,
+    /// Missing for binary and encrypted pastes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<&'a str>,
+    binary: bool,
+    size: u64,
+    /// RFC3339
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+    /// Only set for edited pastes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires: Option<String>,
+}
+
+/// Evicts the oldest pastes over RBIN_MAX_PASTES to make room for the new
+/// paste `id`, which takes their place.
+async fn make_room(state: &AppState, id: &str, paste_meta: &PasteMeta) -> Result<(), ApiError> {
+    let Some(limit) = &state.paste_limit else {
+        return Ok(());
+    };
+    let Some(evicted) = limit.admit(id, paste_meta.views_left.is_none()) else {
+        tracing::warn!(
+            "Rejecting paste, all {} stored pastes have views left and can't be evicted",
+            limit.max()
+        );
+        state.store.delete(id).await;
+        return Err(ApiError::Other(
+            StatusCode::INSUFFICIENT_STORAGE,
+            "The server holds as many pastes as it may, please try again later".to_string(),
+        ));
+    };
+    for old_id in evicted {
+        tracing::info!("Evicting paste {} to make room for {}", old_id, id);
+        remove_paste(state, &old_id).await;
+    }
+    Ok(())
+}
+
+/// Releases the reserved ID of a paste that failed to be saved.
+async fn release_id(state: &AppState, id: &str) {
+    state.store.delete(id).await;
+    if let Some(limit) = &state.paste_limit {
+        limit.forget(id);
+    }
+}
+
+async fn failed_to_hash(state: &AppState, id: &str, error: String) -> ApiError {
+    tracing::error!("Failed to hash password for paste {}: {}", id, error);
+    state.store.delete(id).await;
+    ApiError::Internal("Failed to save paste password".to_string())
+}
+
+// --- Handler for GET /:id and GET /:id.<ext> ---
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/{id}",
+    tag = "pastes",
+    params(
+        ("id" = String, Path, description = "Paste ID, with an extension such as `.rs` for a highlighted page"),
+        ("charset" = Option<String>, Query, description = "Transcode a text paste, e.g. `latin1`"),
+        ("theme" = Option<String>, Query, description = "Color theme of the HTML view: `dark`, `light` or a theme name"),
+        ("X-Paste-Password" = Option<String>, Header, description = "Password of a protected paste"),
+    ),
+    responses(
+        (status = 200, description = "The paste, negotiated by `Accept`", content(
+            (String = "text/plain"),
+            (String = "text/html"),
+            (PasteJson = "application/json"),
+        )),
+        (status = 400, description = "`INVALID_ID`", body = error::ErrorJson),
+        (status = 403, description = "Wrong or missing password", body = error::ErrorJson),
+        (status = 404, description = "`NOT_FOUND`", body = error::ErrorJson),
+    )
+))]
+pub async fn retrieve_paste(
+    State(state): State<AppState>,
+    Path(requested): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    request: Parts,
+) -> Response {
+    tracing::debug!("Received request to retrieve paste: {}", requested);
+    let headers = &request.headers;
+    let password = headers.get(PASSWORD_HEADER).and_then(|h| h.to_str().ok());
+    // Browsers get a readable page, curl and friends keep the raw text
+    let view = match negotiate::preferred(headers, &["text/plain", "text/html", "application/json"])
+    {
+        "text/html" => View::Html,
+        "application/json" => View::Json,
+        _ => View::Plain,
+    };
+    let charset = match parse_charset(&params) {
+        Ok(charset) => charset,
+        Err(error) => return error.into_response(),
+    };
+    let theme = match parse_theme(&state, &params) {
+        Ok(theme) => theme,
+        Err(error) => return error.into_response(),
+    };
+    serve_paste(&state, &request, requested, password, view, charset, theme).await
+}
+
+// --- Handler for POST /:id and POST /:id.<ext> ---
+// Target of the password form shown to browsers for protected pastes.
+pub async fn unlock_paste(
+    State(state): State<AppState>,
+    Path(requested): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    request: Parts,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    tracing::debug!("Received unlock request for paste: {}", requested);
+    let password = form.get("password").map(String::as_str);
+    let theme = match parse_theme(&state, &params) {
+        Ok(theme) => theme,
+        Err(error) => return error.into_response(),
+    };
+    serve_paste(
+        &state,
+        &request,
+        requested,
+        password,
+        View::Html,
+        None,
+        theme,
+    )
+    .await
+}
+
+/// Representation of a paste negotiated for `GET /:id`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum View {
+    Plain,
+    Html,
+    Json,
+}
+
+/// Loads and renders a paste for the HTML, plain text or JSON views. A
+/// `charset` only applies to text pastes served as they are, a `theme` to the
+/// HTML views.
+async fn serve_paste(
+    state: &AppState,
+    request: &Parts,
+    requested: String,
+    password: Option<&str>,
+    view: View,
+    charset: Option<&'static Encoding>,
+    theme: Option<&str>,
+) -> Response {
+    // An extension (`/abc123.rs`) asks for a syntax highlighted HTML view
+    let (id, ext) = match requested.split_once('.') {
+        Some((id, ext)) => (id.to_string(), Some(ext.to_string())),
+        None => (requested, None),
+    };
+    let json = view == View::Json && ext.is_none();
+    let stream = ext.is_none() && !json;
+    let wants_html = view == View::Html || ext.is_some();
+    let headers = &request.headers;
+    let paste = match load_paste(state, &id, password, wants_html, stream).await {
+        Ok(paste) => paste,
+        Err(response) => {
+            let status = response.status();
+            // Relative, so it works behind a proxy on a subpath too
+            if status == StatusCode::NOT_FOUND && state.store.is_bundle(&id).await {
+                return Redirect::to(&format!("{}/", id)).into_response();
+            }
+            return response;
+        }
+    };
+    // Short links redirect from every view, `/:id/raw` shows their target
+    if let Some(response) = short_link_redirect(&paste) {
+        if let Err(response) = count_view(state, &id, &request.method).await {
+            return response;
+        }
+        return response;
+    }
+    // Encrypted pastes are only served as they are, the key stays with the client
+    let (json, ext) = match paste.meta.encrypted {
+        true => (false, None),
+        false => (json, ext),
+    };
+    let paste = match strip_bom(&id, paste).await {
+        Ok(paste) => paste,
+        Err(response) => return response,
+    };
+    // Pastes with a stored content type are served as such, not wrapped in a
+    // page, and binary pastes can't be shown as text at all
+    let as_is = ext.is_none() && (!wants_html || paste.meta.content_type.is_some());
+    let raw = !json && (as_is || paste.meta.binary || paste.meta.encrypted);
+    let charset = charset.filter(|_| raw && is_text_paste(&paste.meta));
+    let variant = match &ext {
+        _ if json => "json".to_string(),
+        _ if raw => charset.map_or("raw".to_string(), |c| format!("raw.{}", c.name())),
+        Some(ext) => format!("hl.{}", ext),
+        None => "html".to_string(),
+    };
+    let validators = CacheValidators::new(&id, &paste, &variant);
+    if let Some(response) = validators.not_modified(headers) {
+        return response;
+    }
+    if let Err(response) = count_view(state, &id, &request.method).await {
+        return response;
+    }
+    let negotiated = ext.is_none();
+    let response = if json {
+        json_response(&id, paste)
+    } else if let Some(charset) = charset {
+        let content_type = paste.meta.content_type.as_deref().unwrap_or("text/plain");
+        transcoded_response(headers, &id, content_type, paste.body, charset).await
+    } else {
+        let css = match theme {
+            Some(theme) => highlight::page_css(theme, None),
+            None => highlight::page_css(&state.highlight_theme, state.light_theme.as_deref()),
+        };
+        render_paste(headers, &id, ext, paste, raw, css).await
+    };
+    let mut response = validators.apply(response);
+    if negotiated {
+        // The same URL serves plain text or HTML depending on Accept, caches must know
+        response
+            .headers_mut()
+            .append(header::VARY, HeaderValue::from_static("accept"));
+    }
+    response
+}
+
+/// `302 Found` to the target of a short link paste, `None` for other pastes.
+fn short_link_redirect(paste: &LoadedPaste) -> Option<Response> {
+    if !paste.meta.redirect {
+        return None;
+    }
+    let PasteBody::Loaded(content) = &paste.body else {
+        // Never the case for short links, which are small
+        return None;
+    };
+    // Checked on upload already, but the sidecar might have been edited since
+    let target = short_link_target(std::str::from_utf8(content).ok()?)?;
+    let location = HeaderValue::from_str(target.as_str()).ok()?;
+    Some((StatusCode::FOUND, [(header::LOCATION, location)]).into_response())
+}
+
+fn json_response(id: &str, paste: LoadedPaste) -> Response {
+    match paste_json(id, &paste) {
+        Some(json) => Json(json).into_response(),
+        // JSON views are never streamed, see `serve_paste`
+        None => (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.").into_response(),
+    }
+}
+
+/// The JSON view of `paste`, `None` if its body is left on disk.
+fn paste_json<'a>(id: &'a str, paste: &'a LoadedPaste) -> Option<PasteJson<'a>> {
+    let PasteBody::Loaded(content) = &paste.body else {
+        return None;
+    };
+    let rfc3339 = |time: SystemTime| humantime::format_rfc3339_seconds(time).to_string();
+    let text = !paste.meta.binary && !paste.meta.encrypted;
+    Some(PasteJson {
+        id,
+        size: content.len() as u64,
+        content: text.then(|| String::from_utf8_lossy(content).into_owned()),
+        content_type: paste.meta.content_type.as_deref(),
+        binary: paste.meta.binary,
+        created: paste.created.map(rfc3339),
+        modified: paste.meta.modified.map(rfc3339),
+        expires: paste.meta.expires.map(rfc3339),
+    })
+}
+
+// --- Handler for POST /batch ---
+// Several pastes in one request, e.g. for a dashboard. Takes a JSON array of
+// IDs and answers with an object from each ID to its JSON view as in
+// `GET /:id`, or to the error it failed with, like `{"error": "...", "code":
+// "NOT_FOUND"}`. Protected pastes fail, there's no way to pass passwords.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    post,
+    path = "/batch",
+    tag = "pastes",
+    request_body(
+        description = "Paste IDs, at most RBIN_MAX_BATCH",
+        content((Vec<String> = "application/json")),
+    ),
+    responses(
+        (status = 200, description = "The pastes or their errors by ID", body = HashMap<String, PasteJson>),
+        (status = 400, description = "`BAD_REQUEST`, not a JSON array of IDs", body = error::ErrorJson),
+        (status = 413, description = "`TOO_LARGE`, more IDs than RBIN_MAX_BATCH", body = error::ErrorJson),
+    )
+))]
+pub async fn batch_pastes(
+    State(state): State<AppState>,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let ids: Vec<String> = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::BadRequest(format!("Expected a JSON array of paste IDs: {}", e)))?;
+    tracing::debug!("Received batch request for {} pastes", ids.len());
+    if ids.len() as u64 > state.max_batch {
+        return Err(ApiError::TooLarge(format!(
+            "At most {} pastes can be requested at once",
+            state.max_batch
+        )));
+    }
+    let mut pastes = BTreeMap::new();
+    for id in ids {
+        // Read each paste once, every read counts towards `max_views`
+        if pastes.contains_key(&id) {
+            continue;
+        }
+        let paste = match load_paste(&state, &id, None, false, false).await {
+            Ok(paste) => match count_view(&state, &id, &Method::POST).await {
+                Ok(()) => strip_bom(&id, paste).await,
+                Err(response) => Err(response),
+            },
+            Err(response) => Err(response),
+        };
+        let entry = match paste {
+            Ok(paste) => serde_json::to_value(paste_json(&id, &paste)),
+            Err(response) => serde_json::to_value(batch_error(response).await),
+        };
+        match entry {
+            Ok(entry) => pastes.insert(id, entry),
+            Err(e) => return Err(ApiError::Internal(format!("Failed to encode paste: {}", e))),
+        };
+    }
+    Ok(Json(pastes).into_response())
+}
+
+/// The error of one paste of `POST /batch`, from the response it failed with.
+async fn batch_error(response: Response) -> serde_json::Value {
+    let status = response.status();
+    let code = response
+        .extensions()
+        .get::<error::ErrorCode>()
+        .map_or_else(|| error::status_code(status), |code| code.0);
+    let message = match axum::body::to_bytes(response.into_body(), 64 * 1024).await {
+        Ok(body) => String::from_utf8_lossy(&body).trim_end().to_string(),
+        Err(_) => String::new(),
+    };
+    serde_json::json!({ "error": message, "code": code })
+}
+
+/// Builds the response for `serve_paste`: the paste itself if `raw`, else a
+/// highlighted or plain HTML view. Without `ext` the language is guessed.
+async fn render_paste(
+    headers: &HeaderMap,
+    id: &str,
+    ext: Option<String>,
+    paste: LoadedPaste,
+    raw: bool,
+    css: String,
+) -> Response {
+    if raw {
+        let content_type = paste_content_type(&paste.meta);
+        let response = raw_response(headers, content_type, paste.body).await;
+        return with_checksum(response, &paste.meta);
+    }
+    let content = match paste.body {
+        PasteBody::Loaded(content) => content,
+        PasteBody::File(file) => match file.read_all().await {
+            Ok(content) => content,
+            Err(e) => {
+                tracing::error!("Error reading paste {}: {}", id, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.")
+                    .into_response();
+            }
+        },
+    };
+
+    // Without an extension, highlight anyway if the language is recognizable
+    let ext = ext.or_else(|| highlight::detect_language(&content));
+    if let Some(ext) = ext {
+        let render_id = id.to_string();
+        let created = paste.created;
+        return match tokio::task::spawn_blocking(move || {
+            highlight::render_html(
+                &render_id,
+                &ext,
+                &String::from_utf8_lossy(&content),
+                created,
+                &css,
+            )
+        })
+        .await
+        {
+            Ok(html) => {
+                let content_type = HeaderValue::from_static(HTML_CONTENT_TYPE);
+                paste_response(headers, content_type, html.into()).await
+            }
+            Err(e) => {
+                tracing::error!("Highlighting task for paste {} failed: {}", id, e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Error rendering paste.").into_response()
+            }
+        };
+    }
+    let page = html::paste_page(
+        id,
+        &html::escape(&String::from_utf8_lossy(&content)),
+        &css,
+        paste.created,
+    );
+    paste_response(
+        headers,
+        HeaderValue::from_static(HTML_CONTENT_TYPE),
+        page.into(),
+    )
+    .await
+}
+
+// --- Handler for GET /:id/raw ---
+// Always plain text (or the stored type of binary pastes), regardless of Accept
+// headers, for downloads and scripts.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/{id}/raw",
+    tag = "pastes",
+    params(
+        ("id" = String, Path, description = "Paste ID"),
+        ("charset" = Option<String>, Query, description = "Transcode a text paste, e.g. `latin1`"),
+        ("X-Paste-Password" = Option<String>, Header, description = "Password of a protected paste"),
+    ),
+    responses(
+        (status = 200, description = "The content as stored", body = String, content_type = "text/plain"),
+        (status = 400, description = "`INVALID_ID`", body = error::ErrorJson),
+        (status = 403, description = "Wrong or missing password", body = error::ErrorJson),
+        (status = 404, description = "`NOT_FOUND`", body = error::ErrorJson),
+    )
+))]
+pub async fn retrieve_raw_paste(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    tracing::debug!("Received request to retrieve raw paste ID: {}", id);
+    let password = headers.get(PASSWORD_HEADER).and_then(|h| h.to_str().ok());
+    let charset = match parse_charset(&params) {
+        Ok(charset) => charset,
+        Err(error) => return error.into_response(),
+    };
+    let paste = match load_paste(&state, &id, password, false, true).await {
+        Ok(paste) => paste,
+        Err(response) => return response,
+    };
+    let paste = match strip_bom(&id, paste).await {
+        Ok(paste) => paste,
+        Err(response) => return response,
+    };
+    let charset = charset.filter(|_| is_text_paste(&paste.meta));
+    let variant = charset.map_or("raw".to_string(), |c| format!("raw.{}", c.name()));
+    let validators = CacheValidators::new(&id, &paste, &variant);
+    if let Some(response) = validators.not_modified(&headers) {
+        return response;
+    }
+    if let Err(response) = count_view(&state, &id, &method).await {
+        return response;
+    }
+    let response = if let Some(charset) = charset {
+        transcoded_response(&headers, &id, "text/plain", paste.body, charset).await
+    } else {
+        let content_type = if paste.meta.binary {
+            paste_content_type(&paste.meta)
+        } else {
+            HeaderValue::from_static(PLAIN_CONTENT_TYPE)
+        };
+        let response = raw_response(&headers, content_type, paste.body).await;
+        with_checksum(response, &paste.meta)
+    };
+    validators.apply(response)
+}
+
+// --- Handler for GET /:id/tail ---
+// Follows a paste like `tail -f`: sends its content, then whatever is added to
+// it in one long chunked response. Ends once the paste is removed, expires or
+// shrinks (replaced by an edit), or hasn't changed for `TAIL_IDLE_TIMEOUT`.
+pub async fn tail_paste(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    tracing::debug!("Received tail request for paste ID: {}", id);
+    let password = headers.get(PASSWORD_HEADER).and_then(|h| h.to_str().ok());
+    let paste = match load_paste(&state, &id, password, false, false).await {
+        Ok(paste) => paste,
+        Err(response) => return response,
+    };
+    if let Err(response) = count_view(&state, &id, &method).await {
+        return response;
+    }
+    let PasteBody::Loaded(content) = paste.body else {
+        // Never streamed, see `load_paste`
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.").into_response();
+    };
+    let content_type = if paste.meta.binary {
+        paste_content_type(&paste.meta)
+    } else {
+        HeaderValue::from_static(PLAIN_CONTENT_TYPE)
+    };
+    let follow = TailState {
+        state,
+        id,
+        version: paste.version,
+        sent: content.len(),
+    };
+    let updates = futures_util::stream::unfold(follow, |follow| follow.next());
+    let body = futures_util::stream::once(async move { Ok(content) }).chain(updates);
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-store")),
+            (
+                header::X_CONTENT_TYPE_OPTIONS,
+                HeaderValue::from_static("nosniff"),
+            ),
+        ],
+        axum::Extension(Uncompressed),
+        Body::from_stream(body),
+    )
+        .into_response()
+}
+
+/// Keeps the compression layer off a response, like the endless body of
+/// `GET /:id/tail`, whose updates a compressor would hold back until enough
+/// output piles up.
+#[derive(Clone, Copy)]
+struct Uncompressed;
+
+/// A paste followed by `GET /:id/tail`.
+struct TailState {
+    state: AppState,
+    id: String,
+    /// Of the file last sent from, see `PasteFile::version`
+    version: String,
+    /// Bytes of content sent so far
+    sent: usize,
+}
+
+impl TailState {
+    /// Waits for content to be added to the paste and returns it, `None` once
+    /// the paste isn't worth following anymore.
+    async fn next(mut self) -> Option<(std::io::Result<Bytes>, Self)> {
+        let mut idle = Duration::ZERO;
+        loop {
+            tokio::select! {
+                () = tokio::time::sleep(TAIL_POLL_INTERVAL) => {}
+                () = self.state.shutdown.cancelled() => return None,
+            }
+            let file =
+                match storage::open(&*self.state.store, &self.id, self.state.cipher.as_deref())
+                    .await
+                {
+                    Ok(file) => file,
+                    Err(e) => {
+                        if e.kind() != std::io::ErrorKind::NotFound {
+                            tracing::error!("Error reading paste {} to tail: {}", self.id, e);
+                        }
+                        return None;
+                    }
+                };
+            if file.version() == self.version {
+                idle += TAIL_POLL_INTERVAL;
+                if idle >= TAIL_IDLE_TIMEOUT {
+                    tracing::debug!("Stopped tailing paste {}, it hasn't changed", self.id);
+                    return None;
+                }
+                continue;
+            }
+            idle = Duration::ZERO;
+            self.version = file.version();
+            match self.state.store.read_meta(&self.id).await {
+                Ok(Some(paste_meta)) if paste_meta.is_expired() => return None,
+                _ => {}
+            }
+            let content = match file.read_all().await {
+                Ok(content) => content,
+                Err(e) => {
+                    tracing::error!("Error reading paste {} to tail: {}", self.id, e);
+                    return None;
+                }
+            };
+            if content.len() < self.sent {
+                tracing::debug!("Stopped tailing paste {}, it was replaced", self.id);
+                return None;
+            }
+            if content.len() > self.sent {
+                let added = content.slice(self.sent..);
+                self.sent = content.len();
+                return Some((Ok(added), self));
+            }
+        }
+    }
+}
+
+// --- Handler for GET /:id/download ---
+// The raw paste as an attachment, named like the uploaded file.
+pub async fn download_paste(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    tracing::debug!("Received download request for paste ID: {}", id);
+    let password = headers.get(PASSWORD_HEADER).and_then(|h| h.to_str().ok());
+    let paste = match load_paste(&state, &id, password, false, true).await {
+        Ok(paste) => paste,
+        Err(response) => return response,
+    };
+    let validators = CacheValidators::new(&id, &paste, "download");
+    if let Some(response) = validators.not_modified(&headers) {
+        return response;
+    }
+    if let Err(response) = count_view(&state, &id, &method).await {
+        return response;
+    }
+    let file_name = paste.meta.file_name.clone().unwrap_or_else(|| {
+        let ext = if paste.meta.binary { "bin" } else { "txt" };
+        format!("{}.{}", id, ext)
+    });
+    let content_type = paste_content_type(&paste.meta);
+    let response = raw_response(&headers, content_type, paste.body).await;
+    let mut response = with_checksum(response, &paste.meta);
+    if let Ok(disposition) = HeaderValue::from_str(&content_disposition(&file_name)) {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, disposition);
+    }
+    validators.apply(response)
+}
+
+// --- Handler for GET /admin/pastes ---
+// Lists stored pastes for housekeeping, sorted by ID and paginated with
+// `?limit=` and `?offset=`. Requires RBIN_ADMIN_TOKEN.
+pub async fn list_pastes(
+    State(state): State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = check_admin_token(&state, &params, &headers) {
+        return rejection.into_response();
+    }
+
+    let parse = |name: &str, default: usize| match params.get(name) {
+        Some(value) => value
+            .trim()
+            .parse::<usize>()
+            .map_err(|e| format!("Invalid {} '{}': {}", name, value, e)),
+        None => Ok(default),
+    };
+    let (limit, offset) = match (parse("limit", DEFAULT_LIST_LIMIT), parse("offset", 0)) {
+        (Ok(limit), Ok(offset)) => (limit.min(MAX_LIST_LIMIT), offset),
+        (Err(message), _) | (_, Err(message)) => {
+            return (StatusCode::BAD_REQUEST, message).into_response()
+        }
+    };
+
+    let mut ids = match state.store.list().await {
+        Ok(ids) => ids,
+        Err(e) => {
+            tracing::error!("Failed to list pastes: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error listing pastes.").into_response();
+        }
+    };
+    // Directory order is arbitrary, pages must be stable
+    ids.sort_unstable();
+    let total = ids.len();
+
+    let mut pastes = Vec::new();
+    for id in ids.into_iter().skip(offset).take(limit) {
+        // Multi-file pastes report the size of all their files
+        let (size, modified) =
+            match storage::open(&*state.store, &id, state.cipher.as_deref()).await {
+                Ok(file) => (file.len, file.modified),
+                Err(_) => match state.store.bundle_size(&id).await {
+                    Ok(size) => (size, None),
+                    // Removed since the pastes were listed
+                    Err(_) => continue,
+                },
+            };
+        let paste_meta = match state.store.read_meta(&id).await {
+            Ok(paste_meta) => paste_meta.unwrap_or_default(),
+            Err(e) => {
+                tracing::warn!("Failed to read metadata for paste {}: {}", id, e);
+                PasteMeta::default()
+            }
+        };
+        let created = paste_meta.created.or(modified);
+        let preview = preview(&state, &id, &paste_meta).await;
+        pastes.push(ListedPaste {
+            id,
+            size,
+            created: created.map(|time| humantime::format_rfc3339_seconds(time).to_string()),
+            preview,
+        });
+    }
+    Json(PasteList {
+        total,
+        offset,
+        limit,
+        pastes,
+    })
+    .into_response()
+}
+
+/// The first PREVIEW_LENGTH characters of a text paste on one line, or of the
+/// first text file of a multi-file paste. Only the start of the content is
+/// read, except for pastes encrypted at rest, which are read whole up to
+/// SEARCH_MAX_SIZE. `None` for binary and client-encrypted pastes.
+async fn preview(state: &AppState, id: &str, paste_meta: &PasteMeta) -> Option<String> {
+    if paste_meta.binary || paste_meta.encrypted {
+        return None;
+    }
+    let key = match paste_meta.files.iter().position(|file| !file.binary) {
+        Some(index) => storage::bundle_key(id, index),
+        None if paste_meta.files.is_empty() => id.to_string(),
+        None => return None,
+    };
+    let mut file = storage::open(&*state.store, &key, state.cipher.as_deref())
+        .await
+        .ok()?;
+    // No character takes more than 4 bytes
+    let head = if !file.is_encrypted() {
+        file.head(PREVIEW_LENGTH * 4 + UTF8_BOM.len()).await.ok()?
+    } else if file.len <= SEARCH_MAX_SIZE {
+        file.read_all().await.ok()?.to_vec()
+    } else {
+        return None;
+    };
+    let text = String::from_utf8_lossy(head.strip_prefix(UTF8_BOM).unwrap_or(&head));
+    let mut chars = text.trim_start().chars();
+    let mut preview: String = chars
+        .by_ref()
+        .take(PREVIEW_LENGTH)
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    if chars.next().is_some() {
+        preview.push('…');
+    }
+    Some(preview)
+}
+
+/// Lets requests to the admin endpoints through if they carry RBIN_ADMIN_TOKEN
+/// as `?token=` or `X-Admin-Token`.
+fn check_admin_token(
+    state: &AppState,
+    params: &HashMap<String, String>,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, &'static str)> {
+    // Don't even admit the endpoints exist when they're disabled
+    let Some(expected) = &state.admin_token else {
+        return Err((StatusCode::NOT_FOUND, "Not found."));
+    };
+    let provided = headers
+        .get("X-Admin-Token")
+        .and_then(|h| h.to_str().ok())
+        .or_else(|| params.get("token").map(String::as_str));
+    if !provided.is_some_and(|provided| constant_time_eq(provided.as_bytes(), expected.as_bytes()))
+    {
+        tracing::warn!("Rejected admin request: bad or missing admin token");
+        return Err((StatusCode::FORBIDDEN, "Invalid or missing admin token."));
+    }
+    Ok(())
+}
+
+/// JSON response of `GET /admin/pastes`.
+#[derive(Serialize)]
+struct PasteList {
+    /// Number of pastes across all pages
+    total: usize,
+    offset: usize,
+    limit: usize,
+    pastes: Vec<ListedPaste>,
+}
+
+#[derive(Serialize)]
+struct ListedPaste {
+    id: String,
+    /// On disk, i.e. compressed or encrypted
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+    /// The start of the content, for text pastes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preview: Option<String>,
+}
+
+// --- Handler for GET /admin/search ---
+// Finds pastes containing `?q=`, ignoring case. Stores with a full-text index
+// answer from it, otherwise every paste of up to SEARCH_MAX_SIZE is read and
+// scanned. Requires RBIN_ADMIN_TOKEN.
+pub async fn search_pastes(
+    State(state): State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = check_admin_token(&state, &params, &headers) {
+        return rejection.into_response();
+    }
+    let query = params.get("q").map_or("", |q| q.trim());
+    // Anything shorter matches nearly every paste, and the SQLite index can't look it up
+    if query.chars().count() < MIN_SEARCH_LENGTH {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Search for at least {} characters with ?q=.",
+                MIN_SEARCH_LENGTH
+            ),
+        )
+            .into_response();
+    }
+
+    let matches = match state.store.search(query, SEARCH_LIMIT).await {
+        Ok(Some(matches)) => {
+            // Expired pastes stay in the index until the cleanup removes them
+            let mut current = Vec::new();
+            for (id, snippet) in matches {
+                if !matches!(state.store.read_meta(&id).await, Ok(Some(meta)) if meta.is_expired())
+                {
+                    current.push((id, snippet));
+                }
+            }
+            Ok(current)
+        }
+        Ok(None) => scan_pastes(&state, query).await,
+        Err(e) => Err(e),
+    };
+    match matches {
+        Ok(matches) => Json(SearchResults {
+            query: query.to_string(),
+            results: matches
+                .into_iter()
+                .map(|(id, snippet)| SearchResult { id, snippet })
+                .collect(),
+        })
+        .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to search pastes: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error searching pastes.").into_response()
+        }
+    }
+}
+
+/// Reads pastes in ID order until SEARCH_LIMIT of them contain `query`, for
+/// stores without a full-text index.
+async fn scan_pastes(state: &AppState, query: &str) -> std::io::Result<Vec<(String, String)>> {
+    let mut ids = state.store.list().await?;
+    ids.sort_unstable();
+    let needle = query.to_ascii_lowercase();
+    let mut matches = Vec::new();
+    for id in ids {
+        if matches.len() >= SEARCH_LIMIT {
+            break;
+        }
+        let paste_meta = match state.store.read_meta(&id).await {
+            Ok(paste_meta) => paste_meta.unwrap_or_default(),
+            // Still being uploaded
+            Err(_) => continue,
+        };
+        // Binary content and what only the client can decrypt aren't text
+        if paste_meta.binary || paste_meta.encrypted || paste_meta.is_expired() {
+            continue;
+        }
+        let keys: Vec<String> = if paste_meta.files.is_empty() {
+            vec![id.clone()]
+        } else {
+            paste_meta
+                .files
+                .iter()
+                .enumerate()
+                .filter(|(_, file)| !file.binary)
+                .map(|(index, _)| storage::bundle_key(&id, index))
+                .collect()
+        };
+        for key in keys {
+            let Ok(file) = storage::open(&*state.store, &key, state.cipher.as_deref()).await else {
+                continue;
+            };
+            if file.len > SEARCH_MAX_SIZE {
+                continue;
+            }
+            let Ok(content) = file.read_all().await else {
+                continue;
+            };
+            if let Some(snippet) = snippet(&String::from_utf8_lossy(&content), &needle) {
+                matches.push((id.clone(), snippet));
+                break;
+            }
+        }
+    }
+    Ok(matches)
+}
+
+/// The text around the first match of `needle`, ignoring ASCII case, on one
+/// line. `needle` must be lowercase.
+fn snippet(text: &str, needle: &str) -> Option<String> {
+    let start = text.to_ascii_lowercase().find(needle)?;
+    let mut from = start.saturating_sub(SNIPPET_CONTEXT);
+    while !text.is_char_boundary(from) {
+        from -= 1;
+    }
+    let mut to = (start + needle.len() + SNIPPET_CONTEXT).min(text.len());
+    while !text.is_char_boundary(to) {
+        to += 1;
+    }
+    let mut snippet = String::new();
+    if from > 0 {
+        snippet.push('…');
+    }
+    snippet.extend(
+        text[from..to]
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c }),
+    );
+    if to < text.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+/// JSON response of `GET /admin/search`.
+#[derive(Serialize)]
+struct SearchResults {
+    query: String,
+    results: Vec<SearchResult>,
+}
+
+#[derive(Serialize)]
+struct SearchResult {
+    id: String,
+    /// The text around the match
+    snippet: String,
+}
+
+// --- Handler for GET /:id/info ---
+// Metadata about a paste as JSON. Doesn't count as a view.
+#[cfg_attr(feature = "openapi", utoipa::path(
+    get,
+    path = "/{id}/info",
+    tag = "pastes",
+    params(
+        ("id" = String, Path, description = "Paste ID"),
+        ("X-Paste-Password" = Option<String>, Header, description = "Password of a protected paste"),
+    ),
+    responses(
+        (status = 200, description = "Metadata of the paste", body = PasteInfo),
+        (status = 400, description = "`INVALID_ID`", body = error::ErrorJson),
+        (status = 403, description = "Wrong or missing password", body = error::ErrorJson),
+        (status = 404, description = "`NOT_FOUND`", body = error::ErrorJson),
+    )
+))]
+pub async fn paste_info(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    tracing::debug!("Received info request for paste ID: {}", id);
+    if !validate_id(&state, &id) {
+        tracing::warn!("Invalid ID format received: {}", id);
+        return ApiError::InvalidId("Invalid paste ID format.".to_string()).into_response();
+    }
+    let password = headers.get(PASSWORD_HEADER).and_then(|h| h.to_str().ok());
+    let (file, paste_meta) = match open_paste(&state, &id).await {
+        Ok(paste) => paste,
+        Err(response) => return response,
+    };
+    let paste_meta = paste_meta.unwrap_or_default();
+    if let Err(response) = check_access(&state, &id, &paste_meta, password, false).await {
+        return response;
+    }
+
+    // Legacy pastes have no creation time recorded, the file's is the best guess
+    let created = paste_meta.created.or(file.modified);
+    // The size on disk is the compressed one, readers care about the content
+    let size = if file.compressed || file.is_encrypted() {
+        match file.read_all().await {
+            Ok(content) => content.len() as u64,
+            Err(e) => {
+                tracing::error!("Error reading paste {}: {}", id, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.")
+                    .into_response();
+            }
+        }
+    } else {
+        file.len
+    };
+    let rfc3339 = |time: SystemTime| humantime::format_rfc3339_seconds(time).to_string();
+    Json(PasteInfo {
+        id: &id,
+        views: paste_meta.views,
+        views_left: paste_meta.views_left,
+        size,
+        created: created.map(rfc3339),
+        modified: paste_meta.modified.map(rfc3339),
+        expires: paste_meta.expires.map(rfc3339),
+        sha256: paste_meta.sha256.as_deref(),
+    })
+    .into_response()
+}
+
+// --- Handler for GET /:id/qr ---
+// A PNG QR code of the paste's public URL, `?size=` sets the pixels per module.
+pub async fn paste_qr(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    request: Request,
+) -> Response {
+    tracing::debug!("Received QR code request for paste ID: {}", id);
+    let headers = with_host(request.headers().clone(), &request);
+    if !validate_id(&state, &id) {
+        tracing::warn!("Invalid ID format received: {}", id);
+        return ApiError::InvalidId("Invalid paste ID format.".to_string()).into_response();
+    }
+    let module_size = match params.get("size") {
+        None => qr::DEFAULT_MODULE_SIZE,
+        Some(size) => match size.parse::<u32>() {
+            Ok(size) if (1..=qr::MAX_MODULE_SIZE).contains(&size) => size,
+            _ => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "Invalid size '{}': expected 1 to {} pixels per module.",
+                        size,
+                        qr::MAX_MODULE_SIZE
+                    ),
+                )
+                    .into_response();
+            }
+        },
+    };
+    // Only hand out codes for pastes the caller could read
+    let password = headers.get(PASSWORD_HEADER).and_then(|h| h.to_str().ok());
+    let paste_meta = match open_paste(&state, &id).await {
+        Ok((_, paste_meta)) => paste_meta.unwrap_or_default(),
+        Err(response) => return response,
+    };
+    if let Err(response) = check_access(&state, &id, &paste_meta, password, false).await {
+        return response;
+    }
+
+    let url = format!("{}/{}", base_url(&state, &headers), id);
+    match qr::render_png(&url, module_size) {
+        Ok(png) => ([(header::CONTENT_TYPE, "image/png")], png).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to render QR code for paste {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error rendering QR code.",
+            )
+                .into_response()
+        }
+    }
+}
+
+// --- Fallback ---
+// Paths no route matches. Those with a trailing slash, e.g. a URL copied as
+// `/aBcDeF/raw/`, are redirected to the path without it.
+pub async fn strip_trailing_slash(method: Method, uri: Uri) -> Response {
+    let stripped = uri
+        .path()
+        .strip_suffix('/')
+        .filter(|path| !path.is_empty() && !path.ends_with('/'));
+    let Some(path) = stripped.filter(|_| method == Method::GET || method == Method::HEAD) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    // Relative, so it works behind a proxy on a subpath too
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let location = match uri.query() {
+        Some(query) => format!("../{}?{}", name, query),
+        None => format!("../{}", name),
+    };
+    tracing::debug!("Redirecting {} to {}", uri.path(), location);
+    moved_permanently(&location)
+}
+
+// --- Method Fallback ---
+// Methods a route doesn't support, e.g. `DELETE /`. axum adds the `Allow`
+// header listing the supported ones.
+pub async fn method_not_allowed(method: Method, uri: Uri) -> ApiError {
+    tracing::debug!("Method {} not allowed for {}", method, uri.path());
+    ApiError::Other(
+        StatusCode::METHOD_NOT_ALLOWED,
+        format!(
+            "Method {} is not allowed for {}, see the Allow header",
+            method,
+            uri.path()
+        ),
+    )
+}
+
+/// A `301 Moved Permanently`, `Redirect::permanent` is a 308 which older
+/// clients may not follow.
+fn moved_permanently(location: &str) -> Response {
+    match HeaderValue::from_str(location) {
+        Ok(location) => (
+            StatusCode::MOVED_PERMANENTLY,
+            [(header::LOCATION, location)],
+        )
+            .into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+// --- Handlers for GET /:id/ and POST /:id/ ---
+// The file list of a multi-file paste, as plain text URLs, JSON or a page.
+pub async fn bundle_index(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    tracing::debug!("Received file list request for paste ID: {}", id);
+    let password = headers.get(PASSWORD_HEADER).and_then(|h| h.to_str().ok());
+    let view =
+        match negotiate::preferred(&headers, &["text/plain", "text/html", "application/json"]) {
+            "text/html" => View::Html,
+            "application/json" => View::Json,
+            _ => View::Plain,
+        };
+    serve_bundle_index(&state, &method, &headers, id, password, view).await
+}
+
+pub async fn unlock_bundle_index(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    tracing::debug!("Received unlock request for file list of paste: {}", id);
+    let password = form.get("password").map(String::as_str);
+    serve_bundle_index(&state, &Method::POST, &headers, id, password, View::Html).await
+}
+
+async fn serve_bundle_index(
+    state: &AppState,
+    method: &Method,
+    headers: &HeaderMap,
+    id: String,
+    password: Option<&str>,
+    view: View,
+) -> Response {
+    // Single pastes live at `/:id`, also with an extension like `/:id.rs/`
+    let single = id.split_once('.').map_or(id.as_str(), |(single, _)| single);
+    if validate_id(state, single)
+        && !state.store.is_bundle(single).await
+        && state.store.exists(single).await.unwrap_or(false)
+    {
+        return moved_permanently(&format!("../{}", id));
+    }
+    let paste_meta = match load_bundle(state, &id, password, view == View::Html).await {
+        Ok(paste_meta) => paste_meta,
+        Err(response) => return response,
+    };
+    if let Err(response) = count_view(state, &id, method).await {
+        return response;
+    }
+
+    let file_url = |name: &str| format!("{}/{}/{}", base_url(state, headers), id, name);
+    let mut response = match view {
+        View::Json => {
+            let rfc3339 = |time: SystemTime| humantime::format_rfc3339_seconds(time).to_string();
+            Json(BundleJson {
+                id: &id,
+                files: paste_meta
+                    .files
+                    .iter()
+                    .map(|file| BundleFileJson {
+                        url: file_url(&file.name),
+                        name: &file.name,
+                        size: file.size,
+                        content_type: file.content_type.as_deref(),
+                        binary: file.binary,
+                    })
+                    .collect(),
+                created: paste_meta.created.map(rfc3339),
+                expires: paste_meta.expires.map(rfc3339),
+            })
+            .into_response()
+        }
+        View::Html => {
+            let files: Vec<_> = paste_meta
+                .files
+                .iter()
+                .map(|file| (file.name.as_str(), file.size))
+                .collect();
+            Html(html::bundle_page(&id, &files, paste_meta.created)).into_response()
+        }
+        // One URL per line, e.g. for `xargs curl -O`
+        View::Plain => paste_meta
+            .files
+            .iter()
+            .map(|file| file_url(&file.name) + "\n")
+            .collect::<String>()
+            .into_response(),
+    };
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+// --- Handlers for GET /:id/:file and POST /:id/:file ---
+// One file of a multi-file paste, served as is.
+pub async fn bundle_file(
+    State(state): State<AppState>,
+    Path((id, name)): Path<(String, String)>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    tracing::debug!("Received request for file {} of paste ID: {}", name, id);
+    let password = headers.get(PASSWORD_HEADER).and_then(|h| h.to_str().ok());
+    let wants_html = negotiate::preferred(&headers, &["text/plain", "text/html"]) == "text/html";
+    serve_bundle_file(&state, &method, &headers, id, name, password, wants_html).await
+}
+
+pub async fn unlock_bundle_file(
+    State(state): State<AppState>,
+    Path((id, name)): Path<(String, String)>,
+    headers: HeaderMap,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    tracing::debug!("Received unlock request for file {} of paste: {}", name, id);
+    let password = form.get("password").map(String::as_str);
+    serve_bundle_file(&state, &Method::POST, &headers, id, name, password, true).await
+}
+
+async fn serve_bundle_file(
+    state: &AppState,
+    method: &Method,
+    headers: &HeaderMap,
+    id: String,
+    name: String,
+    password: Option<&str>,
+    wants_html: bool,
+) -> Response {
+    let paste_meta = match load_bundle(state, &id, password, wants_html).await {
+        Ok(paste_meta) => paste_meta,
+        Err(response) => return response,
+    };
+    let Some(index) = paste_meta.files.iter().position(|file| file.name == name) else {
+        tracing::warn!("File {} not found in paste {}", name, id);
+        return (
+            StatusCode::NOT_FOUND,
+            format!("File '{}' not found in paste '{}'.", name, id),
+        )
+            .into_response();
+    };
+    let key = storage::bundle_key(&id, index);
+    let file = match storage::open(&*state.store, &key, state.cipher.as_deref()).await {
+        Ok(file) => file,
+        Err(e) => {
+            tracing::error!("Error reading file {} of paste {}: {}", name, id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.").into_response();
+        }
+    };
+    let version = file.version();
+    let modified = file.modified;
+    let body = if file.len >= STREAM_MIN_SIZE && !file.is_encrypted() {
+        PasteBody::File(file)
+    } else {
+        match file.read_all().await {
+            Ok(content) => PasteBody::Loaded(content),
+            Err(e) => {
+                tracing::error!("Error reading file {} of paste {}: {}", name, id, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.")
+                    .into_response();
+            }
+        }
+    };
+
+    // Served like a single paste of the file's type
+    let bundle_file = &paste_meta.files[index];
+    let file_meta = PasteMeta {
+        content_type: bundle_file.content_type.clone(),
+        binary: bundle_file.binary,
+        sha256: bundle_file.sha256.clone(),
+        files: Vec::new(),
+        ..paste_meta
+    };
+    let paste = LoadedPaste {
+        body,
+        version,
+        created: file_meta.created.or(modified),
+        modified,
+        meta: file_meta,
+    };
+    let paste = match strip_bom(&id, paste).await {
+        Ok(paste) => paste,
+        Err(response) => return response,
+    };
+    let validators = CacheValidators::new(&id, &paste, &format!("file.{}", name));
+    if let Some(response) = validators.not_modified(headers) {
+        return response;
+    }
+    if let Err(response) = count_view(state, &id, method).await {
+        return response;
+    }
+    let content_type = paste_content_type(&paste.meta);
+    let response = raw_response(headers, content_type, paste.body).await;
+    validators.apply(with_checksum(response, &paste.meta))
+}
+
+/// Reads the metadata of multi-file paste `id` and checks access to it like
+/// `load_paste` does for single pastes.
+async fn load_bundle(
+    state: &AppState,
+    id: &str,
+    password: Option<&str>,
+    wants_html: bool,
+) -> Result<PasteMeta, Response> {
+    if !validate_id(state, id) {
+        tracing::warn!("Invalid ID format received: {}", id);
+        return Err(ApiError::InvalidId("Invalid paste ID format.".to_string()).into_response());
+    }
+    let paste_meta = match state.store.read_meta(id).await {
+        Ok(Some(paste_meta)) if !paste_meta.files.is_empty() => paste_meta,
+        Ok(_) => {
+            tracing::warn!("Multi-file paste not found: {}", id);
+            state.metrics.paste_not_found();
+            return Err(
+                (StatusCode::NOT_FOUND, format!("Paste '{}' not found.", id)).into_response(),
+            );
+        }
+        Err(e) => {
+            tracing::error!("Failed to read metadata for paste {}: {}", id, e);
+            return Err(
+                (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.").into_response(),
+            );
+        }
+    };
+    // The sidecar is written before the files are moved into place
+    if !state.store.is_bundle(id).await {
+        tracing::warn!("Multi-file paste not found: {}", id);
+        state.metrics.paste_not_found();
+        return Err((StatusCode::NOT_FOUND, format!("Paste '{}' not found.", id)).into_response());
+    }
+    check_access(state, id, &paste_meta, password, wants_html).await?;
+    Ok(paste_meta)
+}
+
+/// JSON response of `GET /:id/` for multi-file pastes.
+#[derive(Serialize)]
+struct BundleJson<'a> {
+    id: &'a str,
+    files: Vec<BundleFileJson<'a>>,
+    /// RFC3339
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BundleFileJson<'a> {
+    name: &'a str,
+    url: String,
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_type: Option<&'a str>,
+    binary: bool,
+}
+
+/// JSON response of `GET /:id/info`.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+struct PasteInfo<'a> {
+    id: &'a str,
+    views: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    views_left: Option<u64>,
+    size: u64,
+    /// RFC3339
+    #[serde(skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+    /// Only set for edited pastes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires: Option<String>,
+    /// Hex, as in `X-Content-SHA256`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<&'a str>,
+}
+
+/// `ETag`, `Last-Modified` and `Cache-Control` for one representation of a
+/// paste.
+struct CacheValidators {
+    etag: HeaderValue,
+    // Whole seconds, as HTTP dates have them
+    last_modified: Option<SystemTime>,
+    cache_control: HeaderValue,
+}
+
+impl CacheValidators {
+    /// `variant` tells apart the representations served under the same URL
+    /// (plain, HTML page, ...). The tag is weak as gzip encoding may vary.
+    fn new(id: &str, paste: &LoadedPaste, variant: &str) -> Self {
+        let variant: String = variant
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '.')
+            .collect();
+        let etag = format!("W/\"{}-{}-{}\"", id, paste.version, variant);
+        // Expiring, protected and encrypted pastes must not linger in caches
+        let cache_control = if paste.meta.expires.is_some()
+            || paste.meta.views_left.is_some()
+            || paste.meta.is_protected()
+            || paste.meta.encrypted
+        {
+            HeaderValue::from_static("no-store")
+        } else {
+            HeaderValue::from_static(PASTE_CACHE_CONTROL)
+        };
+        // The file's time follows edits, the creation time is for stores without one
+        let last_modified = paste.modified.or(paste.created).map(|time| {
+            let seconds = time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs());
+            SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+        });
+        Self {
+            etag: HeaderValue::from_str(&etag)
+                .unwrap_or_else(|_| HeaderValue::from_static("W/\"\"")),
+            last_modified,
+            cache_control,
+        }
+    }
+
+    /// `304 Not Modified` if the client's `If-None-Match` covers this
+    /// representation, or without one, if it hasn't changed since
+    /// `If-Modified-Since`.
+    fn not_modified(&self, headers: &HeaderMap) -> Option<Response> {
+        let unchanged = if headers.contains_key(header::IF_NONE_MATCH) {
+            let etag = self.etag.to_str().ok()?;
+            headers
+                .get_all(header::IF_NONE_MATCH)
+                .iter()
+                .filter_map(|h| h.to_str().ok())
+                .flat_map(|list| list.split(','))
+                .map(str::trim)
+                .any(|tag| tag == "*" || weak_eq(tag, etag))
+        } else {
+            // The ETag takes precedence, see RFC 9110 section 13.2.2
+            let since = headers
+                .get(header::IF_MODIFIED_SINCE)
+                .and_then(|h| h.to_str().ok())
+                .and_then(|date| httpdate::parse_http_date(date).ok());
+            matches!((self.last_modified, since), (Some(modified), Some(since)) if modified <= since)
+        };
+        unchanged.then(|| {
+            let mut response = (
+                StatusCode::NOT_MODIFIED,
+                [
+                    (header::ETAG, self.etag.clone()),
+                    (header::CACHE_CONTROL, self.cache_control.clone()),
+                ],
+            )
+                .into_response();
+            self.insert_last_modified(response.headers_mut());
+            response
+        })
+    }
+
+    /// Adds the validators to a successful response.
+    fn apply(self, mut response: Response) -> Response {
+        if response.status().is_success() {
+            let headers = response.headers_mut();
+            self.insert_last_modified(headers);
+            headers.insert(header::ETAG, self.etag);
+            headers.insert(header::CACHE_CONTROL, self.cache_control);
+        }
+        response
+    }
+
+    fn insert_last_modified(&self, headers: &mut HeaderMap) {
+        let Some(modified) = self.last_modified else {
+            return;
+        };
+        if let Ok(date) = HeaderValue::from_str(&httpdate::fmt_http_date(modified)) {
+            headers.insert(header::LAST_MODIFIED, date);
+        }
+    }
+}
+
+/// Weak entity tag comparison, ignoring `W/` prefixes.
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+// --- Byte Order Marks and Charsets ---
+// Editors on Windows like to start UTF-8 files with a byte order mark, which
+// then ends up in terminals and pages. Text views leave it out, downloads keep
+// the file as uploaded. `?charset=` transcodes plain text responses from the
+// UTF-8 they are stored as, e.g. for tools that expect windows-1252.
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Text pastes, i.e. neither binary nor encrypted by the client.
+fn is_text_paste(paste_meta: &PasteMeta) -> bool {
+    !paste_meta.binary && !paste_meta.encrypted
+}
+
+/// Drops a leading byte order mark from text pastes. Large pastes starting
+/// with one are read into memory for that rather than streamed.
+async fn strip_bom(id: &str, mut paste: LoadedPaste) -> Result<LoadedPaste, Response> {
+    if !is_text_paste(&paste.meta) {
+        return Ok(paste);
+    }
+    let failed = |e: std::io::Error| {
+        tracing::error!("Error reading paste {}: {}", id, e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.").into_response()
+    };
+    let has_bom = match &mut paste.body {
+        PasteBody::Loaded(content) => content.starts_with(UTF8_BOM),
+        PasteBody::File(file) => file.starts_with(UTF8_BOM).await.map_err(failed)?,
+    };
+    if !has_bom {
+        return Ok(paste);
+    }
+    let content = match paste.body {
+        PasteBody::Loaded(content) => content,
+        PasteBody::File(file) => file.read_all().await.map_err(failed)?,
+    };
+    paste.body = PasteBody::Loaded(content.slice(UTF8_BOM.len()..));
+    // The checksum is of the content as uploaded
+    paste.meta.sha256 = None;
+    Ok(paste)
+}
+
+/// The theme asked for with `?theme=`: one of syntect's, or `dark` and `light`
+/// for the configured ones. `None` leaves it to the browser's color scheme.
+fn parse_theme<'a>(
+    state: &'a AppState,
+    params: &'a HashMap<String, String>,
+) -> Result<Option<&'a str>, ApiError> {
+    let Some(theme) = params.get("theme").filter(|theme| !theme.is_empty()) else {
+        return Ok(None);
+    };
+    match theme.as_str() {
+        "dark" => Ok(Some(&state.highlight_theme)),
+        "light" => Ok(Some(
+            state
+                .light_theme
+                .as_deref()
+                .unwrap_or(highlight::DEFAULT_LIGHT_THEME),
+        )),
+        theme if highlight::is_theme(theme) => Ok(Some(theme)),
+        _ => Err(ApiError::BadRequest(format!(
+            "Unknown theme '{}', use dark, light or one of {}",
+            theme,
+            highlight::theme_names().join(", ")
+        ))),
+    }
+}
+
+/// The charset asked for with `?charset=`, `None` for UTF-8 or none at all.
+fn parse_charset(params: &HashMap<String, String>) -> Result<Option<&'static Encoding>, ApiError> {
+    let Some(label) = params.get("charset").filter(|label| !label.is_empty()) else {
+        return Ok(None);
+    };
+    let Some(encoding) = Encoding::for_label(label.trim().as_bytes()) else {
+        return Err(ApiError::BadRequest(format!("Unknown charset '{}'", label)));
+    };
+    // UTF-16 is encoded by hand, encoding_rs only decodes it
+    let supported =
+        encoding == UTF_16LE || encoding == UTF_16BE || encoding.output_encoding() == encoding;
+    if !supported {
+        return Err(ApiError::BadRequest(format!(
+            "Pastes can't be served as charset '{}'",
+            label
+        )));
+    }
+    Ok(Some(encoding).filter(|&encoding| encoding != UTF_8))
+}
+
+/// Serves a text paste transcoded to `charset`, or `406 Not Acceptable` if it
+/// has characters the charset can't represent.
+async fn transcoded_response(
+    headers: &HeaderMap,
+    id: &str,
+    content_type: &str,
+    body: PasteBody,
+    charset: &'static Encoding,
+) -> Response {
+    let content = match body {
+        PasteBody::Loaded(content) => content,
+        PasteBody::File(file) => match file.read_all().await {
+            Ok(content) => content,
+            Err(e) => {
+                tracing::error!("Error reading paste {}: {}", id, e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.")
+                    .into_response();
+            }
+        },
+    };
+    let text = String::from_utf8_lossy(&content);
+    let encoded: Vec<u8> = if charset == UTF_16LE {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    } else if charset == UTF_16BE {
+        text.encode_utf16().flat_map(u16::to_be_bytes).collect()
+    } else {
+        let (encoded, _, unmappable) = charset.encode(&text);
+        if unmappable {
+            tracing::debug!("Paste {} can't be encoded as {}", id, charset.name());
+            return (
+                StatusCode::NOT_ACCEPTABLE,
+                format!(
+                    "Paste contains characters that can't be represented in {}",
+                    charset.name()
+                ),
+            )
+                .into_response();
+        }
+        encoded.into_owned()
+    };
+    let content_type = format!("{}; charset={}", content_type, charset.name());
+    let content_type = HeaderValue::from_str(&content_type)
+        .unwrap_or_else(|_| HeaderValue::from_static(PLAIN_CONTENT_TYPE));
+    raw_response(headers, content_type, PasteBody::Loaded(encoded.into())).await
+}
+
+/// Adds `X-Content-SHA256` to a response with the paste's content as it was
+/// uploaded, so clients can verify it. Partial responses get the checksum of
+/// the whole content.
+fn with_checksum(mut response: Response, paste_meta: &PasteMeta) -> Response {
+    let checksum = paste_meta
+        .sha256
+        .as_deref()
+        .and_then(|sha256| HeaderValue::from_str(sha256).ok());
+    if let Some(checksum) = checksum.filter(|_| response.status().is_success()) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static(CHECKSUM_HEADER), checksum);
+    }
+    response
+}
+
+/// The `Content-Type` to serve a paste's raw content with.
+fn paste_content_type(paste_meta: &PasteMeta) -> HeaderValue {
+    paste_meta
+        .content_type
+        .as_deref()
+        .and_then(|ct| {
+            HeaderValue::from_str(&content_type::header_value(ct, !paste_meta.binary)).ok()
+        })
+        .unwrap_or_else(|| HeaderValue::from_static(PLAIN_CONTENT_TYPE))
+}
+
+/// Serves the paste content itself, honoring a single byte `Range`.
+async fn raw_response(headers: &HeaderMap, content_type: HeaderValue, body: PasteBody) -> Response {
+    let Some(range) = headers.get(header::RANGE) else {
+        return full_response(headers, content_type, body).await;
+    };
+    // Ranges refer to the uncompressed content, which compressed files don't
+    // know the length of without decompressing them
+    let body = match body {
+        PasteBody::File(file) if file.compressed => match file.read_all().await {
+            Ok(content) => PasteBody::Loaded(content),
+            Err(e) => {
+                tracing::error!("Error reading paste for range request: {}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.")
+                    .into_response();
+            }
+        },
+        body => body,
+    };
+    let len = match &body {
+        PasteBody::Loaded(content) => content.len() as u64,
+        PasteBody::File(file) => file.len,
+    };
+    let (start, end) = match range.to_str().ok().and_then(|r| parse_range(r, len)) {
+        Some(Some(range)) => range,
+        // Multiple ranges aren't supported, send everything instead
+        Some(None) => return full_response(headers, content_type, body).await,
+        None => {
+            tracing::debug!("Unsatisfiable range {:?} for {} bytes", range, len);
+            return (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", len))],
+            )
+                .into_response();
+        }
+    };
+    let part_len = end - start + 1;
+    let part = match body {
+        PasteBody::Loaded(content) => Body::from(content.slice(start as usize..=end as usize)),
+        PasteBody::File(mut file) => {
+            if let Err(e) = file.file.seek(std::io::SeekFrom::Start(start)).await {
+                tracing::error!("Error seeking in paste for range request: {}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.")
+                    .into_response();
+            }
+            Body::from_stream(ReaderStream::new(file.file.take(part_len)))
+        }
+    };
+    (
+        StatusCode::PARTIAL_CONTENT,
+        [
+            (header::CONTENT_TYPE, content_type),
+            (
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, len))
+                    .unwrap_or_else(|_| HeaderValue::from_static("bytes */*")),
+            ),
+            (header::CONTENT_LENGTH, HeaderValue::from(part_len)),
+            (header::ACCEPT_RANGES, HeaderValue::from_static("bytes")),
+            (
+                header::X_CONTENT_TYPE_OPTIONS,
+                HeaderValue::from_static("nosniff"),
+            ),
+        ],
+        part,
+    )
+        .into_response()
+}
+
+/// Parses a `Range` header for content of `len` bytes into an inclusive byte
+/// range. `Some(None)` means a multi-range request, `None` an invalid or
+/// unsatisfiable one.
+fn parse_range(range: &str, len: u64) -> Option<Option<(u64, u64)>> {
+    let spec = range.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return Some(None);
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        // Suffix range: the last `n` bytes
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            if suffix == 0 {
+                return None;
+            }
+            (len.saturating_sub(suffix), len.checked_sub(1)?)
+        }
+        (start, "") => (start.parse().ok()?, len.checked_sub(1)?),
+        (start, end) => {
+            let end: u64 = end.parse().ok()?;
+            (start.parse().ok()?, end.min(len.checked_sub(1)?))
+        }
+    };
+    (start <= end && start < len).then_some(Some((start, end)))
+}
+
+async fn full_response(
+    headers: &HeaderMap,
+    content_type: HeaderValue,
+    body: PasteBody,
+) -> Response {
+    let mut response = match body {
+        PasteBody::Loaded(content) => paste_response(headers, content_type, content).await,
+        PasteBody::File(file) => stream_response(headers, content_type, file),
+    };
+    response
+        .headers_mut()
+        .insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    response
+}
+
+/// Builds a `200 OK` paste response, gzip encoded when the client supports it
+/// and the body is large enough for compression to pay off.
+async fn paste_response(headers: &HeaderMap, content_type: HeaderValue, body: Bytes) -> Response {
+    let content_type = (header::CONTENT_TYPE, content_type);
+    let vary = (header::VARY, HeaderValue::from_static("accept-encoding"));
+    // Browsers must not second-guess stored content types
+    let nosniff = (
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    if body.len() < GZIP_MIN_SIZE || !negotiate::accepts_encoding(headers, "gzip") {
+        return (StatusCode::OK, [content_type, vary, nosniff], body).into_response();
+    }
+    match tokio::task::spawn_blocking(move || storage::gzip(&body)).await {
+        Ok(Ok(compressed)) => (
+            StatusCode::OK,
+            [
+                content_type,
+                vary,
+                nosniff,
+                (header::CONTENT_ENCODING, HeaderValue::from_static("gzip")),
+            ],
+            compressed,
+        )
+            .into_response(),
+        Ok(Err(e)) => {
+            tracing::error!("Failed to gzip response: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error encoding paste.").into_response()
+        }
+        Err(e) => {
+            tracing::error!("Gzip task failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Error encoding paste.").into_response()
+        }
+    }
+}
+
+/// Streams a large paste straight from disk. Compressed files are
+/// sent as-is to clients accepting gzip, everything else is (de)compressed on
+/// the fly, in which case the length isn't known upfront.
+fn stream_response(
+    headers: &HeaderMap,
+    content_type: HeaderValue,
+    paste: storage::PasteFile,
+) -> Response {
+    let reader = BufReader::new(paste.file);
+    let gzip = negotiate::accepts_encoding(headers, "gzip");
+    let (body, length) = match (paste.compressed, gzip) {
+        (false, false) | (true, true) => (
+            Body::from_stream(ReaderStream::new(reader)),
+            Some(paste.len),
+        ),
+        (false, true) => (
+            Body::from_stream(ReaderStream::new(GzipEncoder::new(reader))),
+            None,
+        ),
+        (true, false) => (
+            Body::from_stream(ReaderStream::new(GzipDecoder::new(reader))),
+            None,
+        ),
+    };
+    let mut response = (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::VARY, HeaderValue::from_static("accept-encoding")),
+            (
+                header::X_CONTENT_TYPE_OPTIONS,
+                HeaderValue::from_static("nosniff"),
+            ),
+        ],
+        body,
+    )
+        .into_response();
+    if let Some(length) = length {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_LENGTH, length.into());
+    }
+    if gzip {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    }
+    response
+}
+
+/// A paste that passed `load_paste`'s checks.
+struct LoadedPaste {
+    body: PasteBody,
+    meta: PasteMeta,
+    version: String,
+    created: Option<SystemTime>,
+    /// Of the content, changed by edits and appends
+    modified: Option<SystemTime>,
+}
+
+enum PasteBody {
+    Loaded(Bytes),
+    /// Large paste left on disk for `stream_response`.
+    File(storage::PasteFile),
+}
+
+/// Validates `id` and reads the paste, lazily removing it if it has expired and
+/// checking `password` if it is protected. Failures come back as ready-to-send
+/// error responses; `wants_html` turns a missing password into a password form.
+/// With `stream`, pastes of at least `STREAM_MIN_SIZE` bytes are left on disk
+/// instead of being read into memory (and the cache). The read isn't counted
+/// yet, see `count_view`.
+async fn load_paste(
+    state: &AppState,
+    id: &str,
+    password: Option<&str>,
+    wants_html: bool,
+    stream: bool,
+) -> Result<LoadedPaste, Response> {
+    if !validate_id(state, id) {
+        tracing::warn!("Invalid ID format received: {}", id);
+        return Err(ApiError::InvalidId("Invalid paste ID format.".to_string()).into_response());
+    }
+
+    let cached = state.cache.as_ref().and_then(|cache| cache.get(id));
+    let (body, meta, version, modified) = match cached {
+        Some(CachedPaste {
+            content,
+            meta,
+            version,
+            modified,
+        }) => {
+            tracing::debug!("Serving paste {} from cache", id);
+            (PasteBody::Loaded(content), meta, version, modified)
+        }
+        None => {
+            let (file, meta) = open_paste(state, id).await?;
+            let version = file.version();
+            let modified = file.modified;
+            // Encrypted files can only be decrypted as a whole
+            if stream && file.len >= STREAM_MIN_SIZE && !file.is_encrypted() {
+                (PasteBody::File(file), meta, version, modified)
+            } else {
+                let content = match file.read_all().await {
+                    Ok(content) => content,
+                    Err(e) => {
+                        tracing::error!("Error reading paste {}: {}", id, e);
+                        return Err(
+                            (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.")
+                                .into_response(),
+                        );
+                    }
+                };
+                if let Some(cache) = &state.cache {
+                    let paste = CachedPaste {
+                        content: content.clone(),
+                        meta: meta.clone(),
+                        version: version.clone(),
+                        modified,
+                    };
+                    cache.insert(id, paste);
+                }
+                (PasteBody::Loaded(content), meta, version, modified)
+            }
+        }
+    };
+
+    let meta = meta.unwrap_or_default();
+    check_access(state, id, &meta, password, wants_html).await?;
+    tracing::debug!("Successfully retrieved paste ID: {}", id);
+    Ok(LoadedPaste {
+        body,
+        version,
+        // Legacy pastes have no creation time recorded, the file's is the best guess
+        created: meta.created.or(modified),
+        modified,
+        meta,
+    })
+}
+
+/// Lazily removes `id` if it has expired and checks `password` if it is
+/// protected, see `load_paste`.
+async fn check_access(
+    state: &AppState,
+    id: &str,
+    meta: &PasteMeta,
+    password: Option<&str>,
+    wants_html: bool,
+) -> Result<(), Response> {
+    if meta.is_expired() {
+        tracing::info!("Paste {} has expired, removing it", id);
+        remove_paste(state, id).await;
+        return Err((StatusCode::GONE, format!("Paste '{}' has expired.", id)).into_response());
+    }
+    if meta.is_protected() {
+        let provided = password.map(str::to_string);
+        let paste_meta = meta.clone();
+        let verified =
+            tokio::task::spawn_blocking(move || paste_meta.verify_password(provided.as_deref()))
+                .await
+                .unwrap_or(false);
+        if !verified {
+            tracing::warn!(
+                "Rejected read of protected paste {}: bad or missing password",
+                id
+            );
+            if wants_html {
+                let page = html::password_page(id, password.is_some());
+                return Err((StatusCode::UNAUTHORIZED, Html(page)).into_response());
+            }
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                format!(
+                    "Paste '{}' is password protected, send the password in the {} header.",
+                    id, PASSWORD_HEADER
+                ),
+            )
+                .into_response());
+        }
+    }
+    Ok(())
+}
+
+/// Counts a read of `id` that serves its content, once a `304 Not Modified`
+/// is ruled out. `HEAD` requests don't count, link previews and `curl -I`
+/// mustn't use up the views of a paste.
+async fn count_view(state: &AppState, id: &str, method: &Method) -> Result<(), Response> {
+    if method == Method::HEAD {
+        return Ok(());
+    }
+    record_view(state, id).await?;
+    state.metrics.paste_retrieved();
+    Ok(())
+}
+
+/// Counts a successful read of `id` in its metadata sidecar, using up one of
+/// its views if they are limited. The last one removes the paste, reads from
+/// then on get `404 Not Found`, even those that loaded it before. Failures to
+/// count are only logged, they shouldn't keep anyone from reading the paste.
+async fn record_view(state: &AppState, id: &str) -> Result<(), Response> {
+    let not_found =
+        || (StatusCode::NOT_FOUND, format!("Paste '{}' not found.", id)).into_response();
+    // Serializes the read-modify-write against other views and removals
+    let _guard = state.paste_locks.lock(id).await;
+    // Removed meanwhile, don't leave an orphaned sidecar behind
+    if !state.store.exists(id).await.unwrap_or(false) {
+        return Err(not_found());
+    }
+    let mut paste_meta = match state.store.read_meta(id).await {
+        Ok(paste_meta) => paste_meta.unwrap_or_default(),
+        Err(e) => {
+            tracing::warn!("Not counting view of paste {}: {}", id, e);
+            return Ok(());
+        }
+    };
+    let last_view = match paste_meta.views_left {
+        // Its last view is being served, the removal is still to come
+        Some(0) => return Err(not_found()),
+        Some(left) => {
+            paste_meta.views_left = Some(left - 1);
+            left == 1
+        }
+        None => false,
+    };
+    paste_meta.views += 1;
+    if let Err(e) = state.store.write_meta(id, &paste_meta).await {
+        tracing::warn!("Failed to count view of paste {}: {}", id, e);
+        // Better no read than one too many
+        if paste_meta.views_left.is_some() {
+            return Err(not_found());
+        }
+    }
+    if last_view {
+        tracing::info!("Paste {} reached its maximum views, removing it", id);
+        remove_locked_paste(state, id).await;
+    }
+    Ok(())
+}
+
+/// Opens a paste's content and reads its metadata from disk, bypassing the cache.
+async fn open_paste(
+    state: &AppState,
+    id: &str,
+) -> Result<(storage::PasteFile, Option<PasteMeta>), Response> {
+    tracing::debug!("Attempting to read paste {}", id);
+
+    // The content is only renamed into place once its metadata is complete, so
+    // open it first: a missing file means the paste doesn't exist (yet).
+    let file = match storage::open(&*state.store, id, state.cipher.as_deref()).await {
+        Ok(file) => file,
+        Err(e) => {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                if state.store.is_bundle(id).await {
+                    return Err((
+                        StatusCode::NOT_FOUND,
+                        format!(
+                            "Paste '{}' has multiple files, see {}/ for the list.",
+                            id, id
+                        ),
+                    )
+                        .into_response());
+                }
+                tracing::warn!("Paste ID not found: {}", id);
+                state.metrics.paste_not_found();
+                return Err(
+                    (StatusCode::NOT_FOUND, format!("Paste '{}' not found.", id)).into_response(),
+                );
+            } else {
+                tracing::error!("Error reading paste {}: {}", id, e);
+                return Err(
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.").into_response(),
+                );
+            }
+        }
+    };
+
+    match state.store.read_meta(id).await {
+        Ok(meta) => Ok((file, meta)),
+        Err(e) => {
+            tracing::error!("Error reading metadata for paste {}: {}", id, e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Error retrieving paste.").into_response())
+        }
+    }
+}
+
+/// Removes an existing paste from disk and the cache.
+async fn remove_paste(state: &AppState, id: &str) {
+    let _guard = state.paste_locks.lock(id).await;
+    remove_locked_paste(state, id).await;
+}
+
+/// Like `remove_paste`, for callers already holding the lock of `id`.
+async fn remove_locked_paste(state: &AppState, id: &str) {
+    let freed = state.store.delete(id).await;
+    if let Some(limit) = &state.paste_limit {
+        limit.forget(id);
+    }
+    if let Some(cache) = &state.cache {
+        cache.invalidate(id);
+    }
+    // Gone already, e.g. removed by a repeated DELETE or an expiry meanwhile
+    let Some(freed) = freed else {
+        return;
+    };
+    if let Some(quota) = &state.quota {
+        quota.release(freed);
+    }
+    state.metrics.paste_removed();
+}
+
+// --- Handler for DELETE /:id ---
+#[cfg_attr(feature = "openapi", utoipa::path(
+    delete,
+    path = "/{id}",
+    tag = "pastes",
+    params(
+        ("id" = String, Path, description = "Paste ID"),
+        ("X-Delete-Token" = Option<String>, Header, description = "Delete token of the paste"),
+        ("token" = Option<String>, Query, description = "The delete token, instead of the header"),
+    ),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 403, description = "Wrong or missing token, or read-only mode", body = error::ErrorJson),
+        (status = 404, description = "`NOT_FOUND`", body = error::ErrorJson),
+    )
+))]
+pub async fn delete_paste(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    tracing::debug!("Received request to delete paste ID: {}", id);
+    if let Err(e) = check_writable(&state) {
+        return e.into_response();
+    }
+    if !validate_id(&state, &id) {
+        tracing::warn!("Invalid ID format received: {}", id);
+        return ApiError::InvalidId("Invalid paste ID format.".to_string()).into_response();
+    }
+
+    // The token may be passed as a header or, for convenience, as `?token=`
+    let provided = headers
+        .get("X-Delete-Token")
+        .and_then(|h| h.to_str().ok())
+        .or_else(|| params.get("token").map(String::as_str));
+
+    if !state.store.exists(&id).await.unwrap_or(false) {
+        tracing::warn!("Delete requested for unknown paste ID: {}", id);
+        return (StatusCode::NOT_FOUND, format!("Paste '{}' not found.", id)).into_response();
+    }
+
+    let expected = match state.store.read_meta(&id).await {
+        Ok(paste_meta) => paste_meta.and_then(|m| m.delete_token),
+        Err(e) => {
+            tracing::error!("Error reading metadata for paste {}: {}", id, e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Error deleting paste.").into_response();
+        }
+    };
+
+    match (provided, expected) {
+        (Some(provided), Some(expected))
+            if constant_time_eq(provided.as_bytes(), expected.as_bytes()) =>
+        {
+            remove_paste(&state, &id).await;
+            tracing::info!("Deleted paste ID: {}", id);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        _ => {
+            tracing::warn!("Rejected delete for paste ID {}: bad or missing token", id);
+            (StatusCode::FORBIDDEN, "Invalid or missing deletion token.").into_response()
+        }
+    }
+}
+
+// --- Helpers ---
+/// Whether `id` may name a paste, checked by every handler taking one before
+/// the store sees it. Generated and custom IDs alike may only use `id_chars`,
+/// the characters of RBIN_ID_ALPHABET, RBIN_ID_PREFIX and RBIN_ID_SCHEME plus
+/// `-` and `_`, and never a path separator, `.` or NUL, so IDs are safe to use
+/// as file names in RBIN_PASTE_DIR.
+fn validate_id(state: &AppState, id: &str) -> bool {
+    (MIN_CUSTOM_ID_LENGTH..=MAX_ID_LENGTH).contains(&id.len())
+        && id.chars().all(|c| {
+            (c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                && state.id_chars.binary_search(&c).is_ok()
+        })
+}
+
+/// Reduces an uploaded file name to something safe to put in a header: no
+/// directories, quotes or control characters (CR/LF would allow header injection).
+fn sanitize_file_name(file_name: &str) -> Option<String> {
+    let base = file_name.rsplit(['/', '\\']).next().unwrap_or("");
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+        .take(MAX_FILE_NAME_LENGTH)
+        .collect();
+    let cleaned = cleaned.trim();
+    // curl names stdin uploads (`rbin=@-`) "-"
+    if cleaned.is_empty() || cleaned == "-" || cleaned.chars().all(|c| c == '.') {
+        return None;
+    }
+    Some(cleaned.to_string())
+}
+
+/// A `Content-Disposition: attachment` value for a sanitized file name, with
+/// an ASCII fallback and the exact UTF-8 name for clients that support it.
+fn content_disposition(file_name: &str) -> String {
+    let ascii: String = file_name
+        .chars()
+        .map(|c| if c.is_ascii() { c } else { '_' })
+        .collect();
+    if ascii == file_name {
+        return format!("attachment; filename=\"{}\"", file_name);
+    }
+    let encoded: String = file_name
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        ascii, encoded
+    )
+}
+
+/// The public URL prefix for paste links: `RBIN_BASE_URL` if set, else derived
+/// from the `Host` header. The scheme is `https` when serving TLS ourselves.
+/// Behind a trusted proxy (RBIN_TRUST_PROXY) its `X-Forwarded-Host` and
+/// `X-Forwarded-Proto` headers take precedence.
+fn base_url(state: &AppState, headers: &HeaderMap) -> String {
+    if let Some(base_url) = &state.base_url {
+        return base_url.to_string();
+    }
+    let forwarded = |name: &str| {
+        headers
+            .get(name)
+            .filter(|_| state.trust_proxy)
+            .and_then(|h| h.to_str().ok())
+    };
+    let host = forwarded("X-Forwarded-Host")
+        .or_else(|| headers.get(header::HOST).and_then(|h| h.to_str().ok()))
+        .unwrap_or("localhost");
+    let scheme = forwarded("X-Forwarded-Proto").unwrap_or(if state.tls { "https" } else { "http" });
+    format!("{}://{}", scheme, host)
+}
+
+/// HTTP/2 requests carry the host in the URI rather than a `Host` header, fill
+/// it in so `base_url` works the same for both.
+fn with_host(mut headers: HeaderMap, request: &Request) -> HeaderMap {
+    if !headers.contains_key(header::HOST) {
+        if let Some(host) = request
+            .uri()
+            .authority()
+            .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok())
+        {
+            headers.insert(header::HOST, host);
+        }
+    }
+    headers
+}
+
+/// The client's IP: the first `X-Forwarded-For` entry if present and sent by a
+/// trusted proxy, else the peer address.
+fn client_ip(state: &AppState, headers: &HeaderMap, addr: SocketAddr) -> IpAddr {
+    headers
+        .get("X-Forwarded-For")
+        .filter(|_| state.trust_proxy)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|list| list.split(',').next())
+        .and_then(|ip| ip.trim().parse().ok())
+        .unwrap_or_else(|| addr.ip())
+}
+
+/// Formats a byte count for messages, in the largest unit that divides it evenly.
+fn format_size(size: usize) -> String {
+    const UNITS: [(usize, &str); 3] = [
+        (1024 * 1024 * 1024, "GiB"),
+        (1024 * 1024, "MiB"),
+        (1024, "KiB"),
+    ];
+    UNITS
+        .iter()
+        .find(|(unit, _)| size >= *unit && size.is_multiple_of(*unit))
+        .map(|(unit, name)| format!("{} {}", size / unit, name))
+        .unwrap_or_else(|| format!("{} bytes", size))
+}
+
+fn generate_token() -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), TOKEN_LENGTH)
+}
+
+/// Compares two secrets without short-circuiting on the first differing byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
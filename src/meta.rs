@@ -0,0 +1,148 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+// --- Paste Metadata ---
+// Kept by the paste store next to the content, as JSON in `{id}.meta` for
+// the filesystem store. Pastes created before metadata existed have none.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PasteMeta {
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expires: Option<SystemTime>,
+    /// Secret required by `DELETE /:id`. Legacy pastes without one can't be deleted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delete_token: Option<String>,
+    /// Secret required to replace the content with `PUT /:id`. Pastes from
+    /// before edit tokens existed can't be edited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit_token: Option<String>,
+    /// Secret required to add content with `POST /:id/append`, missing for
+    /// pastes from before append tokens existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub append_token: Option<String>,
+    /// Argon2 PHC string; when set, reading the paste requires the password.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+    /// Sanitized content type to serve the paste with instead of `text/plain`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// Set for content that isn't valid UTF-8, which is never shown as text.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub binary: bool,
+    /// Set for short links (`?shorten=1`), whose content is the URL that
+    /// `GET /:id` redirects to.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub redirect: bool,
+    /// Set for content encrypted by the client (`?encrypted=1`), which is only
+    /// ever served as it is and never cached.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
+    /// Hex SHA-256 of the content, sent as `X-Content-SHA256`. Missing for
+    /// pastes from before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Sanitized name of the uploaded file, used by `GET /:id/download`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_name: Option<String>,
+    /// Missing for pastes created before it was recorded, fall back to the file's mtime.
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub created: Option<SystemTime>,
+    /// Last time the content was replaced with `PUT /:id` or appended to,
+    /// unset if never.
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub modified: Option<SystemTime>,
+    /// Number of successful reads.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub views: u64,
+    /// Reads left before the paste removes itself, from the `max_views`
+    /// field. Unlimited if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub views_left: Option<u64>,
+    /// The files of a multi-file paste, in upload order. Empty for single pastes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<BundleFile>,
+}
+
+/// One file of a multi-file paste, stored under its index, see `storage`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleFile {
+    /// Unique within the paste, addresses the file as `/:id/:name`.
+    pub name: String,
+    /// Content bytes, uncompressed.
+    pub size: u64,
+    /// Hex SHA-256 of the content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub binary: bool,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+impl PasteMeta {
+    pub fn is_expired(&self) -> bool {
+        self.expires
+            .is_some_and(|expires| expires <= SystemTime::now())
+    }
+
+    pub fn is_protected(&self) -> bool {
+        self.password_hash.is_some()
+    }
+
+    /// Checks `password` against the stored hash. Unprotected pastes accept anything.
+    /// Argon2 is deliberately slow, call this from a blocking task.
+    pub fn verify_password(&self, password: Option<&str>) -> bool {
+        let Some(hash) = &self.password_hash else {
+            return true;
+        };
+        let (Some(password), Ok(hash)) = (password, PasswordHash::new(hash)) else {
+            return false;
+        };
+        // The PHC verifier compares the derived hashes in constant time
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    }
+}
+
+/// Hashes a paste password for storage. Call this from a blocking task.
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Parses a paste lifetime such as `30m`, `1h`, `7d` or `1h 30m`.
+/// A bare number is taken as seconds. Zero durations are rejected.
+pub fn parse_ttl(input: &str) -> Result<Duration, String> {
+    let input = input.trim().to_ascii_lowercase();
+    let ttl = match input.parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(_) => humantime::parse_duration(&input).map_err(|e| e.to_string())?,
+    };
+    if ttl.is_zero() {
+        return Err("duration must be greater than zero".to_string());
+    }
+    Ok(ttl)
+}
@@ -0,0 +1,126 @@
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// Upper bounds (in bytes) of the paste size histogram buckets
+const SIZE_BUCKETS: [u64; 6] = [
+    1024,
+    10 * 1024,
+    100 * 1024,
+    1024 * 1024,
+    5 * 1024 * 1024,
+    10 * 1024 * 1024,
+];
+
+// --- Metrics ---
+// Plain atomics rendered in the Prometheus text exposition format by `GET /metrics`.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pastes_created: AtomicU64,
+    retrievals: AtomicU64,
+    not_found: AtomicU64,
+    pastes_stored: AtomicU64,
+    // Non-cumulative counts per bucket, the last slot is `+Inf`
+    size_buckets: [AtomicU64; SIZE_BUCKETS.len() + 1],
+    size_sum: AtomicU64,
+}
+
+impl Metrics {
+    /// Starts tracking with `stored` pastes already present in the paste directory.
+    pub fn new(stored: u64) -> Self {
+        let metrics = Self::default();
+        metrics.pastes_stored.store(stored, Ordering::Relaxed);
+        metrics
+    }
+
+    pub fn paste_created(&self, size: u64) {
+        self.pastes_created.fetch_add(1, Ordering::Relaxed);
+        self.pastes_stored.fetch_add(1, Ordering::Relaxed);
+        let bucket = SIZE_BUCKETS
+            .iter()
+            .position(|&bound| size <= bound)
+            .unwrap_or(SIZE_BUCKETS.len());
+        self.size_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.size_sum.fetch_add(size, Ordering::Relaxed);
+    }
+
+    pub fn paste_removed(&self) {
+        // Saturate at zero in case the directory was modified behind our back
+        let _ = self
+            .pastes_stored
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+
+    pub fn paste_retrieved(&self) {
+        self.retrievals.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn paste_not_found(&self) {
+        self.not_found.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let counters = [
+            (
+                "rbin_pastes_created_total",
+                "counter",
+                "Total number of pastes created.",
+                &self.pastes_created,
+            ),
+            (
+                "rbin_paste_retrievals_total",
+                "counter",
+                "Total number of successful paste retrievals.",
+                &self.retrievals,
+            ),
+            (
+                "rbin_paste_not_found_total",
+                "counter",
+                "Total number of paste lookups that found nothing.",
+                &self.not_found,
+            ),
+            (
+                "rbin_pastes",
+                "gauge",
+                "Number of pastes currently stored.",
+                &self.pastes_stored,
+            ),
+        ];
+        for (name, kind, help, value) in counters {
+            render_metric(&mut out, name, kind, help, value.load(Ordering::Relaxed));
+        }
+
+        let name = "rbin_paste_size_bytes";
+        let _ = writeln!(out, "# HELP {} Size of created pastes in bytes.", name);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (i, bucket) in self.size_buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let le = SIZE_BUCKETS
+                .get(i)
+                .map_or_else(|| "+Inf".to_string(), u64::to_string);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+        }
+        let _ = writeln!(
+            out,
+            "{}_sum {}",
+            name,
+            self.size_sum.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(out, "{}_count {}", name, cumulative);
+        out
+    }
+}
+
+/// Appends a gauge tracked outside of `Metrics` to rendered metrics.
+pub fn render_gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    render_metric(out, name, "gauge", help, value);
+}
+
+fn render_metric(out: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
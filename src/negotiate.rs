@@ -0,0 +1,78 @@
+use axum::http::{header, HeaderMap};
+
+/// Picks the entry of `offers` the client prefers according to its `Accept`
+/// header. The first offer is the default, used when there is no `Accept`
+/// header, on ties (so a bare `*/*` keeps the default) and when nothing matches.
+pub fn preferred<'a>(headers: &HeaderMap, offers: &[&'a str]) -> &'a str {
+    let default = offers[0];
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|h| h.to_str().ok()) else {
+        return default;
+    };
+
+    let mut best = default;
+    let mut best_score = score(accept, default);
+    for offer in &offers[1..] {
+        // Only a strictly better match may replace an earlier offer
+        let offer_score = score(accept, offer);
+        if offer_score > best_score {
+            best = offer;
+            best_score = offer_score;
+        }
+    }
+    best
+}
+
+/// Returns the quality and specificity (2 = exact, 1 = `type/*`, 0 = `*/*`)
+/// of the most specific `Accept` entry matching `offer`.
+fn score(accept: &str, offer: &str) -> (f32, u8) {
+    let (offer_type, _) = offer.split_once('/').unwrap_or((offer, ""));
+    let mut best: Option<(f32, u8)> = None;
+    for entry in accept.split(',') {
+        let mut parts = entry.split(';');
+        let range = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        let quality = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        let specificity = if range == offer {
+            2
+        } else if range == format!("{}/*", offer_type) {
+            1
+        } else if range == "*/*" {
+            0
+        } else {
+            continue;
+        };
+        if best.is_none_or(|(_, s)| specificity > s) {
+            best = Some((quality, specificity));
+        }
+    }
+    best.unwrap_or((0.0, 0))
+}
+
+/// Whether the client's `Accept-Encoding` header allows `encoding` (e.g. `gzip`).
+pub fn accepts_encoding(headers: &HeaderMap, encoding: &str) -> bool {
+    let Some(accept) = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|h| h.to_str().ok())
+    else {
+        return false;
+    };
+    let mut accepted = false;
+    for entry in accept.split(',') {
+        let mut parts = entry.split(';');
+        let name = parts.next().unwrap_or("").trim();
+        let quality = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(encoding) {
+            // An explicit entry always wins over a wildcard
+            return quality > 0.0;
+        }
+        if name == "*" {
+            accepted = quality > 0.0;
+        }
+    }
+    accepted
+}
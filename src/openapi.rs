@@ -0,0 +1,52 @@
+use axum::Json;
+use utoipa::{OpenApi, ToSchema};
+
+// --- OpenAPI ---
+// The spec served at `GET /openapi.json`, built from the `utoipa::path`
+// annotations of the handlers so it can't drift from them. Only compiled with
+// the `openapi` feature (on by default).
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "rbin",
+        license(name = "MIT"),
+        description = "A minimal pastebin. Errors are plain text, or JSON with `Accept: application/json`."
+    ),
+    paths(
+        crate::handle_paste_submission,
+        crate::handle_custom_paste,
+        crate::retrieve_paste,
+        crate::retrieve_raw_paste,
+        crate::paste_info,
+        crate::batch_pastes,
+        crate::append_paste,
+        crate::delete_paste,
+    ),
+    components(schemas(PasteForm))
+)]
+struct ApiDoc;
+
+/// The fields of a multipart or urlencoded upload, see `FORM_FIELDS`.
+#[allow(dead_code)]
+#[derive(ToSchema)]
+pub struct PasteForm {
+    /// The content, repeated for a multi-file paste. The field is named by
+    /// RBIN_FIELD_NAME, the first file of another field is taken without it
+    #[schema(format = Binary)]
+    rbin: Vec<u8>,
+    /// Lifetime such as `1h` or `7d`
+    expires: Option<String>,
+    /// Required to read the paste
+    password: Option<String>,
+    /// Removes the paste after this many reads
+    #[schema(minimum = 1)]
+    max_views: Option<u64>,
+    /// Served type, instead of the detected one
+    content_type: Option<String>,
+}
+
+// --- Handler for GET /openapi.json ---
+pub async fn handle_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
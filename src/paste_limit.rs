@@ -0,0 +1,70 @@
+use std::{collections::VecDeque, sync::Mutex, time::SystemTime};
+
+// --- Paste Limit ---
+// Caps the number of pastes at RBIN_MAX_PASTES. Pastes are kept in order of
+// creation and the oldest ones are evicted to make room for new ones. Pastes
+// with `max_views` count towards the limit but are never evicted, they are
+// meant to be read before they go.
+#[derive(Debug)]
+pub struct PasteLimit {
+    max: u64,
+    // Oldest first
+    pastes: Mutex<VecDeque<Entry>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    id: String,
+    evictable: bool,
+}
+
+impl PasteLimit {
+    /// Starts with the `existing` pastes as `(created, id, evictable)`, in any order.
+    pub fn new(max: u64, mut existing: Vec<(SystemTime, String, bool)>) -> Self {
+        existing.sort();
+        let pastes = existing
+            .into_iter()
+            .map(|(_, id, evictable)| Entry { id, evictable })
+            .collect();
+        Self {
+            max,
+            pastes: Mutex::new(pastes),
+        }
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Takes a place for the new paste `id` and returns the pastes to evict for
+    /// it, or `None` if only pastes that can't be evicted are left.
+    pub fn admit(&self, id: &str, evictable: bool) -> Option<Vec<String>> {
+        let mut pastes = self.lock();
+        let excess = (pastes.len() as u64 + 1).saturating_sub(self.max) as usize;
+        if pastes.iter().filter(|entry| entry.evictable).count() < excess {
+            return None;
+        }
+        let mut evicted = Vec::with_capacity(excess);
+        while evicted.len() < excess {
+            let oldest = pastes.iter().position(|entry| entry.evictable)?;
+            evicted.extend(pastes.remove(oldest).map(|entry| entry.id));
+        }
+        pastes.push_back(Entry {
+            id: id.to_string(),
+            evictable,
+        });
+        Some(evicted)
+    }
+
+    /// Drops a removed paste.
+    pub fn forget(&self, id: &str) {
+        let mut pastes = self.lock();
+        if let Some(index) = pastes.iter().position(|entry| entry.id == id) {
+            pastes.remove(index);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Entry>> {
+        self.pastes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::OwnedMutexGuard;
+
+// --- Paste Locks ---
+// Serialize the read-modify-write updates of a paste's metadata, like counting
+// a view or appending, and its removal. One lock per paste ID, so pastes don't
+// wait for each other. Locks are only kept while held or waited for.
+#[derive(Debug, Default)]
+pub struct PasteLocks {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl PasteLocks {
+    /// Waits until no one else holds the lock of paste `id`.
+    pub async fn lock(&self, id: &str) -> PasteGuard<'_> {
+        let lock = self.entries().entry(id.to_string()).or_default().clone();
+        PasteGuard {
+            locks: self,
+            id: id.to_string(),
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<tokio::sync::Mutex<()>>>> {
+        self.locks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The lock of a paste, released when dropped.
+pub struct PasteGuard<'a> {
+    locks: &'a PasteLocks,
+    id: String,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for PasteGuard<'_> {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+        let mut locks = self.locks.entries();
+        // Only the map refers to it, no one else is waiting
+        if locks
+            .get(&self.id)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.id);
+        }
+    }
+}
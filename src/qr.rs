@@ -0,0 +1,26 @@
+use image::{ImageFormat, Luma};
+use qrcode::QrCode;
+use std::io::Cursor;
+
+// --- QR Codes ---
+// `GET /:id/qr` encodes a paste's public URL for sharing to phones.
+
+/// Pixels per QR module unless `?size=` says otherwise.
+pub const DEFAULT_MODULE_SIZE: u32 = 8;
+/// Keeps the images reasonably small, a URL needs about 40 modules with margin.
+pub const MAX_MODULE_SIZE: u32 = 32;
+
+/// Renders `url` as a black on white PNG QR code with `module_size` pixels
+/// per module.
+pub fn render_png(url: &str, module_size: u32) -> Result<Vec<u8>, String> {
+    let code = QrCode::new(url.as_bytes()).map_err(|e| e.to_string())?;
+    let image = code
+        .render::<Luma<u8>>()
+        .module_dimensions(module_size, module_size)
+        .build();
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(png)
+}
@@ -0,0 +1,104 @@
+use std::sync::{Arc, Mutex};
+
+// --- Disk Quota ---
+// Tracks the bytes taken up by paste content against RBIN_MAX_TOTAL_BYTES.
+// Uploads claim space chunk by chunk as they stream in (see `Reservation`), so
+// concurrent uploads can't overshoot the quota together. Files added or removed
+// behind rbin's back make the count drift, `recount` corrects it.
+#[derive(Debug)]
+pub struct DiskQuota {
+    limit: u64,
+    usage: Mutex<Usage>,
+}
+
+#[derive(Debug)]
+struct Usage {
+    // Content of committed pastes, on disk
+    stored: u64,
+    // Claimed by uploads still in progress
+    pending: u64,
+}
+
+impl DiskQuota {
+    /// Starts tracking with `stored` bytes already present in the paste directory.
+    pub fn new(limit: u64, stored: u64) -> Self {
+        Self {
+            limit,
+            usage: Mutex::new(Usage { stored, pending: 0 }),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Bytes stored or claimed by uploads in progress.
+    pub fn used(&self) -> u64 {
+        let usage = self.lock();
+        usage.stored + usage.pending
+    }
+
+    /// Frees the space of a removed paste.
+    pub fn release(&self, bytes: u64) {
+        let mut usage = self.lock();
+        // Saturate at zero in case the directory was modified behind our back
+        usage.stored = usage.stored.saturating_sub(bytes);
+    }
+
+    /// Replaces the stored byte count with a fresh scan of the paste directory.
+    pub fn recount(&self, stored: u64) {
+        self.lock().stored = stored;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Usage> {
+        self.usage.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Space claimed by one upload. Dropping it before `commit` gives the space back.
+#[derive(Debug)]
+pub struct Reservation {
+    quota: Option<Arc<DiskQuota>>,
+    bytes: u64,
+}
+
+impl Reservation {
+    /// A reservation against `quota`; without a quota every claim succeeds.
+    pub fn new(quota: Option<Arc<DiskQuota>>) -> Self {
+        Self { quota, bytes: 0 }
+    }
+
+    /// Claims `bytes` more, returning `false` if that would exceed the quota.
+    pub fn grow(&mut self, bytes: u64) -> bool {
+        let Some(quota) = &self.quota else {
+            return true;
+        };
+        let mut usage = quota.lock();
+        if usage.stored + usage.pending + bytes > quota.limit {
+            return false;
+        }
+        usage.pending += bytes;
+        self.bytes += bytes;
+        true
+    }
+
+    /// Turns the claim into stored content of `stored` bytes, which may be less
+    /// than claimed for compressed pastes.
+    pub fn commit(mut self, stored: u64) {
+        if let Some(quota) = &self.quota {
+            let mut usage = quota.lock();
+            usage.pending = usage.pending.saturating_sub(self.bytes);
+            usage.stored += stored;
+        }
+        self.bytes = 0;
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let (Some(quota), true) = (&self.quota, self.bytes > 0) {
+            let mut usage = quota.lock();
+            usage.pending = usage.pending.saturating_sub(self.bytes);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// Drop idle clients once this many are tracked, to bound memory use
+const PRUNE_THRESHOLD: usize = 10_000;
+
+// --- Rate Limiting ---
+// One token bucket per client IP: `count` requests are allowed in a burst and
+// tokens refill continuously so that `count` more are available per `window`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    count: u32,
+    window: Duration,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Parses a limit such as `10/min`, `100/hour` or `5/30s`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (count, window) = spec
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| "expected <count>/<window>, e.g. 10/min".to_string())?;
+        let count: u32 = count
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid count '{}': {}", count.trim(), e))?;
+        if count == 0 {
+            return Err("count must be greater than zero".to_string());
+        }
+        let window = match window.trim().to_ascii_lowercase().as_str() {
+            "s" | "sec" | "second" => Duration::from_secs(1),
+            "m" | "min" | "minute" => Duration::from_secs(60),
+            "h" | "hour" => Duration::from_secs(60 * 60),
+            "d" | "day" => Duration::from_secs(24 * 60 * 60),
+            other => humantime::parse_duration(other)
+                .map_err(|e| format!("invalid window '{}': {}", other, e))?,
+        };
+        if window.is_zero() {
+            return Err("window must be greater than zero".to_string());
+        }
+        Ok(Self {
+            count,
+            window,
+            buckets: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Takes a token for `ip`, or returns how long to wait until one is available.
+    pub fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let capacity = f64::from(self.count);
+        let refill_per_sec = capacity / self.window.as_secs_f64();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens + elapsed * refill_per_sec < capacity
+            });
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - bucket.tokens) / refill_per_sec;
+            Err(Duration::from_secs_f64(wait))
+        }
+    }
+}
+
+impl std::fmt::Display for RateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} requests per {}",
+            self.count,
+            humantime::format_duration(self.window)
+        )
+    }
+}
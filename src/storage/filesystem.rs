@@ -0,0 +1,256 @@
+use super::{content_id, suffix, Content, PasteStore, Staged, Stored, TempFile, FORMS};
+use crate::meta::PasteMeta;
+use async_trait::async_trait;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+// --- Filesystem Store ---
+// The default store, a directory (RBIN_PASTE_DIR). Each paste is `{id}.txt`,
+// with the suffix of its form from `FORMS`, e.g. `{id}.txt.gz.enc`, plus its
+// `{id}.meta` JSON sidecar. Binary pastes use the same names, the sidecar
+// tells them apart. Multi-file pastes are a directory `{id}/` instead,
+// holding the files like a paste directory of its own as `0.txt`,
+// `1.txt.gz`, ... Uploads and other temporary files are dot-prefixed.
+
+pub struct FsStore {
+    paste_dir: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(paste_dir: PathBuf) -> Self {
+        Self { paste_dir }
+    }
+
+    fn content_path(&self, key: &str, compressed: bool, encrypted: bool) -> PathBuf {
+        self.paste_dir
+            .join(format!("{}{}", key, suffix(compressed, encrypted)))
+    }
+
+    fn content_paths<'a>(&'a self, key: &'a str) -> impl Iterator<Item = PathBuf> + 'a {
+        FORMS
+            .iter()
+            .map(move |(suffix, ..)| self.paste_dir.join(format!("{}{}", key, suffix)))
+    }
+
+    fn bundle_dir(&self, id: &str) -> PathBuf {
+        self.paste_dir.join(id)
+    }
+
+    fn meta_path(&self, id: &str) -> PathBuf {
+        self.paste_dir.join(format!("{}.meta", id))
+    }
+
+    /// Removes `key` in every form but the one at `keep`, returning the bytes
+    /// freed.
+    async fn remove_content(&self, key: &str, keep: Option<&Path>) -> u64 {
+        let mut freed = 0;
+        for path in self.content_paths(key) {
+            if Some(path.as_path()) == keep {
+                continue;
+            }
+            let len = fs::metadata(&path)
+                .await
+                .map_or(0, |metadata| metadata.len());
+            if fs::remove_file(&path).await.is_ok() {
+                freed += len;
+            }
+        }
+        freed
+    }
+}
+
+#[async_trait]
+impl PasteStore for FsStore {
+    fn staging_dir(&self) -> Option<&Path> {
+        // Next to the pastes, so they are moved into place with a rename
+        Some(&self.paste_dir)
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Stored> {
+        for (suffix, compressed, encrypted) in FORMS {
+            let path = self.paste_dir.join(format!("{}{}", key, suffix));
+            let file = match fs::File::open(path).await {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            let metadata = file.metadata().await?;
+            return Ok(Stored {
+                content: Content::File(file),
+                len: metadata.len(),
+                compressed,
+                encrypted,
+                modified: metadata.modified().ok(),
+            });
+        }
+        Err(io::ErrorKind::NotFound.into())
+    }
+
+    async fn put(&self, key: &str, content: Staged) -> io::Result<u64> {
+        let target = self.content_path(key, content.compressed, content.encrypted);
+        let mut freed = fs::metadata(&target)
+            .await
+            .map_or(0, |metadata| metadata.len());
+        content.persist(&target).await?;
+        freed += self.remove_content(key, Some(&target)).await;
+        Ok(freed)
+    }
+
+    async fn append(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(self.content_path(key, false, false))
+            .await?;
+        file.write_all(data).await?;
+        file.sync_all().await
+    }
+
+    async fn put_bundle(&self, id: &str, files: Vec<Staged>) -> io::Result<()> {
+        // Assembled next to the pastes so the files appear all at once
+        let mut dir = TempFile::new(&self.paste_dir, "bundle");
+        fs::create_dir(&dir.path).await?;
+        for (index, file) in files.into_iter().enumerate() {
+            let name = format!("{}{}", index, suffix(file.compressed, file.encrypted));
+            file.persist(&dir.path.join(name)).await?;
+        }
+        fs::rename(&dir.path, self.bundle_dir(id)).await?;
+        dir.persisted = true;
+        Ok(())
+    }
+
+    async fn exists(&self, id: &str) -> io::Result<bool> {
+        for path in self.content_paths(id) {
+            if fs::try_exists(path).await? {
+                return Ok(true);
+            }
+        }
+        fs::try_exists(self.bundle_dir(id)).await
+    }
+
+    async fn is_bundle(&self, id: &str) -> bool {
+        fs::metadata(self.bundle_dir(id))
+            .await
+            .is_ok_and(|metadata| metadata.is_dir())
+    }
+
+    async fn bundle_size(&self, id: &str) -> io::Result<u64> {
+        dir_size(&self.bundle_dir(id)).await
+    }
+
+    async fn delete(&self, id: &str) -> Option<u64> {
+        let existed = self.exists(id).await.unwrap_or(false);
+        let mut freed = self.remove_content(id, None).await;
+        let dir = self.bundle_dir(id);
+        if let Ok(size) = dir_size(&dir).await {
+            if fs::remove_dir_all(&dir).await.is_ok() {
+                freed += size;
+            }
+        }
+        let _ = fs::remove_file(self.meta_path(id)).await;
+        existed.then_some(freed)
+    }
+
+    async fn reserve(&self, id: &str) -> io::Result<()> {
+        // The empty sidecar, created with `create_new`, is the claim
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.meta_path(id))
+            .await?;
+        // Pastes from before metadata sidecars existed have no `.meta` to collide with
+        if self.exists(id).await? {
+            let _ = fs::remove_file(self.meta_path(id)).await;
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        Ok(())
+    }
+
+    async fn read_meta(&self, id: &str) -> io::Result<Option<PasteMeta>> {
+        match fs::read(self.meta_path(id)).await {
+            Ok(data) => serde_json::from_slice(&data)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn write_meta(&self, id: &str, meta: &PasteMeta) -> io::Result<()> {
+        let data =
+            serde_json::to_vec(meta).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        write_atomic(&self.meta_path(id), &data).await
+    }
+
+    async fn list(&self) -> io::Result<Vec<String>> {
+        let mut ids = Vec::new();
+        let mut entries = fs::read_dir(&self.paste_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let bundle = is_bundle_entry(&entry, &name).await;
+            if let Some(id) = content_id(&name).or(bundle.then_some(&name)) {
+                ids.push(id.to_string());
+            }
+        }
+        Ok(ids)
+    }
+
+    async fn usage(&self) -> io::Result<u64> {
+        let mut used = 0;
+        let mut entries = fs::read_dir(&self.paste_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if content_id(&name).is_some() {
+                used += entry.metadata().await?.len();
+            } else if is_bundle_entry(&entry, &name).await {
+                used += dir_size(&entry.path()).await?;
+            }
+        }
+        Ok(used)
+    }
+
+    async fn check_writable(&self) -> io::Result<()> {
+        let probe = TempFile::new(&self.paste_dir, "ready");
+        fs::write(&probe.path, b"ok").await
+    }
+}
+
+// Directories in the paste directory are multi-file pastes, except for the
+// dot-prefixed ones still being assembled
+async fn is_bundle_entry(entry: &fs::DirEntry, name: &str) -> bool {
+    !name.starts_with('.') && entry.file_type().await.is_ok_and(|t| t.is_dir())
+}
+
+/// Total size of the files in a multi-file paste's directory.
+async fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        size += entry.metadata().await?.len();
+    }
+    Ok(size)
+}
+
+/// Writes `data` to a `.tmp` file next to `path` and renames it into place, so
+/// readers see either the old file or the complete new one, never a partial write.
+async fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let result = async {
+        let mut file = fs::File::create(&tmp_path).await?;
+        file.write_all(data).await?;
+        file.sync_all().await?;
+        fs::rename(&tmp_path, path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path).await;
+    }
+    result
+}
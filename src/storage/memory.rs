@@ -0,0 +1,208 @@
+use super::{Content, PasteStore, Staged, Stored};
+use crate::meta::PasteMeta;
+use async_trait::async_trait;
+use axum::body::Bytes;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Cursor},
+    path::Path,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+// --- In-Memory Store ---
+// Keeps everything in memory (RBIN_STORAGE=memory), so pastes are gone with a
+// restart. For tests and throwaway instances.
+
+#[derive(Default)]
+pub struct InMemoryStore {
+    // Never held across an await
+    pastes: Mutex<Pastes>,
+}
+
+#[derive(Default)]
+struct Pastes {
+    /// By key, i.e. including the files of multi-file pastes
+    content: HashMap<String, Object>,
+    bundles: HashSet<String>,
+    /// `None` for reserved IDs whose metadata isn't written yet
+    meta: HashMap<String, Option<PasteMeta>>,
+}
+
+struct Object {
+    data: Bytes,
+    compressed: bool,
+    encrypted: bool,
+    modified: SystemTime,
+}
+
+impl Pastes {
+    fn exists(&self, id: &str) -> bool {
+        self.content.contains_key(id) || self.bundles.contains(id)
+    }
+
+    fn bundle_files<'a>(&'a self, id: &str) -> impl Iterator<Item = &'a Object> + 'a {
+        let prefix = format!("{}/", id);
+        self.content
+            .iter()
+            .filter(move |(key, _)| key.starts_with(&prefix))
+            .map(|(_, object)| object)
+    }
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Object {
+    async fn new(content: Staged) -> io::Result<Self> {
+        let (compressed, encrypted) = (content.compressed, content.encrypted);
+        Ok(Self {
+            data: content.into_bytes().await?,
+            compressed,
+            encrypted,
+            modified: SystemTime::now(),
+        })
+    }
+}
+
+#[async_trait]
+impl PasteStore for InMemoryStore {
+    fn staging_dir(&self) -> Option<&Path> {
+        None
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Stored> {
+        let pastes = self.pastes.lock().unwrap();
+        let object = pastes.content.get(key).ok_or(io::ErrorKind::NotFound)?;
+        Ok(Stored {
+            content: Content::Memory(Cursor::new(object.data.clone())),
+            len: object.data.len() as u64,
+            compressed: object.compressed,
+            encrypted: object.encrypted,
+            modified: Some(object.modified),
+        })
+    }
+
+    async fn put(&self, key: &str, content: Staged) -> io::Result<u64> {
+        let object = Object::new(content).await?;
+        let old = self
+            .pastes
+            .lock()
+            .unwrap()
+            .content
+            .insert(key.to_string(), object);
+        Ok(old.map_or(0, |old| old.data.len() as u64))
+    }
+
+    async fn append(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let mut pastes = self.pastes.lock().unwrap();
+        let object = pastes.content.get_mut(key).ok_or(io::ErrorKind::NotFound)?;
+        if object.compressed || object.encrypted {
+            return Err(io::Error::other("only plain content can be appended to"));
+        }
+        object.data = [object.data.as_ref(), data].concat().into();
+        object.modified = SystemTime::now();
+        Ok(())
+    }
+
+    async fn put_bundle(&self, id: &str, files: Vec<Staged>) -> io::Result<()> {
+        let mut objects = Vec::with_capacity(files.len());
+        for file in files {
+            objects.push(Object::new(file).await?);
+        }
+        let mut pastes = self.pastes.lock().unwrap();
+        for (index, object) in objects.into_iter().enumerate() {
+            pastes.content.insert(super::bundle_key(id, index), object);
+        }
+        pastes.bundles.insert(id.to_string());
+        Ok(())
+    }
+
+    async fn exists(&self, id: &str) -> io::Result<bool> {
+        Ok(self.pastes.lock().unwrap().exists(id))
+    }
+
+    async fn is_bundle(&self, id: &str) -> bool {
+        self.pastes.lock().unwrap().bundles.contains(id)
+    }
+
+    async fn bundle_size(&self, id: &str) -> io::Result<u64> {
+        let pastes = self.pastes.lock().unwrap();
+        if !pastes.bundles.contains(id) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(pastes
+            .bundle_files(id)
+            .map(|object| object.data.len() as u64)
+            .sum())
+    }
+
+    async fn delete(&self, id: &str) -> Option<u64> {
+        let mut pastes = self.pastes.lock().unwrap();
+        let existed = pastes.exists(id);
+        let prefix = format!("{}/", id);
+        let mut freed = 0;
+        pastes.content.retain(|key, object| {
+            let remove = key == id || key.starts_with(&prefix);
+            if remove {
+                freed += object.data.len() as u64;
+            }
+            !remove
+        });
+        pastes.bundles.remove(id);
+        pastes.meta.remove(id);
+        existed.then_some(freed)
+    }
+
+    async fn reserve(&self, id: &str) -> io::Result<()> {
+        let mut pastes = self.pastes.lock().unwrap();
+        if pastes.meta.contains_key(id) || pastes.exists(id) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        pastes.meta.insert(id.to_string(), None);
+        Ok(())
+    }
+
+    async fn read_meta(&self, id: &str) -> io::Result<Option<PasteMeta>> {
+        match self.pastes.lock().unwrap().meta.get(id) {
+            Some(Some(meta)) => Ok(Some(meta.clone())),
+            // Like the empty sidecar of the filesystem store
+            Some(None) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the metadata isn't written yet",
+            )),
+            None => Ok(None),
+        }
+    }
+
+    async fn write_meta(&self, id: &str, meta: &PasteMeta) -> io::Result<()> {
+        self.pastes
+            .lock()
+            .unwrap()
+            .meta
+            .insert(id.to_string(), Some(meta.clone()));
+        Ok(())
+    }
+
+    async fn list(&self) -> io::Result<Vec<String>> {
+        let pastes = self.pastes.lock().unwrap();
+        let content = pastes.content.keys().filter(|key| !key.contains('/'));
+        Ok(content.chain(&pastes.bundles).cloned().collect())
+    }
+
+    async fn usage(&self) -> io::Result<u64> {
+        let pastes = self.pastes.lock().unwrap();
+        Ok(pastes
+            .content
+            .values()
+            .map(|object| object.data.len() as u64)
+            .sum())
+    }
+
+    async fn check_writable(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,738 @@
+use crate::{
+    encryption::{Cipher, EncryptWriter},
+    meta::PasteMeta,
+};
+use async_compression::tokio::{bufread::GzipDecoder, write::GzipEncoder};
+use async_trait::async_trait;
+use axum::body::Bytes;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use rand::distributions::{Alphanumeric, DistString};
+use sha2::{Digest, Sha256};
+use std::{
+    io::{self, Cursor, Read, SeekFrom, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    fs,
+    io::{
+        AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufReader,
+        ReadBuf,
+    },
+};
+
+mod filesystem;
+mod memory;
+mod s3;
+mod sqlite;
+
+pub use filesystem::FsStore;
+pub use memory::InMemoryStore;
+pub use s3::{S3Config, S3Store};
+pub use sqlite::SqliteStore;
+
+// Bytes of an upload kept in memory, for content type sniffing and short links
+pub const HEAD_LEN: usize = 2048;
+
+// --- Paste Storage ---
+// Pastes live in a `PasteStore`, picked with RBIN_STORAGE. Stores keep the
+// content of each paste under its ID, and the files of a multi-file paste
+// under `{id}/0`, `{id}/1`, ... Content is kept as uploaded or gzip
+// compressed (RBIN_COMPRESS), and possibly encrypted (RBIN_ENCRYPTION_KEY);
+// the store remembers which, so pastes keep working when the settings are
+// toggled. Everything else, like the metadata, is up to the store.
+
+// Every form of paste content, as the file name suffix used by stores that
+// need one and whether it is compressed and encrypted
+const FORMS: [(&str, bool, bool); 4] = [
+    (".txt", false, false),
+    (".txt.gz", true, false),
+    (".txt.enc", false, true),
+    (".txt.gz.enc", true, true),
+];
+
+fn suffix(compressed: bool, encrypted: bool) -> &'static str {
+    let (suffix, ..) = FORMS
+        .iter()
+        .find(|(_, c, e)| (*c, *e) == (compressed, encrypted))
+        .expect("all forms are listed");
+    suffix
+}
+
+/// The paste ID of a content file name, if it is one.
+fn content_id(name: &str) -> Option<&str> {
+    FORMS
+        .iter()
+        .find_map(|(suffix, ..)| name.strip_suffix(suffix))
+        .filter(|id| !id.starts_with('.'))
+}
+
+/// Key of file `index` of multi-file paste `id`, see `open`.
+pub fn bundle_key(id: &str, index: usize) -> String {
+    format!("{}/{}", id, index)
+}
+
+/// Where and how pastes are stored. Content is addressed by key, a paste ID or
+/// a `bundle_key`; other methods take paste IDs. Read content with `open` and
+/// write it with `Upload`, which handle compression and encryption.
+#[async_trait]
+pub trait PasteStore: Send + Sync {
+    /// Directory uploads are written to before `put` takes them over, or `None`
+    /// to keep them in memory.
+    fn staging_dir(&self) -> Option<&Path>;
+
+    /// The content stored under `key`, `NotFound` if there is none.
+    async fn get(&self, key: &str) -> io::Result<Stored>;
+
+    /// Stores `content` under `key`, replacing what was stored there in any
+    /// form. Returns the bytes the old content took up.
+    async fn put(&self, key: &str, content: Staged) -> io::Result<u64>;
+
+    /// Adds `data` to the end of the content under `key`, which is stored as
+    /// is, i.e. neither compressed nor encrypted.
+    async fn append(&self, key: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Stores the files of multi-file paste `id` under their `bundle_key`, all
+    /// at once.
+    async fn put_bundle(&self, id: &str, files: Vec<Staged>) -> io::Result<()>;
+
+    /// Whether paste `id` exists, with content or as a multi-file paste.
+    async fn exists(&self, id: &str) -> io::Result<bool>;
+
+    /// Whether `id` is a multi-file paste.
+    async fn is_bundle(&self, id: &str) -> bool;
+
+    /// Total size of the files of multi-file paste `id` as stored.
+    async fn bundle_size(&self, id: &str) -> io::Result<u64>;
+
+    /// Removes paste `id` in all its forms and its metadata, ignoring what is
+    /// already gone. Returns the number of content bytes freed, `None` if it
+    /// had no content to remove.
+    async fn delete(&self, id: &str) -> Option<u64>;
+
+    /// Claims `id` for a new paste, failing with `AlreadyExists` if it is
+    /// taken. Reading its metadata fails until `write_meta`.
+    async fn reserve(&self, id: &str) -> io::Result<()>;
+
+    /// The metadata of paste `id`, `None` if it has none, like pastes created
+    /// before metadata existed.
+    async fn read_meta(&self, id: &str) -> io::Result<Option<PasteMeta>>;
+
+    async fn write_meta(&self, id: &str, meta: &PasteMeta) -> io::Result<()>;
+
+    /// The IDs of all pastes.
+    async fn list(&self) -> io::Result<Vec<String>>;
+
+    /// Size of all paste content as stored, leaving out metadata and uploads
+    /// in progress.
+    async fn usage(&self) -> io::Result<u64>;
+
+    /// Fails unless pastes can be written, for `GET /ready`.
+    async fn check_writable(&self) -> io::Result<()>;
+
+    /// Pastes whose text contains `query`, as IDs with the text around the
+    /// match, from the store's own full-text index. `None` for stores without
+    /// one, callers then scan the content.
+    async fn search(
+        &self,
+        _query: &str,
+        _limit: usize,
+    ) -> io::Result<Option<Vec<(String, String)>>> {
+        Ok(None)
+    }
+}
+
+/// Content as a store returns it, see `open` for reading it.
+pub struct Stored {
+    pub content: Content,
+    /// Size as stored, i.e. compressed and encrypted if set
+    pub len: u64,
+    pub compressed: bool,
+    pub encrypted: bool,
+    pub modified: Option<SystemTime>,
+}
+
+/// Stored content, readable and seekable either way.
+pub enum Content {
+    File(fs::File),
+    Memory(Cursor<Bytes>),
+}
+
+impl AsyncRead for Content {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Content::File(file) => Pin::new(file).poll_read(cx, buf),
+            Content::Memory(data) => Pin::new(data).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncSeek for Content {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        match self.get_mut() {
+            Content::File(file) => Pin::new(file).start_seek(position),
+            Content::Memory(data) => Pin::new(data).start_seek(position),
+        }
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        match self.get_mut() {
+            Content::File(file) => Pin::new(file).poll_complete(cx),
+            Content::Memory(data) => Pin::new(data).poll_complete(cx),
+        }
+    }
+}
+
+/// An opened paste file, see `open`.
+pub struct PasteFile {
+    pub file: Content,
+    /// Size of the content as stored, i.e. compressed if `compressed` is set.
+    pub len: u64,
+    pub compressed: bool,
+    pub modified: Option<SystemTime>,
+    // Set for encrypted files
+    cipher: Option<Cipher>,
+}
+
+impl PasteFile {
+    /// Identifies this version of the paste file (size and modification time),
+    /// for cache validation.
+    pub fn version(&self) -> String {
+        let modified = self
+            .modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_nanos());
+        format!("{:x}-{:x}", self.len, modified)
+    }
+
+    /// Encrypted files can only be read as a whole with `read_all`, `file`
+    /// is the ciphertext.
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Whether the (decompressed) content starts with `prefix`, leaving the
+    /// file at its start again. Not for encrypted files.
+    pub async fn starts_with(&mut self, prefix: &[u8]) -> io::Result<bool> {
+        Ok(self.head(prefix.len()).await? == prefix)
+    }
+
+    /// Up to `limit` bytes of the start of the (decompressed) content, without
+    /// reading the rest, leaving the file at its start again. Not for
+    /// encrypted files.
+    pub async fn head(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        if self.cipher.is_some() {
+            return Err(io::Error::other("encrypted files can only be read whole"));
+        }
+        let mut head = Vec::with_capacity(limit);
+        let reader = BufReader::new(&mut self.file);
+        if self.compressed {
+            GzipDecoder::new(reader)
+                .take(limit as u64)
+                .read_to_end(&mut head)
+                .await?;
+        } else {
+            reader.take(limit as u64).read_to_end(&mut head).await?;
+        }
+        self.file.seek(SeekFrom::Start(0)).await?;
+        Ok(head)
+    }
+
+    /// Reads the whole paste into memory, decrypting and decompressing it if
+    /// needed.
+    pub async fn read_all(mut self) -> io::Result<Bytes> {
+        let mut data = Vec::new();
+        self.file.read_to_end(&mut data).await?;
+        if !self.compressed && self.cipher.is_none() {
+            return Ok(data.into());
+        }
+        tokio::task::spawn_blocking(move || {
+            if let Some(cipher) = &self.cipher {
+                data = cipher.decrypt(&data)?;
+            }
+            if !self.compressed {
+                return Ok(data.into());
+            }
+            let mut content = Vec::new();
+            GzDecoder::new(data.as_slice()).read_to_end(&mut content)?;
+            Ok(content.into())
+        })
+        .await?
+    }
+}
+
+/// Opens the content under `key` in whichever form it is stored. Encrypted
+/// content needs the `cipher`.
+pub async fn open(
+    store: &dyn PasteStore,
+    key: &str,
+    cipher: Option<&Cipher>,
+) -> io::Result<PasteFile> {
+    let stored = store.get(key).await?;
+    let cipher = match (stored.encrypted, cipher) {
+        (false, _) => None,
+        (true, Some(cipher)) => Some(cipher.clone()),
+        (true, None) => {
+            return Err(io::Error::other(
+                "the paste is encrypted, but RBIN_ENCRYPTION_KEY is not set",
+            ))
+        }
+    };
+    Ok(PasteFile {
+        file: stored.content,
+        len: stored.len,
+        compressed: stored.compressed,
+        modified: stored.modified,
+        cipher,
+    })
+}
+
+/// Finished upload content waiting to be `put` into a store, in a temporary
+/// file or in memory as its store wants it. Dropping it deletes the file.
+pub struct Staged {
+    data: StagedData,
+    /// Size as stored
+    pub len: u64,
+    pub compressed: bool,
+    pub encrypted: bool,
+}
+
+enum StagedData {
+    File(TempFile),
+    Memory(Bytes),
+}
+
+impl Staged {
+    /// The content as stored, reading the temporary file if there is one.
+    pub async fn into_bytes(self) -> io::Result<Bytes> {
+        match self.data {
+            StagedData::File(file) => fs::read(&file.path).await.map(Bytes::from),
+            StagedData::Memory(data) => Ok(data),
+        }
+    }
+
+    /// Moves the content to `path`, renaming the temporary file if there is one.
+    /// That only works within a file system, so keep them next to each other.
+    async fn persist(self, path: &Path) -> io::Result<()> {
+        match self.data {
+            StagedData::File(mut file) => {
+                fs::rename(&file.path, path).await?;
+                file.persisted = true;
+                Ok(())
+            }
+            StagedData::Memory(data) => {
+                let mut file = fs::File::create(path).await?;
+                file.write_all(&data).await?;
+                file.sync_all().await
+            }
+        }
+    }
+}
+
+// A file, or the directory a multi-file paste is assembled in, deleted again
+// when dropped unless it has been moved into place
+struct TempFile {
+    path: PathBuf,
+    persisted: bool,
+}
+
+impl TempFile {
+    fn new(dir: &Path, kind: &str) -> Self {
+        // Dot-prefixed so it can never clash with a paste ID
+        let name = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+        Self {
+            path: dir.join(format!(".{}-{}.tmp", kind, name)),
+            persisted: false,
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted && std::fs::remove_file(&self.path).is_err() {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+// Where an upload's content goes, shared with the writer it is behind
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl AsyncWrite for Buffer {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+enum Target {
+    File(TempFile),
+    Memory(Buffer),
+}
+
+/// The lines of content written in chunks, see `Upload::lines`. Also used to
+/// measure appends together with the content they are added to.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LineCount {
+    // Newlines so far, and the characters since the last one
+    newlines: u64,
+    line_len: u64,
+    longest_line: u64,
+}
+
+impl LineCount {
+    pub fn write(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            if byte == b'\n' {
+                self.newlines += 1;
+                self.line_len = 0;
+            } else if byte & 0xC0 != 0x80 {
+                // Continuation bytes belong to the character before them
+                self.line_len += 1;
+                self.longest_line = self.longest_line.max(self.line_len);
+            }
+        }
+    }
+
+    /// Number of lines, counting a last line without newline.
+    pub fn lines(&self) -> u64 {
+        self.newlines + u64::from(self.line_len > 0)
+    }
+
+    /// Length in characters of the longest line, without its newline.
+    pub fn longest_line(&self) -> u64 {
+        self.longest_line
+    }
+}
+
+/// A paste being streamed into its store's staging area, gzip compressed and
+/// encrypted on the way if requested. `commit` puts it into the store under
+/// its key; dropping an uncommitted upload deletes what was written again.
+pub struct Upload {
+    target: Target,
+    // The target, possibly behind the encoder and encryption
+    writer: Box<dyn AsyncWrite + Send + Unpin>,
+    compressed: bool,
+    encrypted: bool,
+    size: u64,
+    // Start of the content, see `head`
+    head: Vec<u8>,
+    utf8: bool,
+    // Trailing bytes of an incomplete UTF-8 sequence, continued by the next chunk
+    utf8_tail: Vec<u8>,
+    lines: LineCount,
+    // Characters so far, and the span between leading and trailing whitespace
+    chars: u64,
+    content_start: Option<u64>,
+    content_end: u64,
+    hasher: Sha256,
+}
+
+impl Upload {
+    pub async fn create(
+        store: &dyn PasteStore,
+        compress: bool,
+        cipher: Option<&Cipher>,
+    ) -> io::Result<Self> {
+        let (target, sink): (_, Box<dyn AsyncWrite + Send + Unpin>) = match store.staging_dir() {
+            Some(dir) => {
+                let file = TempFile::new(dir, "upload");
+                let sink = fs::File::create(&file.path).await?;
+                (Target::File(file), Box::new(sink))
+            }
+            None => {
+                let buffer = Buffer::default();
+                (Target::Memory(buffer.clone()), Box::new(buffer))
+            }
+        };
+        // Compressed first, ciphertext doesn't compress
+        let writer: Box<dyn AsyncWrite + Send + Unpin> = match (compress, cipher) {
+            (false, None) => sink,
+            (true, None) => Box::new(GzipEncoder::new(sink)),
+            (false, Some(cipher)) => Box::new(EncryptWriter::new(sink, cipher)),
+            (true, Some(cipher)) => Box::new(GzipEncoder::new(EncryptWriter::new(sink, cipher))),
+        };
+        Ok(Self {
+            target,
+            writer,
+            compressed: compress,
+            encrypted: cipher.is_some(),
+            size: 0,
+            head: Vec::new(),
+            utf8: true,
+            utf8_tail: Vec::new(),
+            lines: LineCount::default(),
+            chars: 0,
+            content_start: None,
+            content_end: 0,
+            hasher: Sha256::new(),
+        })
+    }
+
+    /// Number of (uncompressed) content bytes written so far.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The first bytes of the content, all of it for uploads of up to
+    /// `HEAD_LEN` bytes.
+    pub fn head(&self) -> &[u8] {
+        &self.head
+    }
+
+    /// Whether the content written so far is text, i.e. valid UTF-8.
+    pub fn is_text(&self) -> bool {
+        self.utf8 && self.utf8_tail.is_empty()
+    }
+
+    /// The lines written so far.
+    pub fn lines(&self) -> LineCount {
+        self.lines
+    }
+
+    /// Length in characters of the content written so far, without leading
+    /// and trailing whitespace.
+    pub fn trimmed_len(&self) -> u64 {
+        self.content_start
+            .map_or(0, |start| self.content_end - start)
+    }
+
+    /// Hex SHA-256 of the content written so far.
+    pub fn sha256(&self) -> String {
+        format!("{:x}", self.hasher.clone().finalize())
+    }
+
+    pub async fn write(&mut self, chunk: &[u8]) -> io::Result<()> {
+        if self.head.len() < HEAD_LEN {
+            let missing = HEAD_LEN - self.head.len();
+            self.head
+                .extend_from_slice(&chunk[..missing.min(chunk.len())]);
+        }
+        if self.utf8 {
+            self.check_utf8(chunk);
+        }
+        self.count_chars(chunk);
+        self.lines.write(chunk);
+        self.hasher.update(chunk);
+        self.writer.write_all(chunk).await?;
+        self.size += chunk.len() as u64;
+        Ok(())
+    }
+
+    fn count_chars(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            if byte & 0xC0 != 0x80 {
+                self.chars += 1;
+            }
+            if !byte.is_ascii_whitespace() {
+                self.content_start
+                    .get_or_insert(self.chars.saturating_sub(1));
+                self.content_end = self.chars;
+            }
+        }
+    }
+
+    fn check_utf8(&mut self, chunk: &[u8]) {
+        let mut data = std::mem::take(&mut self.utf8_tail);
+        data.extend_from_slice(chunk);
+        if let Err(e) = std::str::from_utf8(&data) {
+            // A sequence cut off at the end of the chunk may still be completed
+            if e.error_len().is_some() {
+                self.utf8 = false;
+            } else {
+                self.utf8_tail = data[e.valid_up_to()..].to_vec();
+            }
+        }
+    }
+
+    /// Finishes the upload, ready to be put into the store.
+    pub async fn finish(mut self) -> io::Result<Staged> {
+        // Writes out the rest of the gzip stream and the last sealed chunk
+        self.writer.shutdown().await?;
+        drop(self.writer);
+        let (data, len) = match self.target {
+            Target::File(file) => {
+                let handle = fs::OpenOptions::new().write(true).open(&file.path).await?;
+                handle.sync_all().await?;
+                let len = handle.metadata().await?.len();
+                (StagedData::File(file), len)
+            }
+            Target::Memory(buffer) => {
+                let data = std::mem::take(&mut *buffer.0.lock().unwrap());
+                let len = data.len() as u64;
+                (StagedData::Memory(data.into()), len)
+            }
+        };
+        Ok(Staged {
+            data,
+            len,
+            compressed: self.compressed,
+            encrypted: self.encrypted,
+        })
+    }
+
+    /// Finishes the upload and stores it as paste `key`, returning its size
+    /// as stored.
+    pub async fn commit(self, store: &dyn PasteStore, key: &str) -> io::Result<u64> {
+        self.replace(store, key).await.map(|(stored, _)| stored)
+    }
+
+    /// Like `commit`, but for overwriting the content of an existing paste.
+    /// Returns the new size as stored and the bytes the old content took up.
+    pub async fn replace(self, store: &dyn PasteStore, key: &str) -> io::Result<(u64, u64)> {
+        let staged = self.finish().await?;
+        let stored = staged.len;
+        let freed = store.put(key, staged).await?;
+        Ok((stored, freed))
+    }
+}
+
+/// Adds `data` to the end of paste `id`, unless its content would then be
+/// longer than `max_len`, returning `None` then. Plain content is appended to
+/// in place, compressed and encrypted content is rewritten in the form it has.
+/// Returns the bytes stored and freed, like `Upload::replace`.
+pub async fn append(
+    store: &dyn PasteStore,
+    id: &str,
+    data: &[u8],
+    max_len: u64,
+    cipher: Option<&Cipher>,
+) -> io::Result<Option<(u64, u64)>> {
+    let file = open(store, id, cipher).await?;
+    if !file.compressed && file.cipher.is_none() {
+        if file.len + data.len() as u64 > max_len {
+            return Ok(None);
+        }
+        drop(file);
+        store.append(id, data).await?;
+        return Ok(Some((data.len() as u64, 0)));
+    }
+    let (compressed, cipher) = (file.compressed, file.cipher.clone());
+    let content = file.read_all().await?;
+    if (content.len() + data.len()) as u64 > max_len {
+        return Ok(None);
+    }
+    let mut upload = Upload::create(store, compressed, cipher.as_ref()).await?;
+    upload.write(&content).await?;
+    upload.write(data).await?;
+    upload.replace(store, id).await.map(Some)
+}
+
+/// Hex SHA-256 of `data`, like `Upload::sha256`.
+pub fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Gzip compresses `data`. CPU-bound, call this from a blocking task.
+pub fn gzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn store_text(store: &dyn PasteStore, key: &str, text: &str, compress: bool) -> u64 {
+        let mut upload = Upload::create(store, compress, None).await.unwrap();
+        upload.write(text.as_bytes()).await.unwrap();
+        upload.commit(store, key).await.unwrap()
+    }
+
+    async fn read_text(store: &dyn PasteStore, key: &str) -> io::Result<Bytes> {
+        open(store, key, None).await?.read_all().await
+    }
+
+    // What every store has to hold to, run against each of them below
+    async fn contract(store: &dyn PasteStore) {
+        assert!(!store.exists("abc").await.unwrap());
+        let missing = read_text(store, "abc").await.unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        assert_eq!(store.read_meta("abc").await.unwrap().map(|_| ()), None);
+
+        store.reserve("abc").await.unwrap();
+        let taken = store.reserve("abc").await.unwrap_err();
+        assert_eq!(taken.kind(), io::ErrorKind::AlreadyExists);
+        assert!(store.read_meta("abc").await.is_err());
+
+        let stored = store_text(store, "abc", "hello\n", false).await;
+        assert_eq!(stored, 6);
+        assert!(store.exists("abc").await.unwrap());
+        assert_eq!(read_text(store, "abc").await.unwrap(), "hello\n");
+        assert_eq!(store.list().await.unwrap(), ["abc"]);
+        assert_eq!(store.usage().await.unwrap(), 6);
+
+        let expires = UNIX_EPOCH + Duration::from_secs(4_000_000_000);
+        let meta = PasteMeta {
+            expires: Some(expires),
+            delete_token: Some("secret".into()),
+            content_type: Some("text/markdown".into()),
+            binary: true,
+            ..Default::default()
+        };
+        store.write_meta("abc", &meta).await.unwrap();
+        let read = store.read_meta("abc").await.unwrap().unwrap();
+        assert_eq!(read.expires, Some(expires));
+        assert_eq!(read.delete_token.as_deref(), Some("secret"));
+        assert_eq!(read.content_type.as_deref(), Some("text/markdown"));
+        assert!(read.binary);
+        assert_eq!(read.edit_token, None);
+
+        // Replacing the content in another form leaves no trace of the old one
+        let replaced = store_text(store, "abc", "bye\n", true).await;
+        assert_eq!(read_text(store, "abc").await.unwrap(), "bye\n");
+        assert_eq!(store.usage().await.unwrap(), replaced);
+
+        let stored = store_text(store, "def", "one\n", false).await;
+        store.append("def", b"two\n").await.unwrap();
+        assert_eq!(read_text(store, "def").await.unwrap(), "one\ntwo\n");
+        assert_eq!(store.delete("def").await, Some(stored + 4));
+
+        assert_eq!(store.delete("abc").await, Some(replaced));
+        assert!(!store.exists("abc").await.unwrap());
+        let gone = read_text(store, "abc").await.unwrap_err();
+        assert_eq!(gone.kind(), io::ErrorKind::NotFound);
+        assert_eq!(store.read_meta("abc").await.unwrap().map(|_| ()), None);
+        assert_eq!(store.delete("abc").await, None);
+        assert!(store.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn memory_store_keeps_the_contract() {
+        contract(&InMemoryStore::new()).await;
+    }
+
+    #[tokio::test]
+    async fn filesystem_store_keeps_the_contract() {
+        let name = format!(
+            "rbin-store-{}",
+            Alphanumeric.sample_string(&mut rand::thread_rng(), 8)
+        );
+        let dir = std::env::temp_dir().join(name);
+        std::fs::create_dir(&dir).unwrap();
+        contract(&FsStore::new(dir.clone())).await;
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,357 @@
+use super::{content_id, suffix, Content, PasteStore, Staged, Stored, FORMS};
+use crate::meta::PasteMeta;
+use async_trait::async_trait;
+use axum::http::{header, HeaderMap, HeaderValue};
+use rand::distributions::{Alphanumeric, DistString};
+use s3::{creds::Credentials, error::S3Error, serde_types::Object, Bucket, Region};
+use std::{
+    fmt,
+    io::{self, Cursor},
+    path::Path,
+    time::SystemTime,
+};
+
+// --- S3 Store ---
+// Keeps pastes in an S3 compatible bucket (RBIN_STORAGE=s3), so several
+// replicas can serve the same pastes. Objects are named like the files of the
+// filesystem store, `{id}.txt.gz`, `{id}.meta`, `{id}/0.txt`, ..., so a paste
+// directory can be copied into a bucket as is. Content is buffered in memory
+// on its way in and out.
+
+/// Connection settings, see RBIN_S3_*.
+#[derive(Clone)]
+pub struct S3Config {
+    pub bucket: String,
+    pub region: String,
+    /// For services other than AWS, e.g. `http://localhost:9000` for MinIO
+    pub endpoint: Option<String>,
+    /// Access key ID and secret, else taken from the AWS_* variables
+    pub credentials: Option<(String, String)>,
+}
+
+// Leaves out the credentials
+impl fmt::Debug for S3Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Config")
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("endpoint", &self.endpoint)
+            .finish_non_exhaustive()
+    }
+}
+
+pub struct S3Store {
+    bucket: Box<Bucket>,
+    /// Writes only if the object doesn't exist yet, for `reserve`
+    create_only: Bucket,
+}
+
+/// A content object of a key, as found by `S3Store::forms`.
+struct Form {
+    name: String,
+    size: u64,
+    modified: Option<SystemTime>,
+    compressed: bool,
+    encrypted: bool,
+}
+
+impl S3Store {
+    pub fn new(config: &S3Config) -> Result<Self, String> {
+        let region = match &config.endpoint {
+            Some(endpoint) => Region::Custom {
+                region: config.region.clone(),
+                endpoint: endpoint.clone(),
+            },
+            None => config
+                .region
+                .parse()
+                .map_err(|e| format!("invalid region: {}", e))?,
+        };
+        let credentials = match &config.credentials {
+            Some((access_key, secret_key)) => {
+                Credentials::new(Some(access_key), Some(secret_key), None, None, None)
+            }
+            None => Credentials::from_env(),
+        }
+        .map_err(|e| format!("no S3 credentials: {}", e))?;
+        let mut bucket =
+            Bucket::new(&config.bucket, region, credentials).map_err(|e| e.to_string())?;
+        // Other services rarely have a host name per bucket
+        if config.endpoint.is_some() {
+            bucket = bucket.with_path_style();
+        }
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static("*"));
+        let create_only = bucket
+            .with_extra_headers(headers)
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            bucket,
+            create_only,
+        })
+    }
+
+    /// Every object below `prefix`, following continuation tokens.
+    async fn objects(&self, prefix: &str) -> io::Result<Vec<Object>> {
+        let pages = self
+            .bucket
+            .list(prefix.to_string(), None)
+            .await
+            .map_err(failed)?;
+        Ok(pages.into_iter().flat_map(|page| page.contents).collect())
+    }
+
+    /// The objects holding `key`, normally one, in the order of `FORMS`.
+    async fn forms(&self, key: &str) -> io::Result<Vec<Form>> {
+        let objects = self.objects(&format!("{}.txt", key)).await?;
+        Ok(FORMS
+            .iter()
+            .filter_map(|&(suffix, compressed, encrypted)| {
+                let name = format!("{}{}", key, suffix);
+                let object = objects.iter().find(|object| object.key == name)?;
+                Some(Form {
+                    modified: humantime::parse_rfc3339_weak(&object.last_modified).ok(),
+                    size: object.size,
+                    name,
+                    compressed,
+                    encrypted,
+                })
+            })
+            .collect())
+    }
+
+    /// `None` if there's no object called `name`.
+    async fn get_object(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let response = self.bucket.get_object(name).await.map_err(failed)?;
+        match response.status_code() {
+            200 => Ok(Some(response.to_vec())),
+            404 => Ok(None),
+            status => Err(status_error("GET", name, status)),
+        }
+    }
+
+    async fn put_object(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        let response = self.bucket.put_object(name, data).await.map_err(failed)?;
+        match response.status_code() {
+            200..=299 => Ok(()),
+            status => Err(status_error("PUT", name, status)),
+        }
+    }
+
+    /// Succeeds if the object is gone, whether it existed or not.
+    async fn delete_object(&self, name: &str) -> io::Result<()> {
+        let response = self.bucket.delete_object(name).await.map_err(failed)?;
+        match response.status_code() {
+            200..=299 | 404 => Ok(()),
+            status => Err(status_error("DELETE", name, status)),
+        }
+    }
+}
+
+fn failed(e: S3Error) -> io::Error {
+    io::Error::other(e)
+}
+
+fn status_error(method: &str, name: &str, status: u16) -> io::Error {
+    match status {
+        404 => io::ErrorKind::NotFound.into(),
+        _ => io::Error::other(format!(
+            "S3 {} {} failed with status {}",
+            method, name, status
+        )),
+    }
+}
+
+fn meta_name(id: &str) -> String {
+    format!("{}.meta", id)
+}
+
+#[async_trait]
+impl PasteStore for S3Store {
+    fn staging_dir(&self) -> Option<&Path> {
+        None
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Stored> {
+        let form = self
+            .forms(key)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(io::ErrorKind::NotFound)?;
+        // Gone since the listing if `None`
+        let data = self
+            .get_object(&form.name)
+            .await?
+            .ok_or(io::ErrorKind::NotFound)?;
+        Ok(Stored {
+            len: data.len() as u64,
+            content: Content::Memory(Cursor::new(data.into())),
+            compressed: form.compressed,
+            encrypted: form.encrypted,
+            modified: form.modified,
+        })
+    }
+
+    async fn put(&self, key: &str, content: Staged) -> io::Result<u64> {
+        let name = format!("{}{}", key, suffix(content.compressed, content.encrypted));
+        let old = self.forms(key).await?;
+        self.put_object(&name, &content.into_bytes().await?).await?;
+        let mut freed = 0;
+        for form in old {
+            if form.name == name || self.delete_object(&form.name).await.is_ok() {
+                freed += form.size;
+            }
+        }
+        Ok(freed)
+    }
+
+    async fn append(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        // Objects can't be appended to, so this rewrites the whole paste
+        let name = format!("{}{}", key, suffix(false, false));
+        let mut content = self
+            .get_object(&name)
+            .await?
+            .ok_or(io::ErrorKind::NotFound)?;
+        content.extend_from_slice(data);
+        self.put_object(&name, &content).await
+    }
+
+    async fn put_bundle(&self, id: &str, files: Vec<Staged>) -> io::Result<()> {
+        // One after the other, the paste is only shown once its metadata is written
+        for (index, file) in files.into_iter().enumerate() {
+            let name = format!(
+                "{}/{}{}",
+                id,
+                index,
+                suffix(file.compressed, file.encrypted)
+            );
+            self.put_object(&name, &file.into_bytes().await?).await?;
+        }
+        Ok(())
+    }
+
+    async fn exists(&self, id: &str) -> io::Result<bool> {
+        Ok(!self.forms(id).await?.is_empty() || self.is_bundle(id).await)
+    }
+
+    async fn is_bundle(&self, id: &str) -> bool {
+        self.bucket
+            .list_page(format!("{}/", id), None, None, None, Some(1))
+            .await
+            .is_ok_and(|(page, _)| !page.contents.is_empty())
+    }
+
+    async fn bundle_size(&self, id: &str) -> io::Result<u64> {
+        let files = self.objects(&format!("{}/", id)).await?;
+        if files.is_empty() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(files.iter().map(|file| file.size).sum())
+    }
+
+    async fn delete(&self, id: &str) -> Option<u64> {
+        let mut objects: Vec<_> = self
+            .forms(id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|form| (form.name, form.size))
+            .collect();
+        if let Ok(files) = self.objects(&format!("{}/", id)).await {
+            objects.extend(files.into_iter().map(|file| (file.key, file.size)));
+        }
+        let existed = !objects.is_empty();
+        let mut freed = 0;
+        for (name, size) in objects {
+            if self.delete_object(&name).await.is_ok() {
+                freed += size;
+            }
+        }
+        let _ = self.delete_object(&meta_name(id)).await;
+        existed.then_some(freed)
+    }
+
+    async fn reserve(&self, id: &str) -> io::Result<()> {
+        // The empty metadata object is the claim. Services that ignore
+        // `If-None-Match` on writes overwrite it instead, leaving a short window
+        // in which two uploads can end up with the same ID.
+        let name = meta_name(id);
+        let response = self
+            .create_only
+            .put_object(&name, b"")
+            .await
+            .map_err(failed)?;
+        match response.status_code() {
+            200..=299 => {}
+            // 409 while a competing conditional write is in flight
+            412 | 409 => return Err(io::ErrorKind::AlreadyExists.into()),
+            status => return Err(status_error("PUT", &name, status)),
+        }
+        // Pastes from before metadata sidecars existed have no `.meta` to collide with
+        if self.exists(id).await? {
+            let _ = self.delete_object(&name).await;
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        Ok(())
+    }
+
+    async fn read_meta(&self, id: &str) -> io::Result<Option<PasteMeta>> {
+        match self.get_object(&meta_name(id)).await? {
+            Some(data) => serde_json::from_slice(&data)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            None => Ok(None),
+        }
+    }
+
+    async fn write_meta(&self, id: &str, meta: &PasteMeta) -> io::Result<()> {
+        let data =
+            serde_json::to_vec(meta).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.put_object(&meta_name(id), &data).await
+    }
+
+    async fn list(&self) -> io::Result<Vec<String>> {
+        let pages = self
+            .bucket
+            .list(String::new(), Some("/".to_string()))
+            .await
+            .map_err(failed)?;
+        let mut ids = Vec::new();
+        for page in pages {
+            ids.extend(
+                page.contents
+                    .iter()
+                    .filter_map(|object| content_id(&object.key))
+                    .map(str::to_string),
+            );
+            // Multi-file pastes, except for probes and the like
+            ids.extend(
+                page.common_prefixes
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|prefix| prefix.prefix.strip_suffix('/').map(str::to_string))
+                    .filter(|id| !id.starts_with('.')),
+            );
+        }
+        Ok(ids)
+    }
+
+    async fn usage(&self) -> io::Result<u64> {
+        // Bundle files are content objects too, just below `{id}/`
+        Ok(self
+            .objects("")
+            .await?
+            .iter()
+            .filter(|object| !object.key.starts_with('.') && content_id(&object.key).is_some())
+            .map(|object| object.size)
+            .sum())
+    }
+
+    async fn check_writable(&self) -> io::Result<()> {
+        let name = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+        let name = format!(".ready-{}.tmp", name);
+        self.put_object(&name, b"ok").await?;
+        self.delete_object(&name).await
+    }
+}
@@ -0,0 +1,527 @@
+use super::{Content, PasteStore, Staged, Stored};
+use crate::meta::PasteMeta;
+use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use std::{
+    io::{self, Cursor, Read},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// --- SQLite Store ---
+// Keeps pastes in a single SQLite database (RBIN_STORAGE=sqlite, RBIN_DB_PATH),
+// which is backed up by copying one file and updates content and metadata in
+// transactions. Pastes are rows of `pastes`, the files of multi-file pastes
+// rows of `files`. Parts of the metadata are copied into columns so the
+// database can be queried without parsing the JSON. The text of pastes is
+// indexed in `search` for `GET /admin/search`.
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS pastes (
+    id TEXT PRIMARY KEY NOT NULL,
+    -- NULL for multi-file pastes and reserved IDs
+    content BLOB,
+    content_type TEXT,
+    -- Unix time in seconds
+    created_at INTEGER,
+    expires_at INTEGER,
+    flags INTEGER NOT NULL DEFAULT 0,
+    -- Unix time in nanoseconds, so every edit changes the ETag
+    modified_at INTEGER,
+    -- PasteMeta as JSON, NULL until written
+    meta TEXT
+);
+CREATE INDEX IF NOT EXISTS pastes_expires_at ON pastes (expires_at);
+CREATE TABLE IF NOT EXISTS files (
+    paste_id TEXT NOT NULL REFERENCES pastes (id) ON DELETE CASCADE,
+    idx INTEGER NOT NULL,
+    content BLOB NOT NULL,
+    flags INTEGER NOT NULL DEFAULT 0,
+    modified_at INTEGER,
+    PRIMARY KEY (paste_id, idx)
+);
+-- By the key of the content, i.e. `{id}/{index}` for the files of multi-file pastes
+CREATE VIRTUAL TABLE IF NOT EXISTS search USING fts5 (key UNINDEXED, text, tokenize = 'trigram');
+";
+
+// Bits of the `flags` columns
+const COMPRESSED: i64 = 1;
+const ENCRYPTED: i64 = 2;
+const BUNDLE: i64 = 4;
+
+pub struct SqliteStore {
+    // Queries run on the blocking pool, one at a time
+    conn: Arc<Mutex<Connection>>,
+    // Off for pastes encrypted at rest, the index would hold their plaintext
+    indexing: bool,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it and its tables if needed.
+    /// New content is added to the search index if `indexing` is set.
+    pub fn open(path: &Path, indexing: bool) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            indexing,
+        })
+    }
+
+    async fn run<T: Send + 'static>(
+        &self,
+        query: impl FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    ) -> io::Result<T> {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || query(&mut conn.lock().unwrap()))
+            .await
+            .map_err(io::Error::other)?
+            .map_err(io::Error::other)
+    }
+}
+
+/// Splits a `bundle_key` into the paste ID and the file index.
+fn split_key(key: &str) -> (String, Option<i64>) {
+    match key.split_once('/') {
+        Some((id, index)) if index.parse::<i64>().is_ok() => (id.to_string(), index.parse().ok()),
+        _ => (key.to_string(), None),
+    }
+}
+
+fn form_flags(compressed: bool, encrypted: bool) -> i64 {
+    (compressed as i64 * COMPRESSED) | (encrypted as i64 * ENCRYPTED)
+}
+
+/// Replaces the indexed text of `key` with `data`, unless it isn't searchable.
+fn reindex(
+    conn: &Connection,
+    key: &str,
+    data: &[u8],
+    flags: i64,
+    indexing: bool,
+) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM search WHERE key = ?1", [key])?;
+    if let Some(text) = indexing.then(|| searchable_text(data, flags)).flatten() {
+        conn.execute(
+            "INSERT INTO search (key, text) VALUES (?1, ?2)",
+            params![key, text],
+        )?;
+    }
+    Ok(())
+}
+
+/// Drops paste `id` and its files from the search index.
+fn unindex(conn: &Connection, id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM search WHERE key = ?1 OR substr(key, 1, length(?1) + 1) = ?1 || '/'",
+        [id],
+    )?;
+    Ok(())
+}
+
+/// The content as text, `None` if it's encrypted or isn't valid UTF-8.
+fn searchable_text(data: &[u8], flags: i64) -> Option<String> {
+    if flags & ENCRYPTED != 0 {
+        return None;
+    }
+    if flags & COMPRESSED != 0 {
+        let mut text = String::new();
+        GzDecoder::new(data).read_to_string(&mut text).ok()?;
+        return Some(text);
+    }
+    String::from_utf8(data.to_vec()).ok()
+}
+
+fn unix_secs(time: SystemTime) -> Option<i64> {
+    let since = time.duration_since(UNIX_EPOCH).ok()?;
+    i64::try_from(since.as_secs()).ok()
+}
+
+fn unix_nanos(time: SystemTime) -> Option<i64> {
+    let since = time.duration_since(UNIX_EPOCH).ok()?;
+    i64::try_from(since.as_nanos()).ok()
+}
+
+#[async_trait]
+impl PasteStore for SqliteStore {
+    fn staging_dir(&self) -> Option<&Path> {
+        None
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Stored> {
+        let (id, index) = split_key(key);
+        let row = self
+            .run(move |conn| {
+                let row = |row: &rusqlite::Row| {
+                    Ok((
+                        row.get::<_, Vec<u8>>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, Option<i64>>(2)?,
+                    ))
+                };
+                match index {
+                    None => conn
+                        .query_row(
+                            "SELECT content, flags, modified_at FROM pastes
+                             WHERE id = ?1 AND content IS NOT NULL",
+                            [&id],
+                            row,
+                        )
+                        .optional(),
+                    Some(index) => conn
+                        .query_row(
+                            "SELECT content, flags, modified_at FROM files
+                             WHERE paste_id = ?1 AND idx = ?2",
+                            params![id, index],
+                            row,
+                        )
+                        .optional(),
+                }
+            })
+            .await?;
+        let (data, flags, modified) = row.ok_or(io::ErrorKind::NotFound)?;
+        Ok(Stored {
+            len: data.len() as u64,
+            content: Content::Memory(Cursor::new(data.into())),
+            compressed: flags & COMPRESSED != 0,
+            encrypted: flags & ENCRYPTED != 0,
+            modified: modified.map(|nanos| UNIX_EPOCH + Duration::from_nanos(nanos as u64)),
+        })
+    }
+
+    async fn put(&self, key: &str, content: Staged) -> io::Result<u64> {
+        let (id, index) = split_key(key);
+        let key = key.to_string();
+        let indexing = self.indexing;
+        let flags = form_flags(content.compressed, content.encrypted);
+        let data = content.into_bytes().await?.to_vec();
+        let modified = unix_nanos(SystemTime::now());
+        let freed = self
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                let freed: Option<i64> = match index {
+                    None => {
+                        let freed = tx
+                            .query_row(
+                                "SELECT length(content) FROM pastes WHERE id = ?1",
+                                [&id],
+                                |row| row.get(0),
+                            )
+                            .optional()?;
+                        tx.execute(
+                            "INSERT INTO pastes (id, content, flags, modified_at)
+                             VALUES (?1, ?2, ?3, ?4)
+                             ON CONFLICT (id) DO UPDATE SET content = excluded.content,
+                                 flags = excluded.flags, modified_at = excluded.modified_at",
+                            params![id, data, flags, modified],
+                        )?;
+                        freed.flatten()
+                    }
+                    Some(index) => {
+                        let freed = tx
+                            .query_row(
+                                "SELECT length(content) FROM files
+                                 WHERE paste_id = ?1 AND idx = ?2",
+                                params![id, index],
+                                |row| row.get(0),
+                            )
+                            .optional()?;
+                        tx.execute(
+                            "INSERT OR REPLACE INTO files (paste_id, idx, content, flags, modified_at)
+                             VALUES (?1, ?2, ?3, ?4, ?5)",
+                            params![id, index, data, flags, modified],
+                        )?;
+                        freed
+                    }
+                };
+                reindex(&tx, &key, &data, flags, indexing)?;
+                tx.commit()?;
+                Ok(freed.unwrap_or(0))
+            })
+            .await?;
+        Ok(freed as u64)
+    }
+
+    async fn append(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let (id, index) = split_key(key);
+        let key = key.to_string();
+        let indexing = self.indexing;
+        let data = data.to_vec();
+        let modified = unix_nanos(SystemTime::now());
+        let appended = self
+            .run(move |conn| {
+                // Concatenated here, `||` would turn the BLOB into TEXT
+                let tx = conn.transaction()?;
+                let content: Option<Vec<u8>> = match index {
+                    None => tx.query_row(
+                        "SELECT content FROM pastes WHERE id = ?1 AND content IS NOT NULL",
+                        [&id],
+                        |row| row.get(0),
+                    ),
+                    Some(index) => tx.query_row(
+                        "SELECT content FROM files WHERE paste_id = ?1 AND idx = ?2",
+                        params![id, index],
+                        |row| row.get(0),
+                    ),
+                }
+                .optional()?;
+                let Some(mut content) = content else {
+                    return Ok(false);
+                };
+                content.extend_from_slice(&data);
+                match index {
+                    None => tx.execute(
+                        "UPDATE pastes SET content = ?2, modified_at = ?3 WHERE id = ?1",
+                        params![id, content, modified],
+                    )?,
+                    Some(index) => tx.execute(
+                        "UPDATE files SET content = ?3, modified_at = ?4
+                         WHERE paste_id = ?1 AND idx = ?2",
+                        params![id, index, content, modified],
+                    )?,
+                };
+                reindex(&tx, &key, &content, 0, indexing)?;
+                tx.commit()?;
+                Ok(true)
+            })
+            .await?;
+        if !appended {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(())
+    }
+
+    async fn put_bundle(&self, id: &str, files: Vec<Staged>) -> io::Result<()> {
+        let mut contents = Vec::with_capacity(files.len());
+        for file in files {
+            let flags = form_flags(file.compressed, file.encrypted);
+            contents.push((file.into_bytes().await?.to_vec(), flags));
+        }
+        let id = id.to_string();
+        let indexing = self.indexing;
+        let modified = unix_nanos(SystemTime::now());
+        // In one transaction, so the files appear all at once
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO pastes (id, flags, modified_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (id) DO UPDATE SET content = NULL, flags = excluded.flags,
+                     modified_at = excluded.modified_at",
+                params![id, BUNDLE, modified],
+            )?;
+            unindex(&tx, &id)?;
+            for (index, (data, flags)) in contents.into_iter().enumerate() {
+                reindex(&tx, &super::bundle_key(&id, index), &data, flags, indexing)?;
+                tx.execute(
+                    "INSERT OR REPLACE INTO files (paste_id, idx, content, flags, modified_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![id, index as i64, data, flags, modified],
+                )?;
+            }
+            tx.commit()
+        })
+        .await
+    }
+
+    async fn exists(&self, id: &str) -> io::Result<bool> {
+        let id = id.to_string();
+        self.run(move |conn| {
+            conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM pastes
+                 WHERE id = ?1 AND (content IS NOT NULL OR flags & ?2))",
+                params![id, BUNDLE],
+                |row| row.get(0),
+            )
+        })
+        .await
+    }
+
+    async fn is_bundle(&self, id: &str) -> bool {
+        let id = id.to_string();
+        self.run(move |conn| {
+            conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM pastes WHERE id = ?1 AND flags & ?2)",
+                params![id, BUNDLE],
+                |row| row.get(0),
+            )
+        })
+        .await
+        .unwrap_or(false)
+    }
+
+    async fn bundle_size(&self, id: &str) -> io::Result<u64> {
+        let id = id.to_string();
+        let size: Option<Option<i64>> = self
+            .run(move |conn| {
+                conn.query_row(
+                    "SELECT (SELECT SUM(length(content)) FROM files WHERE paste_id = pastes.id)
+                     FROM pastes WHERE id = ?1 AND flags & ?2",
+                    params![id, BUNDLE],
+                    |row| row.get(0),
+                )
+                .optional()
+            })
+            .await?;
+        let size = size.ok_or(io::ErrorKind::NotFound)?;
+        Ok(size.unwrap_or(0) as u64)
+    }
+
+    async fn delete(&self, id: &str) -> Option<u64> {
+        let id = id.to_string();
+        let freed = self
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                let paste: Option<(i64, bool)> = tx
+                    .query_row(
+                        "SELECT IFNULL(length(content), 0)
+                             + IFNULL((SELECT SUM(length(content)) FROM files WHERE paste_id = pastes.id), 0),
+                             content IS NOT NULL OR flags & ?2
+                         FROM pastes WHERE id = ?1",
+                        params![id, BUNDLE],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()?;
+                // Takes the files along
+                tx.execute("DELETE FROM pastes WHERE id = ?1", [&id])?;
+                unindex(&tx, &id)?;
+                tx.commit()?;
+                // Reserved IDs have a row, but no content yet
+                Ok(paste.filter(|(_, existed)| *existed).map(|(freed, _)| freed))
+            })
+            .await;
+        freed.ok().flatten().map(|freed| freed as u64)
+    }
+
+    async fn reserve(&self, id: &str) -> io::Result<()> {
+        // The row without content or metadata is the claim
+        let id = id.to_string();
+        let inserted = self
+            .run(move |conn| {
+                conn.execute(
+                    "INSERT INTO pastes (id) VALUES (?1) ON CONFLICT (id) DO NOTHING",
+                    [&id],
+                )
+            })
+            .await?;
+        if inserted == 0 {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        Ok(())
+    }
+
+    async fn read_meta(&self, id: &str) -> io::Result<Option<PasteMeta>> {
+        let id = id.to_string();
+        let meta: Option<Option<String>> = self
+            .run(move |conn| {
+                conn.query_row("SELECT meta FROM pastes WHERE id = ?1", [&id], |row| {
+                    row.get(0)
+                })
+                .optional()
+            })
+            .await?;
+        match meta {
+            Some(Some(json)) => serde_json::from_str(&json)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            // Like the empty sidecar of the filesystem store
+            Some(None) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the metadata isn't written yet",
+            )),
+            None => Ok(None),
+        }
+    }
+
+    async fn write_meta(&self, id: &str, meta: &PasteMeta) -> io::Result<()> {
+        let json = serde_json::to_string(meta)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let id = id.to_string();
+        let content_type = meta.content_type.clone();
+        let created = meta.created.and_then(unix_secs);
+        let expires = meta.expires.and_then(unix_secs);
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO pastes (id, meta, content_type, created_at, expires_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (id) DO UPDATE SET meta = excluded.meta,
+                     content_type = excluded.content_type, created_at = excluded.created_at,
+                     expires_at = excluded.expires_at",
+                params![id, json, content_type, created, expires],
+            )
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn list(&self) -> io::Result<Vec<String>> {
+        self.run(|conn| {
+            let mut statement =
+                conn.prepare("SELECT id FROM pastes WHERE content IS NOT NULL OR flags & ?1")?;
+            let ids = statement.query_map([BUNDLE], |row| row.get(0))?;
+            ids.collect()
+        })
+        .await
+    }
+
+    async fn usage(&self) -> io::Result<u64> {
+        let used: i64 = self
+            .run(|conn| {
+                conn.query_row(
+                    "SELECT IFNULL((SELECT SUM(length(content)) FROM pastes), 0)
+                         + IFNULL((SELECT SUM(length(content)) FROM files), 0)",
+                    [],
+                    |row| row.get(0),
+                )
+            })
+            .await?;
+        Ok(used as u64)
+    }
+
+    async fn check_writable(&self) -> io::Result<()> {
+        let read_only = self
+            .run(|conn| {
+                // Waits for the write lock like any write would, then lets go again
+                conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                conn.is_readonly(rusqlite::MAIN_DB)
+            })
+            .await?;
+        if read_only {
+            return Err(io::Error::other("the database is read-only"));
+        }
+        Ok(())
+    }
+
+    async fn search(&self, query: &str, limit: usize) -> io::Result<Option<Vec<(String, String)>>> {
+        if !self.indexing {
+            return Ok(None);
+        }
+        // As one phrase, the trigram tokenizer then matches substrings
+        let phrase = format!("\"{}\"", query.replace('"', "\"\""));
+        let rows: Vec<(String, String)> = self
+            .run(move |conn| {
+                let mut statement = conn.prepare(
+                    "SELECT key, snippet(search, 1, '', '', '…', 64) FROM search
+                     WHERE search MATCH ?1 ORDER BY rank LIMIT ?2",
+                )?;
+                let rows = statement.query_map(params![phrase, limit as i64], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?;
+                rows.collect()
+            })
+            .await?;
+        // Several files of a multi-file paste may match
+        let mut matches: Vec<(String, String)> = Vec::new();
+        for (key, snippet) in rows {
+            let (id, _) = split_key(&key);
+            if !matches.iter().any(|(other, _)| *other == id) {
+                matches.push((id, snippet.replace(|c: char| c.is_control(), " ")));
+            }
+        }
+        Ok(Some(matches))
+    }
+}
@@ -0,0 +1,55 @@
+use serde::Serialize;
+use std::time::{Duration, SystemTime};
+
+// Slow receivers must not pile up pending requests
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// --- Webhook ---
+// POSTs a JSON notification to RBIN_WEBHOOK_URL for every new paste, e.g. for
+// a Slack or Discord bridge. Delivery is best-effort: it happens in the
+// background after the paste is stored, and failures are only logged.
+#[derive(Debug)]
+pub struct Webhook {
+    client: reqwest::Client,
+    url: String,
+}
+
+/// Body of a notification.
+#[derive(Serialize)]
+struct PasteCreated<'a> {
+    id: &'a str,
+    url: &'a str,
+    size: u64,
+    /// RFC3339
+    created: String,
+}
+
+impl Webhook {
+    pub fn new(url: String) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("rbin/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self { client, url })
+    }
+
+    /// Sends the notification for a new paste of `size` bytes. Returns right
+    /// away, the request runs in the background.
+    pub fn paste_created(&self, id: &str, url: &str, size: u64, created: SystemTime) {
+        let body = PasteCreated {
+            id,
+            url,
+            size,
+            created: humantime::format_rfc3339_seconds(created).to_string(),
+        };
+        let request = self.client.post(&self.url).json(&body);
+        let id = id.to_string();
+        tokio::spawn(async move {
+            match request.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => tracing::debug!("Sent webhook for paste {}", id),
+                Err(e) => tracing::warn!("Webhook for paste {} failed: {}", id, e.without_url()),
+            }
+        });
+    }
+}
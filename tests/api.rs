@@ -0,0 +1,943 @@
+use rbin::{AppState, Config, Source};
+use reqwest::{header, Client, StatusCode};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+// --- Test Server ---
+// Every test gets its own rbin router, served in-process on a free port, with
+// pastes kept in memory. Only `rbin --check`, the signal handling and failed
+// startups run the binary.
+
+static SERVERS: AtomicUsize = AtomicUsize::new(0);
+
+struct Server {
+    url: String,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Server {
+    async fn start() -> Self {
+        Self::start_with(&[]).await
+    }
+
+    /// Starts a server with some more settings, e.g. `("max_lines", "1")`.
+    async fn start_with(settings: &[(&str, &str)]) -> Self {
+        Self::with_config(config(settings)).await
+    }
+
+    async fn with_config(config: Config) -> Self {
+        let state = AppState::new(config).await.unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        // Connection info provides the client address, as in `rbin::run`
+        let service = rbin::router(state).into_make_service_with_connect_info::<SocketAddr>();
+        let task = tokio::spawn(async move {
+            axum::serve(listener, service).await.unwrap();
+        });
+        Server { url, task }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The configuration of a test server: pastes in memory, and `settings` by
+/// key. The environment doesn't apply.
+fn config(settings: &[(&str, &str)]) -> Config {
+    let mut settings: HashMap<String, String> = settings
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    settings
+        .entry("storage".to_string())
+        .or_insert_with(|| "memory".to_string());
+    Config::from_source(&Source::from_map(settings)).unwrap()
+}
+
+/// Runs `rbin --check` with `settings` and more `args` in a fresh directory,
+/// returning whether it passed and its report.
+fn check(settings: &[(&str, &str)], args: &[&str]) -> (bool, String) {
+    let dir = std::env::temp_dir().join(format!(
+        "rbin-check-{}-{}",
+        std::process::id(),
+        SERVERS.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rbin"));
+    for (key, _) in std::env::vars().filter(|(key, _)| key.starts_with("RBIN_")) {
+        command.env_remove(key);
+    }
+    let output = command
+        .arg("--check")
+        .args(args)
+        .envs(settings.iter().copied())
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    )
+}
+
+async fn post(server: &Server, content: &'static str) -> reqwest::Response {
+    Client::new()
+        .post(server.url("/"))
+        .body(content)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn creates_and_retrieves_paste() {
+    let server = Server::start().await;
+    let response = post(&server, "Hello, rbin!").await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let url = response.text().await.unwrap();
+    assert!(url.starts_with(&server.url), "unexpected URL {}", url);
+
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "Hello, rbin!");
+}
+
+#[tokio::test]
+async fn upload_answers_with_the_exact_url() {
+    let server = Server::start().await;
+    let exact_url = |body: &[u8]| {
+        let text = std::str::from_utf8(body).unwrap();
+        let id = text.trim_end_matches('\n').rsplit('/').next().unwrap();
+        assert!(id.chars().all(|c| c.is_ascii_alphanumeric()), "{:?}", text);
+        format!("{}/{}", server.url, id)
+    };
+
+    let body = post(&server, "exact").await.bytes().await.unwrap();
+    assert_eq!(body, exact_url(&body).as_bytes());
+
+    let body = Client::new()
+        .post(server.url("/?newline=1"))
+        .body("exact")
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(body, format!("{}\n", exact_url(&body)).as_bytes());
+}
+
+#[tokio::test]
+async fn batch_returns_each_paste_or_its_error() {
+    let mut config = config(&[]);
+    config.max_batch = 3;
+    let server = Server::with_config(config).await;
+    let url = post(&server, "first").await.text().await.unwrap();
+    let id = url.rsplit('/').next().unwrap();
+    let batch = |body: String| {
+        Client::new()
+            .post(server.url("/batch"))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+    };
+
+    let response = batch(format!(r#"["{}", "missing"]"#, id)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let pastes: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(pastes[id]["content"], "first");
+    assert_eq!(pastes["missing"]["code"], "NOT_FOUND");
+
+    let response = batch(r#"["a", "b", "c", "d"]"#.to_string()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let response = batch(r#"{"ids": []}"#.to_string()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Posts `content`, returning its URL and append token.
+async fn post_appendable(server: &Server, content: &'static str) -> (String, String) {
+    let response = Client::new()
+        .post(server.url("/"))
+        .header(header::ACCEPT, "application/json")
+        .body(content)
+        .send()
+        .await
+        .unwrap();
+    let paste: serde_json::Value = response.json().await.unwrap();
+    let url = paste["url"].as_str().unwrap().to_string();
+    (url, paste["append_token"].as_str().unwrap().to_string())
+}
+
+async fn append(url: &str, token: &str, content: &'static str) -> reqwest::Response {
+    Client::new()
+        .post(format!("{}/append", url))
+        .header("X-Append-Token", token)
+        .body(content)
+        .send()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn appends_are_held_to_the_line_limits() {
+    let server = Server::start_with(&[("max_lines", "2"), ("max_line_length", "5")]).await;
+    let (url, token) = post_appendable(&server, "a\n").await;
+    assert_eq!(append(&url, &token, "bc").await.status(), StatusCode::OK);
+    // Continues the last line
+    let response = append(&url, &token, "def!").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let message = response.text().await.unwrap();
+    assert!(
+        message.contains("longer than the maximum of 5"),
+        "{}",
+        message
+    );
+    let response = append(&url, &token, "\nc").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let message = response.text().await.unwrap();
+    assert!(message.contains("maximum of 2 lines"), "{}", message);
+
+    let raw = reqwest::get(format!("{}/raw", url)).await.unwrap();
+    assert_eq!(raw.text().await.unwrap(), "a\nbc");
+}
+
+#[tokio::test]
+async fn appends_are_rate_limited() {
+    let server = Server::start_with(&[("rate_limit", "2/hour")]).await;
+    let (url, token) = post_appendable(&server, "first\n").await;
+    assert_eq!(
+        append(&url, &token, "second\n").await.status(),
+        StatusCode::OK
+    );
+    let response = append(&url, &token, "third\n").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn listed_pastes_have_a_preview() {
+    let server = Server::start_with(&[("admin_token", "secret")]).await;
+    let long = "x".repeat(300);
+    let short_url = post(&server, "Line one\nline two")
+        .await
+        .text()
+        .await
+        .unwrap();
+    let long_url = Client::new()
+        .post(server.url("/"))
+        .body(long.clone())
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let response = Client::new()
+        .get(server.url("/admin/pastes"))
+        .header("X-Admin-Token", "secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let list: serde_json::Value = response.json().await.unwrap();
+    let preview = |url: &str| {
+        let id = url.rsplit('/').next().unwrap();
+        let pastes = list["pastes"].as_array().unwrap();
+        let paste = pastes.iter().find(|paste| paste["id"] == id).unwrap();
+        paste["preview"].as_str().unwrap().to_string()
+    };
+    assert_eq!(preview(&short_url), "Line one line two");
+    assert_eq!(preview(&long_url), format!("{}…", &long[..200]));
+}
+
+#[tokio::test]
+async fn raw_view_ignores_accept() {
+    let server = Server::start().await;
+    let url = post(&server, "<b>raw</b>").await.text().await.unwrap();
+    let response = Client::new()
+        .get(format!("{}/raw", url))
+        .header(header::ACCEPT, "text/html")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap();
+    assert!(content_type.starts_with("text/plain"), "{}", content_type);
+    assert_eq!(response.text().await.unwrap(), "<b>raw</b>");
+}
+
+#[tokio::test]
+async fn unchanged_paste_is_not_modified_since() {
+    let server = Server::start().await;
+    let url = post(&server, "Polled").await.text().await.unwrap();
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(
+        response.headers()[header::CACHE_CONTROL],
+        "public, max-age=60, must-revalidate"
+    );
+    let last_modified = response.headers()[header::LAST_MODIFIED].clone();
+
+    let client = Client::new();
+    let response = client
+        .get(&url)
+        .header(header::IF_MODIFIED_SINCE, last_modified.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    let response = client
+        .get(&url)
+        .header(header::IF_MODIFIED_SINCE, "Sat, 01 Jan 2000 00:00:00 GMT")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    // A stale ETag wins over the date
+    let response = client
+        .get(&url)
+        .header(header::IF_MODIFIED_SINCE, last_modified)
+        .header(header::IF_NONE_MATCH, "\"stale\"")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn head_and_revalidation_leave_views_alone() {
+    let server = Server::start().await;
+    let client = Client::new();
+    let url = client
+        .post(server.url("/"))
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body("rbin=once&max_views=1")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    let response = client.head(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()[header::ETAG].clone();
+    let response = client
+        .get(&url)
+        .header(header::IF_NONE_MATCH, etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    // The one view is still there, and then used up
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "once");
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn missing_paste_is_not_found() {
+    let server = Server::start().await;
+    let response = reqwest::get(server.url("/doesnotexist")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn highlight_theme_can_be_picked() {
+    let server = Server::start().await;
+    let url = post(&server, "fn main() {}").await.text().await.unwrap();
+    let response = reqwest::get(format!("{}.rs?theme=light", url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("body { background: #eff1f5;"), "{}", page);
+
+    let response = reqwest::get(format!("{}.rs?theme=neon", url))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn help_is_compressed_when_supported() {
+    let server = Server::start().await;
+    let response = Client::new()
+        .get(server.url("/"))
+        .header(header::ACCEPT_ENCODING, "br")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+}
+
+#[tokio::test]
+async fn large_pastes_are_gzipped_when_accepted() {
+    use std::io::Read;
+
+    let server = Server::start().await;
+    let content = "A line of a log file that compresses well.\n".repeat(100);
+    let url = Client::new()
+        .post(server.url("/"))
+        .body(content.clone())
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    // Without decompression, so the encoding stays visible
+    let client = Client::builder().no_gzip().build().unwrap();
+
+    let response = client
+        .get(&url)
+        .header(header::ACCEPT_ENCODING, "gzip")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+    let compressed = response.bytes().await.unwrap();
+    assert!(compressed.len() < content.len());
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(&compressed[..])
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, content);
+
+    let response = client.get(&url).send().await.unwrap();
+    assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+    assert_eq!(response.text().await.unwrap(), content);
+}
+
+#[tokio::test]
+async fn unsupported_method_lists_allowed_ones() {
+    let server = Server::start().await;
+    let response = Client::new().delete(server.url("/")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(response.headers()[header::ALLOW], "GET,HEAD,POST");
+    let message = response.text().await.unwrap();
+    assert!(
+        message.starts_with("Method DELETE is not allowed"),
+        "{}",
+        message
+    );
+}
+
+#[tokio::test]
+async fn favicon_is_not_a_paste() {
+    let server = Server::start().await;
+    let response = reqwest::get(server.url("/favicon.ico")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "image/x-icon");
+    assert!(!response.bytes().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn crawlers_are_kept_away_by_default() {
+    let server = Server::start().await;
+    let response = reqwest::get(server.url("/robots.txt")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "User-agent: *\nDisallow: /\n"
+    );
+
+    let url = post(&server, "secret").await.text().await.unwrap();
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.headers()["x-robots-tag"], "noindex, nofollow");
+}
+
+#[tokio::test]
+async fn crawlers_may_index_when_allowed() {
+    let server = Server::start_with(&[("robots", "allow")]).await;
+    let response = reqwest::get(server.url("/robots.txt")).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "User-agent: *\nDisallow:\n");
+
+    let url = post(&server, "public").await.text().await.unwrap();
+    let response = reqwest::get(&url).await.unwrap();
+    assert!(!response.headers().contains_key("x-robots-tag"));
+}
+
+#[tokio::test]
+async fn read_only_server_rejects_writes() {
+    let server = Server::start_with(&[("read_only", "1")]).await;
+    let response = post(&server, "new").await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let message = response.text().await.unwrap();
+    assert!(message.contains("read-only"), "{}", message);
+
+    let client = Client::new();
+    let response = client
+        .put(server.url("/my-notes"))
+        .body("notes")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.delete(server.url("/my-notes")).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn responses_carry_security_headers() {
+    let server = Server::start().await;
+    let url = post(&server, "<script>alert(1)</script>")
+        .await
+        .text()
+        .await
+        .unwrap();
+    for url in [url, server.url("/health")] {
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+        assert_eq!(response.headers()["referrer-policy"], "no-referrer");
+    }
+
+    let server = Server::start_with(&[(
+        "extra_headers",
+        "Content-Security-Policy: default-src 'none'; img-src 'self'|Referrer-Policy:",
+    )])
+    .await;
+    let response = reqwest::get(server.url("/")).await.unwrap();
+    assert_eq!(
+        response.headers()["content-security-policy"],
+        "default-src 'none'; img-src 'self'"
+    );
+    assert_eq!(response.headers()["x-content-type-options"], "nosniff");
+    assert!(!response.headers().contains_key("referrer-policy"));
+}
+
+#[tokio::test]
+async fn empty_paste_is_rejected() {
+    let server = Server::start().await;
+    let response = post(&server, "").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let message = response.text().await.unwrap();
+    assert!(
+        message.starts_with("Paste content cannot be empty"),
+        "{}",
+        message
+    );
+}
+
+#[tokio::test]
+async fn empty_paste_is_stored_when_allowed() {
+    let server = Server::start_with(&[("allow_empty", "1")]).await;
+    let response = post(&server, "").await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let url = response.text().await.unwrap();
+
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "");
+}
+
+#[tokio::test]
+async fn field_name_can_be_changed() {
+    let server = Server::start_with(&[("field_name", "content")]).await;
+    let client = Client::new();
+    let response = client
+        .post(server.url("/"))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("content=Hello%20there&expires=1h")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let url = response.text().await.unwrap();
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "Hello there");
+
+    // A file of another field is taken when there's none of the configured name
+    let body = "--b\r\n\
+        Content-Disposition: form-data; name=\"expires\"\r\n\r\n1h\r\n\
+        --b\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"notes.txt\"\r\n\r\n\
+        From a file\r\n\
+        --b--\r\n";
+    let response = client
+        .post(server.url("/"))
+        .header("Content-Type", "multipart/form-data; boundary=b")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let url = response.text().await.unwrap();
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "From a file");
+
+    let help = reqwest::get(server.url("/")).await.unwrap();
+    let help = help.text().await.unwrap();
+    assert!(help.contains("curl -F 'content=<-'"), "{}", help);
+    assert!(help.contains("Send several 'content' fields"), "{}", help);
+    assert!(!help.contains("'rbin"), "{}", help);
+}
+
+#[tokio::test]
+async fn stalled_upload_times_out() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let server = Server::start_with(&[("request_timeout", "1")]).await;
+    let addr = server.url.trim_start_matches("http://");
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    // Promises more than it sends, then waits
+    stream
+        .write_all(b"POST / HTTP/1.1\r\nHost: rbin\r\nContent-Length: 100\r\n\r\nstart")
+        .await
+        .unwrap();
+    let mut response = vec![0; 1024];
+    let read = tokio::time::timeout(Duration::from_secs(10), stream.read(&mut response))
+        .await
+        .expect("upload wasn't timed out")
+        .unwrap();
+    let response = String::from_utf8_lossy(&response[..read]);
+    assert!(
+        response.starts_with("HTTP/1.1 408 Request Timeout"),
+        "{}",
+        response
+    );
+}
+
+#[tokio::test]
+async fn oldest_paste_is_evicted_over_the_limit() {
+    let server = Server::start_with(&[("max_pastes", "2")]).await;
+    let response = Client::new()
+        .post(server.url("/"))
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body("rbin=burn&max_views=5")
+        .send()
+        .await
+        .unwrap();
+    let kept = response.text().await.unwrap();
+    let first = post(&server, "first").await.text().await.unwrap();
+    let second = post(&server, "second").await.text().await.unwrap();
+
+    let status = |url: String| async move { reqwest::get(url).await.unwrap().status() };
+    assert_eq!(status(first).await, StatusCode::NOT_FOUND);
+    assert_eq!(status(second).await, StatusCode::OK);
+    assert_eq!(status(kept).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn uuid_ids_can_be_generated() {
+    let server = Server::start_with(&[("id_scheme", "uuid")]).await;
+    let url = post(&server, "unique").await.text().await.unwrap();
+    let id = url.rsplit('/').next().unwrap();
+    let groups: Vec<usize> = id.split('-').map(str::len).collect();
+    assert_eq!(groups, [8, 4, 4, 4, 12], "{}", id);
+    assert_eq!(&id[14..15], "4", "{}", id);
+    assert!(
+        id.chars().all(|c| c == '-' || c.is_ascii_hexdigit()),
+        "{}",
+        id
+    );
+
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "unique");
+}
+
+#[tokio::test]
+async fn timestamp_ids_sort_by_age() {
+    let server = Server::start_with(&[("id_scheme", "timestamp")]).await;
+    let mut ids = Vec::new();
+    for content in ["first", "second", "third"] {
+        let url = post(&server, content).await.text().await.unwrap();
+        ids.push(url.rsplit('/').next().unwrap().to_string());
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert!(ids.iter().all(|id| id.len() == 14), "{:?}", ids);
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!(ids, sorted);
+
+    let response = reqwest::get(server.url(&format!("/{}", ids[1])))
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "second");
+}
+
+#[cfg(unix)]
+#[test]
+fn sigterm_stops_the_server_cleanly() {
+    use std::{process::Stdio, time::Instant};
+
+    let dir = std::env::temp_dir().join(format!(
+        "rbin-signal-{}-{}",
+        std::process::id(),
+        SERVERS.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let port_file = dir.join("port");
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rbin"));
+    for (key, _) in std::env::vars().filter(|(key, _)| key.starts_with("RBIN_")) {
+        command.env_remove(key);
+    }
+    let mut child = command
+        .current_dir(&dir)
+        .env("RBIN_HOST", "127.0.0.1")
+        .env("RBIN_PORT", "0")
+        .env("RBIN_PORT_FILE", &port_file)
+        .env("RBIN_STORAGE", "memory")
+        .env("RUST_LOG", "off")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let started = Instant::now();
+    while !port_file.exists() {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "rbin didn't start"
+        );
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    let killed = Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if started.elapsed() > Duration::from_secs(20) {
+            let _ = child.kill();
+            panic!("rbin didn't stop on SIGTERM");
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    assert_eq!(status.code(), Some(0));
+    assert!(!port_file.exists(), "the port file is left behind");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Runs the binary with `settings` until it gives up, for its exit status and
+/// what it printed to stderr.
+fn failed_start(settings: &[(&str, &str)]) -> (std::process::ExitStatus, String) {
+    use std::{process::Stdio, time::Instant};
+
+    let dir = std::env::temp_dir().join(format!(
+        "rbin-start-{}-{}",
+        std::process::id(),
+        SERVERS.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rbin"));
+    for (key, _) in std::env::vars().filter(|(key, _)| key.starts_with("RBIN_")) {
+        command.env_remove(key);
+    }
+    let mut child = command
+        .current_dir(&dir)
+        .env("RBIN_HOST", "127.0.0.1")
+        .env("RBIN_STORAGE", "memory")
+        .env("RUST_LOG", "off")
+        .envs(settings.iter().copied())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let started = Instant::now();
+    while child.try_wait().unwrap().is_none() {
+        if started.elapsed() > Duration::from_secs(10) {
+            let _ = child.kill();
+            panic!("rbin started with {:?}", settings);
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let output = child.wait_with_output().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    (
+        output.status,
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn failed_startup_exits_with_an_error() {
+    let (status, stderr) = failed_start(&[("RBIN_TLS_CERT", "cert.pem")]);
+    assert_eq!(status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("RBIN_TLS_KEY"), "{}", stderr);
+
+    let tls = [("RBIN_TLS_CERT", "cert.pem"), ("RBIN_TLS_KEY", "key.pem")];
+    let (status, stderr) = failed_start(&tls);
+    assert_eq!(status.code(), Some(1), "{}", stderr);
+    assert!(
+        stderr.contains("Could not load the TLS certificate"),
+        "{}",
+        stderr
+    );
+
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = taken.local_addr().unwrap().port().to_string();
+    let (status, stderr) = failed_start(&[("RBIN_PORT", &port)]);
+    assert_eq!(status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("Could not bind"), "{}", stderr);
+}
+
+#[test]
+fn check_passes_for_a_working_setup() {
+    let (passed, report) = check(&[], &[]);
+    assert!(passed, "{}", report);
+    assert!(report.contains("ok    Paste directory: "), "{}", report);
+    assert!(report.ends_with("All checks passed\n"), "{}", report);
+}
+
+#[test]
+fn check_fails_for_bad_settings() {
+    let (passed, report) = check(&[("RBIN_PORT", "http")], &[]);
+    assert!(!passed, "{}", report);
+    assert!(report.starts_with("FAIL  Configuration: "), "{}", report);
+
+    let (passed, report) = check(
+        &[
+            ("RBIN_TLS_CERT", "missing.pem"),
+            ("RBIN_TLS_KEY", "missing.key"),
+        ],
+        &[],
+    );
+    assert!(!passed, "{}", report);
+    assert!(report.contains("FAIL  TLS: "), "{}", report);
+}
+
+#[test]
+fn command_line_overrides_environment() {
+    let (passed, report) = check(
+        &[("RBIN_PORT", "http"), ("RBIN_MAX_LINES", "0")],
+        &["--port", "8080", "--set", "max_lines=1000"],
+    );
+    assert!(passed, "{}", report);
+    assert!(report.contains("ok    Listen: 0.0.0.0:8080"), "{}", report);
+
+    let (passed, report) = check(&[], &["--set", "max_lines=none"]);
+    assert!(!passed, "{}", report);
+    assert!(
+        report.contains("Invalid --set max_lines 'none'"),
+        "{}",
+        report
+    );
+}
+
+#[tokio::test]
+async fn ids_are_held_to_the_alphabet() {
+    let server = Server::start_with(&[("id_alphabet", "base58")]).await;
+    let url = post(&server, "unambiguous").await.text().await.unwrap();
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "unambiguous");
+
+    // `0` isn't part of base58, `-` is always allowed
+    let response = reqwest::get(server.url("/a0cdef")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = reqwest::get(server.url("/abc-def")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let client = Client::new();
+    let response = client
+        .put(server.url("/notes-0"))
+        .body("notes")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let text = response.text().await.unwrap();
+    assert_eq!(
+        text.lines().next().unwrap(),
+        "Invalid paste ID: use 1-64 characters from a-k, m-z, A-H, J-N, P-Z, 1-9, '-' and '_'"
+    );
+    let help = client.get(server.url("/")).send().await.unwrap();
+    let help = help.text().await.unwrap();
+    assert!(help.contains("Choose your own ID (a-k, m-z, A-H, J-N, P-Z, 1-9, '-' and '_')"));
+}
+
+#[tokio::test]
+async fn invalid_id_is_rejected() {
+    let server = Server::start().await;
+    let response = reqwest::get(server.url("/bad%21id")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = Client::new()
+        .put(server.url("/bad%21id"))
+        .body("content")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn path_traversal_is_rejected() {
+    let server = Server::start().await;
+    let client = Client::new();
+    for id in [
+        "..%2F..%2Fetc%2Fpasswd",
+        "%2E%2E%2Fescape",
+        "a%5Cb",
+        "a%00b",
+    ] {
+        for path in ["", "/raw", "/info", "/download"] {
+            let response = reqwest::get(server.url(&format!("/{}{}", id, path)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}{}", id, path);
+        }
+        let response = client
+            .put(server.url(&format!("/{}", id)))
+            .body("content")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "PUT {}", id);
+        let response = client
+            .delete(server.url(&format!("/{}", id)))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "DELETE {}", id);
+    }
+}
+
+#[tokio::test]
+async fn custom_id_can_only_be_taken_once() {
+    let server = Server::start().await;
+    let put = || {
+        Client::new()
+            .put(server.url("/my-notes"))
+            .body("notes")
+            .send()
+    };
+    assert_eq!(put().await.unwrap().status(), StatusCode::CREATED);
+    assert_eq!(put().await.unwrap().status(), StatusCode::CONFLICT);
+    let response = reqwest::get(server.url("/my-notes")).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "notes");
+}
+
+#[tokio::test]
+async fn errors_are_json_when_preferred() {
+    let server = Server::start().await;
+    let response = Client::new()
+        .post(server.url("/"))
+        .header(header::ACCEPT, "application/json")
+        .body("")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "EMPTY_BODY");
+    assert_eq!(error["error"], "Paste content cannot be empty");
+}
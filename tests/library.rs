@@ -0,0 +1,40 @@
+use reqwest::StatusCode;
+use std::{collections::HashMap, net::SocketAddr};
+
+// rbin mounted under a path of another app, as a library. The settings come
+// from a map rather than the environment of the process.
+
+#[tokio::test]
+async fn serves_pastes_nested_under_a_path() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/paste", listener.local_addr().unwrap());
+    let settings = HashMap::from([
+        ("storage".to_string(), "memory".to_string()),
+        ("base_url".to_string(), url.clone()),
+    ]);
+    let config = rbin::Config::from_source(&rbin::Source::from_map(settings)).unwrap();
+
+    let state = rbin::AppState::new(config).await.unwrap();
+    let app = axum::Router::new()
+        .route("/", axum::routing::get(|| async { "host app" }))
+        .nest("/paste", rbin::router(state));
+    tokio::spawn(async move {
+        let service = app.into_make_service_with_connect_info::<SocketAddr>();
+        axum::serve(listener, service).await.unwrap();
+    });
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .body("nested")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let paste_url = response.text().await.unwrap();
+    assert!(paste_url.starts_with(&url), "unexpected URL {}", paste_url);
+
+    let response = reqwest::get(format!("{}/raw", paste_url)).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "nested");
+    let response = reqwest::get(url.replace("/paste", "/")).await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "host app");
+}